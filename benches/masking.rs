@@ -0,0 +1,36 @@
+// Measures how many codes per second can have their mask chosen
+// Run with cargo bench, add --features rayon to evaluate the masks in parallel
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use qr_generator::builder::QrBuilder;
+
+const PAYLOAD: &str = "https://example.com/track?id=4f9c2a7e-81b3-4d6a-9e0f-5c1d2b3a4e5f&utm_source=email";
+
+fn masking(c: &mut Criterion) {
+    let mut group = c.benchmark_group("apply_masking");
+    group.throughput(Throughput::Elements(1));
+    for version in [10, 25, 40] {
+        // Applying mask 0 a second time leaves the code unmasked
+        let mut unmasked = QrBuilder::new(PAYLOAD).version(version).mask(0).build().unwrap();
+        unmasked.apply_mask(0);
+        group.bench_with_input(BenchmarkId::from_parameter(version),&unmasked,|b, unmasked| {
+            b.iter_batched(|| unmasked.clone(),|mut qr| qr.apply_masking(),BatchSize::SmallInput);
+        });
+    }
+    group.finish();
+}
+
+fn build(c: &mut Criterion) {
+    let mut group = c.benchmark_group("build");
+    group.throughput(Throughput::Elements(1));
+    for version in [10, 25, 40] {
+        let builder = QrBuilder::new(PAYLOAD).version(version);
+        group.bench_with_input(BenchmarkId::from_parameter(version),&builder,|b, builder| {
+            b.iter(|| builder.build().unwrap());
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, masking, build);
+criterion_main!(benches);
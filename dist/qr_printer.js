@@ -1,5 +1,13 @@
 function print_qr_code(str) {
-    let data = make_qr(str);
+    let data;
+    try {
+        data = make_qr(str);
+    }
+    catch(err) {
+        // Message is too long or cannot be encoded
+        console.error(err.message);
+        return;
+    }
     let width = Math.round(Math.sqrt(data.length));
     const canvas = document.getElementById("qr_canvas");
     canvas.width = width;
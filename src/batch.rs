@@ -0,0 +1,390 @@
+use crate::{parse_ec_level, parse_length, write_output, Format, Style, Summary};
+use qr_generator::bits;
+use qr_generator::builder::QrBuilder;
+use qr_generator::error_correction::ECLevel;
+use qr_generator::print::Length;
+use qr_generator::qr::QR;
+use qr_generator::segment::Segment;
+use clap::{Args, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// Arguments of the batch subcommand
+#[derive(Args,Debug)]
+pub struct BatchArgs {
+    /// CSV file with a header row or JSON Lines file, one code per row, or - for stdin
+    /// Rows have a payload and a filename, and optionally ec, version, foreground and background
+    manifest: String,
+    /// Format of the manifest, taken from its extension if not given and CSV for stdin
+    #[arg(long, value_enum)]
+    manifest_format: Option<ManifestFormat>,
+    /// Directory to write the codes to, or a .zip or .tar archive
+    #[arg(short, long)]
+    output: String,
+    /// Format of rows whose filename has no known extension
+    #[arg(short, long, value_enum, default_value_t = Format::Png)]
+    format: Format,
+    /// Error correction level of rows that do not set one
+    #[arg(long, default_value = "M", value_parser = parse_ec_level)]
+    ec: ECLevel,
+    /// Width of the light border around each code in modules
+    #[arg(long, default_value_t = 4)]
+    quiet_zone: u32,
+    /// Size of a module in pixels
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u32).range(1..))]
+    scale: u32,
+    /// Size of a module in pdf and eps, in mm or pt such as 0.5mm or 1.5pt
+    #[arg(long, default_value = "0.5mm", value_parser = parse_length)]
+    module_size: Length,
+    /// Margin added around the quiet zone in pdf and eps for the printer to cut into, in mm or pt
+    #[arg(long, default_value = "0pt", value_parser = parse_length)]
+    bleed: Length,
+    /// Name of the summary report written next to the codes, as CSV or as JSON if it ends in .json
+    #[arg(long, default_value = "report.csv")]
+    report: String,
+}
+
+/// Arguments of the preview subcommand
+#[derive(Args,Debug)]
+pub struct PreviewArgs {
+    /// CSV file with a header row or JSON Lines file, in the same layout as for batch, or - for stdin
+    manifest: String,
+    /// Format of the manifest, taken from its extension if not given and CSV for stdin
+    #[arg(long, value_enum)]
+    manifest_format: Option<ManifestFormat>,
+    /// How to draw the codes, auto uses Kitty graphics or Sixel when the terminal is known to support them
+    #[arg(long, value_enum, default_value_t = Protocol::Auto)]
+    protocol: Protocol,
+    /// Error correction level of rows that do not set one
+    #[arg(long, default_value = "M", value_parser = parse_ec_level)]
+    ec: ECLevel,
+    /// Width of the light border around each code in modules
+    #[arg(long, default_value_t = 4)]
+    quiet_zone: u32,
+    /// Size of a module in pixels for Kitty graphics and Sixel
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..))]
+    scale: u32,
+    /// Swap dark and light in utf8 and ansi, for terminals with light text on a dark background
+    #[arg(long)]
+    invert: bool,
+}
+
+#[derive(ValueEnum,Copy,Clone,Debug,PartialEq,Eq)]
+pub enum Protocol {
+    Auto,
+    Kitty,
+    Sixel,
+    /// Unicode half blocks
+    Utf8,
+    /// ANSI background colors
+    Ansi,
+}
+
+impl Protocol {
+    /// Returns the output format of the protocol, guessing from the environment for auto
+    fn format(&self) -> Format {
+        return match self {
+            Protocol::Auto => {
+                let term = std::env::var("TERM").unwrap_or_default();
+                let term_program = std::env::var("TERM_PROGRAM").unwrap_or_default();
+                if std::env::var_os("KITTY_WINDOW_ID").is_some() || term.contains("kitty") || term_program == "ghostty" || term_program == "WezTerm" {
+                    Format::Kitty
+                }
+                else if term.contains("sixel") || term.starts_with("foot") || term.starts_with("mlterm") {
+                    Format::Sixel
+                }
+                else {
+                    Format::Utf8
+                }
+            },
+            Protocol::Kitty => Format::Kitty,
+            Protocol::Sixel => Format::Sixel,
+            Protocol::Utf8 => Format::Utf8,
+            Protocol::Ansi => Format::Ansi,
+        };
+    }
+}
+
+#[derive(ValueEnum,Copy,Clone,Debug,PartialEq,Eq)]
+pub enum ManifestFormat {
+    Csv,
+    /// One JSON object per line
+    Jsonl,
+}
+
+/// Row of the manifest
+#[derive(Deserialize,Debug,Clone,PartialEq)]
+struct Row {
+    payload: String,
+    filename: String,
+    #[serde(default)]
+    ec: Option<String>,
+    #[serde(default)]
+    version: Option<u8>,
+    /// Color of the dark modules as #rrggbb or #rrggbbaa
+    #[serde(default)]
+    foreground: Option<String>,
+    /// Color of the light modules as #rrggbb or #rrggbbaa
+    #[serde(default)]
+    background: Option<String>,
+}
+
+/// Row of the summary report, fields other than filename are empty if the code could not be made
+#[derive(Serialize,Debug,Clone,PartialEq)]
+struct ReportRow {
+    filename: String,
+    version: Option<u8>,
+    ec_level: Option<String>,
+    mask: Option<u8>,
+    encoding: Option<String>,
+    data_bits: Option<usize>,
+    capacity_bits: Option<usize>,
+    /// Percentage of the capacity taken by the data
+    usage: Option<f64>,
+    error: Option<String>,
+}
+
+/// Destination of the generated files
+enum Sink {
+    Directory(PathBuf),
+    Zip(zip::ZipWriter<File>),
+    Tar(tar::Builder<File>),
+}
+
+impl Sink {
+    /// Opens output as an archive if it ends in .zip or .tar, and as a directory otherwise
+    fn open(output: &str) -> std::io::Result<Sink> {
+        let extension = Path::new(output).extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_lowercase());
+        return Ok(match extension.as_deref() {
+            Some("zip") => Sink::Zip(zip::ZipWriter::new(File::create(output)?)),
+            Some("tar") => Sink::Tar(tar::Builder::new(File::create(output)?)),
+            _ => {
+                std::fs::create_dir_all(output)?;
+                Sink::Directory(PathBuf::from(output))
+            },
+        });
+    }
+
+    /// Writes a file called name with contents
+    fn add(&mut self, name: &str, contents: &[u8]) -> std::io::Result<()> {
+        match self {
+            Sink::Directory(dir) => {
+                let path = dir.join(name);
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(path,contents)?;
+            },
+            Sink::Zip(writer) => {
+                writer.start_file(name,zip::write::FileOptions::default())?;
+                writer.write_all(contents)?;
+            },
+            Sink::Tar(builder) => {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(contents.len() as u64);
+                header.set_mode(0o644);
+                header.set_cksum();
+                builder.append_data(&mut header,name,contents)?;
+            },
+        }
+        return Ok(());
+    }
+
+    fn finish(self) -> std::io::Result<()> {
+        match self {
+            Sink::Directory(_) => {},
+            Sink::Zip(mut writer) => {
+                writer.finish()?;
+            },
+            Sink::Tar(builder) => {
+                builder.into_inner()?;
+            },
+        }
+        return Ok(());
+    }
+}
+
+/// Parses #rrggbb or #rrggbbaa into an RGBA color
+fn parse_color(str: &str) -> Result<[u8; 4],String> {
+    let hex = str.strip_prefix('#').unwrap_or(str);
+    let channel = |i: usize| hex.get(i * 2..i * 2 + 2).and_then(|channel| u8::from_str_radix(channel,16).ok());
+    return match hex.len() {
+        6 | 8 => {
+            let alpha = if hex.len() == 8 {channel(3)} else {Some(255)};
+            match (channel(0), channel(1), channel(2), alpha) {
+                (Some(r), Some(g), Some(b), Some(a)) => Ok([r, g, b, a]),
+                _ => Err(format!("Invalid color {}",str)),
+            }
+        },
+        _ => Err(format!("Invalid color {}",str)),
+    };
+}
+
+/// Reads every row of the manifest, with the line number in errors
+fn read_manifest(manifest: &str, format: Option<ManifestFormat>) -> Result<Vec<Row>,Box<dyn std::error::Error>> {
+    let reader: Box<dyn Read> = match manifest {
+        "-" => Box::new(std::io::stdin()),
+        path => Box::new(File::open(path).map_err(|err| format!("Unable to open {}: {}",path,err))?),
+    };
+    let format = format.unwrap_or_else(|| {
+        match Path::new(manifest).extension().and_then(|ext| ext.to_str()) {
+            Some("jsonl") | Some("ndjson") => ManifestFormat::Jsonl,
+            _ => ManifestFormat::Csv,
+        }
+    });
+    let mut rows = Vec::new();
+    match format {
+        ManifestFormat::Csv => {
+            for row in csv::Reader::from_reader(reader).deserialize() {
+                rows.push(row?);
+            }
+        },
+        ManifestFormat::Jsonl => {
+            for (i, line) in BufReader::new(reader).lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                rows.push(serde_json::from_str(&line).map_err(|err| format!("Line {}: {}",i + 1,err))?);
+            }
+        },
+    }
+    return Ok(rows);
+}
+
+/// Returns the segment a row is encoded in
+fn row_segments(row: &Row) -> Vec<Segment> {
+    return vec![Segment::new(&row.payload,bits::get_encoding(&row.payload))];
+}
+
+/// Generates the code of a row and returns it with its style
+fn build_row(row: &Row, default_ec: ECLevel, style: &Style) -> Result<(QR,Style),Box<dyn std::error::Error>> {
+    // Filenames must stay inside the output directory or archive
+    let path = Path::new(&row.filename);
+    if row.filename.is_empty() || !path.components().all(|part| matches!(part, Component::Normal(_))) {
+        return Err(format!("Filename {} must be a relative path inside the output",row.filename).into());
+    }
+    let ec_level = match &row.ec {
+        Some(ec) if !ec.is_empty() => parse_ec_level(ec)?,
+        _ => default_ec,
+    };
+    let mut builder = QrBuilder::from_segments(&row_segments(row)).ec_level(ec_level);
+    if let Some(version) = row.version {
+        builder = builder.version(version);
+    }
+    let qr_code = builder.build()?;
+
+    let mut style = style.clone();
+    if let Some(foreground) = row.foreground.as_deref().filter(|color| !color.is_empty()) {
+        style.foreground = parse_color(foreground)?;
+    }
+    if let Some(background) = row.background.as_deref().filter(|color| !color.is_empty()) {
+        style.background = parse_color(background)?;
+    }
+    return Ok((qr_code, style));
+}
+
+/// Generates the code of a row and returns the rendered file with its summary
+fn generate_row(args: &BatchArgs, row: &Row) -> Result<(Vec<u8>,Summary),Box<dyn std::error::Error>> {
+    let mut style = Style::new(args.scale,args.quiet_zone);
+    style.module_size = args.module_size;
+    style.bleed = args.bleed;
+    // The report is written last and would replace the code
+    if Path::new(&row.filename) == Path::new(&args.report) {
+        return Err(format!("Filename {} is used by the report",row.filename).into());
+    }
+    let (qr_code, style) = build_row(row,args.ec,&style)?;
+    let format = Format::from_path(&row.filename).unwrap_or(args.format);
+    let mut contents = Vec::new();
+    write_output(&qr_code,format,&style,&mut contents)?;
+    return Ok((contents, Summary::new(&qr_code,&row_segments(row))));
+}
+
+/// Writes the report as CSV, or as a JSON array if name ends in .json
+fn write_report(name: &str, report: &[ReportRow]) -> Result<Vec<u8>,Box<dyn std::error::Error>> {
+    if name.to_lowercase().ends_with(".json") {
+        return Ok(serde_json::to_vec_pretty(report)?);
+    }
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for row in report {
+        writer.serialize(row)?;
+    }
+    return Ok(writer.into_inner().map_err(|err| err.into_error())?);
+}
+
+/// Generates every row of the manifest, then writes the report
+/// Rows that fail are listed in the report and do not stop the others
+pub fn run(args: &BatchArgs) -> Result<(),Box<dyn std::error::Error>> {
+    let rows = read_manifest(&args.manifest,args.manifest_format)?;
+    let mut sink = Sink::open(&args.output).map_err(|err| format!("Unable to create {}: {}",args.output,err))?;
+    let mut report = Vec::with_capacity(rows.len());
+    for row in rows.iter() {
+        let result = generate_row(args,row).and_then(|(contents, summary)| {
+            sink.add(&row.filename,&contents)?;
+            Ok(summary)
+        });
+        report.push(match result {
+            Ok(summary) => ReportRow {
+                filename: row.filename.clone(),
+                usage: Some(summary.usage()),
+                version: Some(summary.version),
+                ec_level: Some(summary.ec_level),
+                mask: Some(summary.mask),
+                encoding: Some(summary.encoding),
+                data_bits: Some(summary.data_bits),
+                capacity_bits: Some(summary.capacity_bits),
+                error: None,
+            },
+            Err(err) => ReportRow {
+                filename: row.filename.clone(),
+                version: None,
+                ec_level: None,
+                mask: None,
+                encoding: None,
+                data_bits: None,
+                capacity_bits: None,
+                usage: None,
+                error: Some(err.to_string()),
+            },
+        });
+    }
+    sink.add(&args.report,&write_report(&args.report,&report)?)?;
+    sink.finish()?;
+    let failed = report.iter().filter(|row| row.error.is_some()).count();
+    if failed > 0 {
+        return Err(format!("{} of {} codes could not be generated, see {}",failed,report.len(),args.report).into());
+    }
+    return Ok(());
+}
+
+/// Prints every code of the manifest to stdout under its filename and summary, without writing any files
+/// Rows that fail print their error and do not stop the others
+pub fn preview(args: &PreviewArgs) -> Result<(),Box<dyn std::error::Error>> {
+    let rows = read_manifest(&args.manifest,args.manifest_format)?;
+    let format = args.protocol.format();
+    let mut style = Style::new(args.scale,args.quiet_zone);
+    style.invert = args.invert;
+    let mut stdout = std::io::stdout().lock();
+    let mut failed = 0;
+    for row in rows.iter() {
+        match build_row(row,args.ec,&style) {
+            Ok((qr_code, style)) => {
+                let summary = Summary::new(&qr_code,&row_segments(row));
+                writeln!(stdout,"{}: version {}-{}, mask {}, {}, {}% of capacity",
+                    row.filename,summary.version,summary.ec_level,summary.mask,summary.encoding,summary.usage())?;
+                write_output(&qr_code,format,&style,&mut stdout)?;
+            },
+            Err(err) => {
+                failed += 1;
+                writeln!(stdout,"{}: {}",row.filename,err)?;
+            },
+        }
+        writeln!(stdout)?;
+    }
+    if failed > 0 {
+        return Err(format!("{} of {} codes could not be generated",failed,rows.len()).into());
+    }
+    return Ok(());
+}
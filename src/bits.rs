@@ -29,10 +29,11 @@ pub(crate) fn collect_bits(bits: &[u8]) -> usize {
 /// If the message is purely numeric, returns Numeric
 /// If the message only has alphanumeric chars, returns Alphanumeric
 /// If the characters fit in Latin-1, returns Byte
+/// If the characters are all double-byte Shift JIS, returns Kanji
 /// Else returns ECI mode for UTF-8
-pub(crate) fn get_encoding(str: &str) -> crate::qr::Encoding {
+pub fn get_encoding(str: &str) -> crate::qr::Encoding {
     use crate::qr::Encoding::*;
-    if str.chars().all(|ch| ch >= '0' && ch <= '9') {
+    if str.chars().all(|ch| ch.is_ascii_digit()) {
         return Numeric;
     }
     if str.chars().all(|ch| alphanumeric_char_to_idx(ch).is_some()) {
@@ -41,11 +42,13 @@ pub(crate) fn get_encoding(str: &str) -> crate::qr::Encoding {
     if str.chars().all(|ch| ch <= 255 as char) {
         return Byte;
     }
+    if str.chars().all(|ch| kanji_char_to_sjis(ch).is_some()) {
+        return Kanji;
+    }
     return ECI;
 }
 
 /// List of characters in the QR "alphanumeric" mode
-#[cfg(test)]
 pub const ALPHANUMERIC_CHARS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
 
 /// Returns the index of a character in the QR "alphanumeric" mode
@@ -86,7 +89,6 @@ pub(crate) fn encode_alphanumeric(str: &str) -> Result<BitList, char> {
 }
 
 /// Decodes valid alphanumeric bit lists into strings
-#[cfg(test)]
 pub(crate) fn decode_alphanumeric(seq: BitList) -> Option<String> {
     let mut out: String = String::with_capacity(seq.len() / 11 + 1);
     // Chunks into bit sequence of size 11 or a trailing sequence of 6
@@ -103,7 +105,7 @@ pub(crate) fn decode_alphanumeric(seq: BitList) -> Option<String> {
             return None;
         }
     }
-    return Some(String::from(""));
+    return Some(out);
 }
 
 /// Converts string of digits to a bit string.
@@ -112,7 +114,7 @@ pub(crate) fn encode_numeric(str: &str) -> Result<BitList, char> {
     let len = str.len();
     let mut digits: Vec<u32> = Vec::with_capacity(len);
     for ch in str.chars() {
-        if ch < '0' || ch > '9' {
+        if !ch.is_ascii_digit() {
             return Err(ch);
         }
         digits.push((ch as u32) - ('0' as u32));
@@ -131,10 +133,8 @@ pub(crate) fn encode_numeric(str: &str) -> Result<BitList, char> {
     return Ok(out);
 }
 
-#[cfg(test)]
 const NUMERIC_CHARS: &[u8] = b"0123456789";
 
-#[cfg(test)]
 /// Decodes a bitlist into a numeric message
 pub(crate) fn decode_numeric(seq: BitList) -> Option<String> {
     let mut out: String = String::from("");
@@ -177,7 +177,6 @@ pub(crate) fn encode_latin(str: &str) -> Result<BitList, char> {
     return Ok(out);
 }
 
-#[cfg(test)]
 /// Decodes bitlist into latin characters
 pub(crate) fn decode_latin(seq: BitList) -> Option<String> {
     let mut out: String = String::with_capacity(seq.len() / 8);
@@ -188,20 +187,65 @@ pub(crate) fn decode_latin(seq: BitList) -> Option<String> {
     return Some(out);
 }
 
-//pub(crate) fn encode_utf8(str: &str) -> Result<BitList, char> {
-//    ECI Coding identity: 0111
-//    UTF-8 id: 0d26
-//    panic!("Not implemented");
-//}
+/// ECI designator for UTF-8 (ECI 000026)
+pub(crate) const ECI_UTF8: u32 = 26;
 
-//pub(crate) fn decode_utf8(seq: BitList) -> Option<String> {
-//    panic!("Not implemented");
-//}
+/// Converts string to its UTF-8 bytes encoded as bits
+/// The ECI header is added by metadata::get_codewords, this only writes the byte segment payload
+pub(crate) fn encode_utf8(str: &str) -> Result<BitList, char> {
+    let mut out: Vec<u8> = Vec::with_capacity(str.len() * 8);
+    for byte in str.bytes() {
+        push_to_bit_list(&mut out, byte as u32, 8);
+    }
+    return Ok(out);
+}
 
-//pub(crate) fn encode_kanji(str: &str) -> Result<BitList, char> {
-//    panic!("Not implemented");
-//}
+/// Decodes bitlist of UTF-8 bytes into a string
+pub(crate) fn decode_utf8(seq: BitList) -> Option<String> {
+    let bytes: Vec<u8> = seq.chunks(8).map(|bits| collect_bits(bits) as u8).collect();
+    return String::from_utf8(bytes).ok();
+}
 
-//pub(crate) fn decode_kanji(seq: BitList) -> Option<String> {
-//    panic!("Not implemented");
-//}
+/// Returns the Shift JIS double-byte value of a character in QR's "kanji" mode
+/// Or None if it is not in the 0x8140-0x9FFC or 0xE040-0xEBBF ranges
+pub fn kanji_char_to_sjis(ch: char) -> Option<u32> {
+    let mut buf = [0u8; 4];
+    let (bytes, _, had_errors) = encoding_rs::SHIFT_JIS.encode(ch.encode_utf8(&mut buf));
+    if had_errors || bytes.len() != 2 {
+        return None;
+    }
+    let code = (bytes[0] as u32) << 8 | bytes[1] as u32;
+    match code {
+        0x8140..=0x9FFC | 0xE040..=0xEBBF => Some(code),
+        _ => None,
+    }
+}
+
+/// Encodes a string into a bit list using QR's "kanji" mode
+/// Each character is converted to Shift JIS and compacted into 13 bits
+pub(crate) fn encode_kanji(str: &str) -> Result<BitList, char> {
+    let mut out: BitList = Vec::with_capacity(str.chars().count() * 13);
+    for ch in str.chars() {
+        let code = kanji_char_to_sjis(ch).ok_or(ch)?;
+        // Subtract the start of the range, then the high byte is multiplied by 0xC0
+        let offset = code - if code <= 0x9FFC {0x8140} else {0xC140};
+        push_to_bit_list(&mut out, (offset >> 8) * 0xC0 + (offset & 0xFF), 13);
+    }
+    return Ok(out);
+}
+
+/// Decodes bitlist of 13 bit kanji values into a string
+pub(crate) fn decode_kanji(seq: BitList) -> Option<String> {
+    let mut bytes: Vec<u8> = Vec::with_capacity(seq.len() / 13 * 2);
+    for bits in seq.chunks(13) {
+        if bits.len() != 13 {return None;}
+        let val = collect_bits(bits) as u32;
+        let offset = (val / 0xC0) << 8 | (val % 0xC0);
+        let code = offset + if offset < 0x1F00 {0x8140} else {0xC140};
+        bytes.push((code >> 8) as u8);
+        bytes.push(code as u8);
+    }
+    let (out, had_errors) = encoding_rs::SHIFT_JIS.decode_without_bom_handling(&bytes);
+    if had_errors {return None;}
+    return Some(out.into_owned());
+}
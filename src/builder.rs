@@ -0,0 +1,278 @@
+use crate::bits;
+use crate::qr::Encoding;
+use crate::qr::{SymbolKind, QR};
+use crate::segment::Segment;
+use crate::structured::{self, StructuredAppend};
+use crate::error::QrError;
+use crate::error_correction::ECLevel;
+use crate::gs1::{self, Fnc1};
+use crate::mask::{MaskReport,MaskScorer};
+
+/// Builder for qr codes that need more control than QR::new
+/// Example:
+/// QrBuilder::new("HELLO WORLD").ec_level(ECLevel::Q).version(5).mask(3).build()
+#[derive(Debug,Clone)]
+pub struct QrBuilder {
+    kind: SymbolKind,
+    data: String,
+    segments: Option<Vec<Segment>>,
+    encoding: Option<Encoding>,
+    ec_level: ECLevel,
+    min_version: u8,
+    max_version: u8,
+    mask_index: Option<u8>,
+    mask_scorer: MaskScorer,
+    boost_ec: bool,
+    fnc1: Option<Fnc1>,
+}
+
+impl QrBuilder {
+    /// Start building a qr code holding str
+    /// Defaults to error correction M, the smallest version and the mask with the lowest penalty
+    pub fn new(str: &str) -> QrBuilder {
+        QrBuilder {
+            kind: SymbolKind::Qr,
+            data: str.to_string(),
+            segments: None,
+            encoding: None,
+            ec_level: ECLevel::M,
+            min_version: 1,
+            max_version: 40,
+            mask_index: None,
+            mask_scorer: MaskScorer::Legacy,
+            boost_ec: false,
+            fnc1: None,
+        }
+    }
+
+    /// Start building a qr code from a list of segments
+    pub fn from_segments(segments: &[Segment]) -> QrBuilder {
+        let mut out = QrBuilder::new("");
+        out.segments = Some(segments.to_vec());
+        return out;
+    }
+
+    /// Start building a qr code from GS1 elements written as "(01)09506000134352(17)201225(10)ABC123"
+    /// Returns an error if an element has an unknown identifier, a value of the wrong length or a wrong check digit
+    pub fn gs1(str: &str) -> Result<QrBuilder,QrError> {
+        let elements = gs1::parse(str)?;
+        return Ok(QrBuilder::new(&gs1::element_string(&elements)).fnc1(Fnc1::First));
+    }
+
+    /// Write an FNC1 mode indicator before the data, where the character 0x1D in the data is the GS separator
+    /// Only regular qr codes support FNC1
+    pub fn fnc1(mut self, fnc1: Fnc1) -> QrBuilder {
+        self.fnc1 = Some(fnc1);
+        return self;
+    }
+
+    /// Create a micro or rectangular micro qr code instead of a regular one
+    /// Micro qr codes go from version 1 to 4 and have masks 0 to 3
+    /// Rectangular codes go from version 1 to 32, see rmqr::rmqr_version, and only have mask 0
+    pub fn kind(mut self, kind: SymbolKind) -> QrBuilder {
+        self.kind = kind;
+        return self;
+    }
+
+    /// Encode the whole string with enc instead of the one returned by get_encoding
+    pub fn encoding(mut self, enc: Encoding) -> QrBuilder {
+        self.encoding = Some(enc);
+        return self;
+    }
+
+    /// Set the error correction level
+    pub fn ec_level(mut self, ec_level: ECLevel) -> QrBuilder {
+        self.ec_level = ec_level;
+        return self;
+    }
+
+    /// Always use version, fails if the data does not fit
+    pub fn version(self, version: u8) -> QrBuilder {
+        return self.version_range(version,version);
+    }
+
+    /// Use the smallest version between min_version and max_version that fits the data
+    pub fn version_range(mut self, min_version: u8, max_version: u8) -> QrBuilder {
+        self.min_version = min_version;
+        self.max_version = max_version;
+        return self;
+    }
+
+    /// Always use the mask at mask_index (0-7) instead of the one with the lowest penalty
+    pub fn mask(mut self, mask_index: u8) -> QrBuilder {
+        self.mask_index = Some(mask_index);
+        return self;
+    }
+
+    /// Score masks with scorer when choosing the one with the lowest penalty
+    /// Only regular qr codes have a choice of scorer, micro qr codes always use micro_score
+    pub fn mask_scorer(mut self, scorer: MaskScorer) -> QrBuilder {
+        self.mask_scorer = scorer;
+        return self;
+    }
+
+    /// Raise the error correction level as long as the data still fits in the same version
+    pub fn boost_ec(mut self, boost_ec: bool) -> QrBuilder {
+        self.boost_ec = boost_ec;
+        return self;
+    }
+
+    /// Returns the segments that will be written
+    /// With FNC1, the data is split into the segments that need the fewest bits unless an encoding is set
+    fn get_segments(&self) -> Result<Vec<Segment>,QrError> {
+        if let Some(segments) = &self.segments {
+            return Ok(segments.clone());
+        }
+        if self.fnc1.is_some() {
+            return match self.encoding {
+                Some(enc) => Ok(vec![gs1::fnc1_segment(&self.data,enc)]),
+                None => gs1::fnc1_segments(&self.data,self.ec_level),
+            };
+        }
+        let enc = self.encoding.unwrap_or_else(|| bits::get_encoding(&self.data));
+        return Ok(vec![Segment::new(&self.data,enc)]);
+    }
+
+    /// Returns the bits written before the segments of every symbol
+    fn header_bits(&self) -> Vec<u8> {
+        return self.fnc1.map(|fnc1| fnc1.header_bits()).unwrap_or_default();
+    }
+
+    /// Create the qr code
+    pub fn build(&self) -> Result<QR,QrError> {
+        if self.kind == SymbolKind::Micro {
+            return self.build_micro();
+        }
+        if self.kind == SymbolKind::Rmqr {
+            return self.build_rmqr();
+        }
+        if let Some(mask_index) = self.mask_index {
+            if mask_index > 7 {
+                return Err(QrError::MaskOutOfRange(mask_index));
+            }
+        }
+        let segments = self.get_segments()?;
+        let header = self.header_bits();
+        let (version, _) = QR::get_min_version_with_header(&segments,self.ec_level,self.min_version,self.max_version,header.len())?;
+        let mut ec_level = self.ec_level;
+        if self.boost_ec {
+            use ECLevel::*;
+            for higher in [M, Q, H] {
+                if higher > ec_level
+                    && QR::get_min_version_with_header(&segments,higher,version,version,header.len()).is_ok() {
+                    ec_level = higher;
+                }
+            }
+        }
+        return QR::create_with_header(&segments,version,ec_level,self.mask_index,self.mask_scorer,&header);
+    }
+
+    /// Create the qr code along with the penalties of every mask, scored by the mask scorer
+    pub fn build_with_mask_report(&self) -> Result<(QR,MaskReport),QrError> {
+        let qr = self.build()?;
+        let report = qr.mask_report(self.mask_scorer);
+        return Ok((qr, report));
+    }
+
+    /// Create a micro qr code, the maximum version is lowered to 4
+    fn build_micro(&self) -> Result<QR,QrError> {
+        if self.fnc1.is_some() {
+            return Err(QrError::UnsupportedSymbolKind(self.kind));
+        }
+        if let Some(mask_index) = self.mask_index {
+            if mask_index > 3 {
+                return Err(QrError::MaskOutOfRange(mask_index));
+            }
+        }
+        let segments = self.get_segments()?;
+        let max_version = std::cmp::min(self.max_version,4);
+        let version = QR::get_min_micro_version_in_range(&segments,self.ec_level,self.min_version,max_version)?;
+        let mut ec_level = self.ec_level;
+        if self.boost_ec {
+            use ECLevel::*;
+            for higher in [M, Q] {
+                if higher > ec_level
+                    && QR::get_min_micro_version_in_range(&segments,higher,version,version).is_ok() {
+                    ec_level = higher;
+                }
+            }
+        }
+        return QR::create_micro(&segments,version,ec_level,self.mask_index);
+    }
+
+    /// Create a rectangular micro qr code, the maximum version is lowered to 32
+    /// Picks the version with the smallest area in the range rather than the lowest number
+    fn build_rmqr(&self) -> Result<QR,QrError> {
+        if self.fnc1.is_some() {
+            return Err(QrError::UnsupportedSymbolKind(self.kind));
+        }
+        if let Some(mask_index) = self.mask_index {
+            if mask_index > 0 {
+                return Err(QrError::MaskOutOfRange(mask_index));
+            }
+        }
+        let segments = self.get_segments()?;
+        let max_version = std::cmp::min(self.max_version,32);
+        let version = QR::get_min_rmqr_version_in_range(&segments,self.ec_level,self.min_version,max_version)?;
+        let mut ec_level = self.ec_level;
+        if self.boost_ec && ec_level < ECLevel::H
+            && QR::get_min_rmqr_version_in_range(&segments,ECLevel::H,version,version).is_ok() {
+            ec_level = ECLevel::H;
+        }
+        return QR::create_rmqr(&segments,version,ec_level);
+    }
+
+    /// Create a structured append sequence of up to 16 qr codes that together hold the data
+    /// Every symbol has the same version, the smallest in the range that needs no more symbols than the largest
+    /// Segments passed to from_segments are joined and split again, and boost_ec is not applied
+    /// With FNC1, every symbol starts with the FNC1 mode indicator after the structured append header
+    /// Data that fits in one symbol gives a single qr code without the header, at the smallest version it fits
+    pub fn build_structured_append(&self) -> Result<Vec<QR>,QrError> {
+        if self.kind != SymbolKind::Qr {
+            return Err(QrError::UnsupportedSymbolKind(self.kind));
+        }
+        if let Some(mask_index) = self.mask_index {
+            if mask_index > 7 {
+                return Err(QrError::MaskOutOfRange(mask_index));
+            }
+        }
+        if !(1..=40).contains(&self.min_version) {
+            return Err(QrError::VersionOutOfRange(self.min_version));
+        }
+        if !(self.min_version..=40).contains(&self.max_version) {
+            return Err(QrError::VersionOutOfRange(self.max_version));
+        }
+        let text: String = match &self.segments {
+            Some(segments) => segments.iter().map(|seg| seg.data.as_str()).collect(),
+            None => self.data.clone(),
+        };
+        let mut version = self.max_version;
+        let mut chunks = structured::split(&text,self.encoding,self.fnc1,version,self.ec_level,usize::MAX)?;
+        if chunks.len() > structured::MAX_SYMBOLS {
+            return Err(QrError::TooManySymbols { needed: chunks.len() });
+        }
+        for smaller in self.min_version..self.max_version {
+            match structured::split(&text,self.encoding,self.fnc1,smaller,self.ec_level,chunks.len()) {
+                Ok(split) if split.len() <= chunks.len() => {
+                    version = smaller;
+                    chunks = split;
+                    break;
+                },
+                _ => {},
+            }
+        }
+        if chunks.len() == 1 {
+            // A sequence has at least two symbols, so one symbol is written as a plain qr code
+            let header = self.header_bits();
+            let (version, _) = QR::get_min_version_with_header(&chunks[0],self.ec_level,self.min_version,version,header.len())?;
+            return Ok(vec![QR::create_with_header(&chunks[0],version,self.ec_level,self.mask_index,self.mask_scorer,&header)?]);
+        }
+        let total = chunks.len() as u8;
+        let parity = structured::parity(&chunks.concat());
+        return chunks.iter().enumerate().map(|(index, segments)| {
+            let mut header = StructuredAppend { index: index as u8, total, parity }.header_bits();
+            header.extend(self.header_bits());
+            QR::create_with_header(segments,version,self.ec_level,self.mask_index,self.mask_scorer,&header)
+        }).collect();
+    }
+}
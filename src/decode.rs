@@ -0,0 +1,239 @@
+use crate::bits;
+use crate::error::QrError;
+use crate::error_correction;
+use crate::error_correction::ECLevel;
+use crate::gs1::{self, Fnc1};
+use crate::metadata;
+use crate::patterns;
+use crate::grid::Grid;
+use crate::qr::{Encoding, SymbolKind, QR};
+use crate::segment::Segment;
+use crate::structured::StructuredAppend;
+
+/// Data and metadata read from a qr code
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct DecodedQr {
+    pub version: u8,
+    pub ec_level: ECLevel,
+    pub mask_index: u8,
+    /// Segments in the order they were written
+    pub segments: Vec<Segment>,
+    /// Text of every segment joined together
+    /// After an FNC1 mode indicator, separators in alphanumeric segments are replaced by 0x1D
+    pub text: String,
+    /// Number of codewords fixed by error correction
+    pub corrected_errors: usize,
+    /// Position in a structured append sequence, if the code is part of one
+    pub structured_append: Option<StructuredAppend>,
+    /// FNC1 mode indicator written before the segments
+    pub fnc1: Option<Fnc1>,
+}
+
+/// Maximum number of wrong bits corrected in the format and version information
+const MAX_INFO_ERRORS: u32 = 3;
+
+impl QR {
+    /// Decode the modules of this qr code back into its data
+    pub fn decode(&self) -> Result<DecodedQr,QrError> {
+        return decode(&self.bitmap);
+    }
+}
+
+/// Decode a grid of modules, where 1 is dark, back into the data it stores
+/// The grid must be the code itself without a quiet zone
+pub fn decode(bitmap: &Grid) -> Result<DecodedQr,QrError> {
+    let width = bitmap.height();
+    if !(21..=177).contains(&width) || !(width - 17).is_multiple_of(4) || bitmap.width() != width {
+        return Err(QrError::InvalidSize(width));
+    }
+    let version = ((width - 17) / 4) as u8;
+    if version >= 7 && read_version(bitmap)? != version {
+        return Err(QrError::VersionUnreadable);
+    }
+    let (ec_level, mask_index) = read_format(bitmap)?;
+
+    // Blank code of the same version to find which modules hold data
+    let mut qr = QR {
+        kind: SymbolKind::Qr,
+        bitmap: Grid::new(width,width),
+        pattern_mask: Grid::new(width,width),
+        ec_level,
+        version,
+        mask_index,
+    };
+    qr.apply_patterns();
+    // Copy the modules and undo the mask
+    qr.bitmap = bitmap.clone();
+    qr.mask(mask_index as usize);
+
+    let codewords = qr.read_message();
+    let (data, corrected_errors) = error_correction::ec_decode(&codewords,version,ec_level)?;
+    let (segments, Headers { structured_append, fnc1 }) = parse_segments(&data,version)?;
+    let text = segments.iter().map(|seg| match (seg.enc, fnc1) {
+        (Encoding::Alphanumeric, Some(_)) => gs1::unescape_alphanumeric(&seg.data),
+        _ => seg.data.clone(),
+    }).collect();
+    return Ok(DecodedQr { version, ec_level, mask_index, segments, text, corrected_errors, structured_append, fnc1 });
+}
+
+/// Reads both copies of the format information and returns the closest valid (ec_level, mask_index)
+fn read_format(bitmap: &Grid) -> Result<(ECLevel,u8),QrError> {
+    use ECLevel::*;
+    let width = bitmap.width();
+    let mut copies = [0u32; 2];
+    for i in 0..15 {
+        for (copy, (y, x)) in copies.iter_mut().zip(patterns::format_positions(i,width)) {
+            *copy = *copy << 1 | bitmap.get(x,y) as u32;
+        }
+    }
+    let mut best = None;
+    let mut best_distance = MAX_INFO_ERRORS + 1;
+    for ec_level in [L, M, Q, H] {
+        for mask_index in 0..8 {
+            let valid = bits::collect_bits(&patterns::format_info(ec_level,mask_index)) as u32;
+            for copy in copies {
+                let distance = (copy ^ valid).count_ones();
+                if distance < best_distance {
+                    best = Some((ec_level, mask_index));
+                    best_distance = distance;
+                }
+            }
+        }
+    }
+    return best.ok_or(QrError::FormatUnreadable);
+}
+
+/// Reads both copies of the version information and returns the closest valid version
+fn read_version(bitmap: &Grid) -> Result<u8,QrError> {
+    let width = bitmap.width();
+    let mut copies = [0u32; 2];
+    for i in (0..18).rev() {
+        for (copy, (y, x)) in copies.iter_mut().zip(patterns::version_positions(i,width)) {
+            *copy = *copy << 1 | bitmap.get(x,y) as u32;
+        }
+    }
+    let mut best = None;
+    let mut best_distance = MAX_INFO_ERRORS + 1;
+    for version in 7..=40 {
+        let valid = bits::collect_bits(&patterns::version_info(version)) as u32;
+        for copy in copies {
+            let distance = (copy ^ valid).count_ones();
+            if distance < best_distance {
+                best = Some(version);
+                best_distance = distance;
+            }
+        }
+    }
+    return best.ok_or(QrError::VersionUnreadable);
+}
+
+/// Reads segments from data codewords until the terminator or the end of the data
+/// Also returns the structured append header and the FNC1 mode indicator if there are any
+fn parse_segments(data: &[u8], version: u8) -> Result<(Vec<Segment>,Headers),QrError> {
+    let mut reader = BitReader { bits: Vec::with_capacity(data.len() * 8), pos: 0 };
+    for byte in data {
+        bits::push_to_bit_list(&mut reader.bits,*byte as u32,8);
+    }
+    let mut segments = Vec::new();
+    // Character set of byte segments, set by an ECI header
+    let mut eci: Option<u32> = None;
+    let mut headers = Headers { structured_append: None, fnc1: None };
+    while reader.remaining() >= 4 {
+        let enc = match reader.read(4)? {
+            0 => break,
+            1 => Encoding::Numeric,
+            2 => Encoding::Alphanumeric,
+            4 => Encoding::Byte,
+            8 => Encoding::Kanji,
+            3 => {
+                let index = reader.read(4)? as u8;
+                let total = reader.read(4)? as u8 + 1;
+                let parity = reader.read(8)? as u8;
+                headers.structured_append = Some(StructuredAppend { index, total, parity });
+                continue;
+            },
+            5 => {
+                headers.fnc1 = Some(Fnc1::First);
+                continue;
+            },
+            9 => {
+                headers.fnc1 = Some(Fnc1::Second(reader.read(8)? as u8));
+                continue;
+            },
+            7 => {
+                eci = Some(reader.read_eci_designator()?);
+                continue;
+            },
+            _ => return Err(QrError::MalformedData),
+        };
+        let count = reader.read(metadata::num_length_bits(version,enc)? as usize)?;
+        let decoded = match enc {
+            Encoding::Numeric => bits::decode_numeric(reader.take(count / 3 * 10 + [0,4,7][count % 3])?),
+            Encoding::Alphanumeric => bits::decode_alphanumeric(reader.take(count / 2 * 11 + count % 2 * 6)?),
+            Encoding::Kanji => bits::decode_kanji(reader.take(count * 13)?),
+            _ => {
+                let payload = reader.take(count * 8)?;
+                match eci {
+                    None | Some(3) => bits::decode_latin(payload),
+                    Some(bits::ECI_UTF8) => {
+                        let text = bits::decode_utf8(payload).ok_or(QrError::MalformedData)?;
+                        segments.push(Segment::new(&text,Encoding::ECI));
+                        continue;
+                    },
+                    Some(designator) => return Err(QrError::UnsupportedEci(designator)),
+                }
+            },
+        };
+        segments.push(Segment::new(&decoded.ok_or(QrError::MalformedData)?,enc));
+    }
+    return Ok((segments, headers));
+}
+
+/// Mode indicators found between the segments that do not start a segment themselves
+struct Headers {
+    structured_append: Option<StructuredAppend>,
+    fnc1: Option<Fnc1>,
+}
+
+/// Reads fields from a list of bits
+struct BitReader {
+    bits: Vec<u8>,
+    pos: usize,
+}
+
+impl BitReader {
+    fn remaining(&self) -> usize {
+        return self.bits.len() - self.pos;
+    }
+
+    /// Returns the next len bits, or MalformedData if there are not enough left
+    fn take(&mut self, len: usize) -> Result<Vec<u8>,QrError> {
+        if len > self.remaining() {
+            return Err(QrError::MalformedData);
+        }
+        self.pos += len;
+        return Ok(self.bits[self.pos - len..self.pos].to_vec());
+    }
+
+    /// Returns the value of the next len bits
+    fn read(&mut self, len: usize) -> Result<usize,QrError> {
+        return Ok(bits::collect_bits(&self.take(len)?));
+    }
+
+    /// Reads an ECI designator, which is 1, 2 or 3 bytes long depending on its leading bits
+    fn read_eci_designator(&mut self) -> Result<u32,QrError> {
+        let first = self.read(8)? as u32;
+        return Ok(if first & 0x80 == 0 {
+            first
+        }
+        else if first & 0xC0 == 0x80 {
+            (first & 0x3F) << 8 | self.read(8)? as u32
+        }
+        else if first & 0xE0 == 0xC0 {
+            (first & 0x1F) << 16 | self.read(16)? as u32
+        }
+        else {
+            return Err(QrError::MalformedData);
+        });
+    }
+}
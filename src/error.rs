@@ -0,0 +1,96 @@
+use crate::qr::Encoding;
+
+/// Errors returned when a QR code cannot be created or decoded
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum QrError {
+    /// The message needs more bits than the largest allowed version can hold
+    DataTooLong { needed_bits: usize, capacity: usize },
+    /// A character cannot be written in the encoding mode of its segment
+    /// position is the index of the character in the whole message
+    InvalidCharacter { ch: char, position: usize, mode: Encoding },
+    /// Version is outside of the supported range
+    VersionOutOfRange(u8),
+    /// Encoding mode cannot be used in this kind of symbol
+    UnsupportedMode(Encoding),
+    /// Error correction level cannot be used in this kind of symbol
+    UnsupportedEcLevel(crate::error_correction::ECLevel),
+    /// Mask index is not between 0 and 7, or 0 and 3 for micro qr codes
+    MaskOutOfRange(u8),
+    /// Grid of modules is not square or its width is not a qr code size
+    InvalidSize(usize),
+    /// Format information is too damaged to find the error correction level and mask
+    FormatUnreadable,
+    /// Version information is too damaged or does not match the size of the code
+    VersionUnreadable,
+    /// A block has more errors than its error correction codewords can fix
+    TooManyErrors { block: usize },
+    /// Decoded bits end in the middle of a segment or contain an unknown mode indicator
+    MalformedData,
+    /// ECI designator of a segment names a character set that cannot be decoded
+    UnsupportedEci(u32),
+    /// Structured append and FNC1 can only be used in regular qr codes
+    UnsupportedSymbolKind(crate::qr::SymbolKind),
+    /// Message needs more than 16 symbols of the largest allowed version
+    TooManySymbols { needed: usize },
+    /// Structured append symbol at this index was not given
+    MissingSymbol(u8),
+    /// Structured append symbols belong to different sequences, repeat an index or fail the parity check
+    SequenceMismatch,
+    /// GS1 application identifier is not known
+    UnknownApplicationIdentifier(String),
+    /// Value of the GS1 element with this application identifier has the wrong length or characters
+    InvalidElement(String),
+    /// Last digit of the GS1 element with this application identifier is not its check digit
+    CheckDigitMismatch { ai: String, expected: char },
+    /// GS1 elements are not written as (AI)value pairs
+    MalformedElementString,
+}
+
+impl std::fmt::Display for QrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QrError::DataTooLong { needed_bits, capacity } =>
+                write!(f, "Data too long: needs {} bits but only {} bits are available", needed_bits, capacity),
+            QrError::InvalidCharacter { ch, position, mode } =>
+                write!(f, "Invalid character '{}' at position {} in encoding {:?}", ch, position, mode),
+            QrError::VersionOutOfRange(version) =>
+                write!(f, "Version {} is out of range", version),
+            QrError::UnsupportedMode(mode) =>
+                write!(f, "Encoding {:?} is not supported", mode),
+            QrError::UnsupportedEcLevel(ec_level) =>
+                write!(f, "Error correction level {:?} is not supported", ec_level),
+            QrError::MaskOutOfRange(mask_index) =>
+                write!(f, "Mask {} is out of range", mask_index),
+            QrError::InvalidSize(width) =>
+                write!(f, "Width {} is not a valid qr code size", width),
+            QrError::FormatUnreadable =>
+                write!(f, "Format information cannot be read"),
+            QrError::VersionUnreadable =>
+                write!(f, "Version information cannot be read"),
+            QrError::TooManyErrors { block } =>
+                write!(f, "Block {} has too many errors to correct", block),
+            QrError::MalformedData =>
+                write!(f, "Data does not form valid segments"),
+            QrError::UnsupportedEci(designator) =>
+                write!(f, "ECI {} is not supported", designator),
+            QrError::UnsupportedSymbolKind(kind) =>
+                write!(f, "Symbol kind {:?} does not support structured append or FNC1", kind),
+            QrError::TooManySymbols { needed } =>
+                write!(f, "Message needs {} symbols but at most 16 can be linked", needed),
+            QrError::MissingSymbol(index) =>
+                write!(f, "Symbol {} of the sequence is missing", index),
+            QrError::SequenceMismatch =>
+                write!(f, "Symbols do not form one structured append sequence"),
+            QrError::UnknownApplicationIdentifier(ai) =>
+                write!(f, "Unknown GS1 application identifier ({})", ai),
+            QrError::InvalidElement(ai) =>
+                write!(f, "Value of GS1 element ({}) has the wrong length or characters", ai),
+            QrError::CheckDigitMismatch { ai, expected } =>
+                write!(f, "Check digit of GS1 element ({}) should be {}", ai, expected),
+            QrError::MalformedElementString =>
+                write!(f, "GS1 elements must be written as (AI)value"),
+        }
+    }
+}
+
+impl std::error::Error for QrError {}
@@ -1,8 +1,10 @@
 extern crate lazy_static;
 use crate::metadata::blocks_table_get;
+use crate::error::QrError;
 type Polynomial = Vec<u8>;
 
-#[derive(PartialEq,Eq,Copy,Clone,Debug)]
+/// Ordered from lowest to highest error correction
+#[derive(PartialEq,Eq,PartialOrd,Ord,Copy,Clone,Debug)]
 pub enum ECLevel {
     /// Low error correction, allows up to 7% bit loss
     L,
@@ -17,12 +19,17 @@ pub enum ECLevel {
 /// Splits data into blocks, add error correction, then combines together
 /// Returned result can then be written to the qr code
 pub fn ec_encode(message: Vec<u8>, version: u8, ec_level: ECLevel) -> Vec<u8> {
-    let (num_data_codewords, ec_per_block, num_blocks) = blocks_table_get(version,ec_level);
+    return ec_encode_blocks(message,blocks_table_get(version,ec_level));
+}
+
+/// Same as ec_encode with the block layout given as (number of data codewords, EC codewords per block, number blocks)
+pub(crate) fn ec_encode_blocks(message: Vec<u8>, blocks: (usize,usize,usize)) -> Vec<u8> {
+    let (num_data_codewords, ec_per_block, num_blocks) = blocks;
     let block_size = num_data_codewords / num_blocks;
     let group1 = num_blocks - num_data_codewords % num_blocks;
     
-    let get_block_idx = |idx| {
-        idx * block_size + if idx > group1 {idx - group1} else {0}
+    let get_block_idx = |idx: usize| {
+        idx * block_size + idx.saturating_sub(group1)
     };
 
     let mut out: Vec<u8> = Vec::with_capacity(message.len()+ec_per_block*num_blocks);
@@ -44,18 +51,183 @@ pub fn ec_encode(message: Vec<u8>, version: u8, ec_level: ECLevel) -> Vec<u8> {
     }
     //Append error correction codes to output
     for j in 0..ec_per_block {
-        for i in 0..num_blocks {
-            out.push(ec_data[i][j]);
+        for block in ec_data.iter() {
+            out.push(block[j]);
         }
     }
     return out;
 }
 
+/// Splits codewords read from a qr code back into blocks, corrects errors, then joins the data codewords
+/// Reverses the interleaving done by ec_encode
+/// Returns the data codewords and the number of codewords that were corrected
+pub(crate) fn ec_decode(codewords: &[u8], version: u8, ec_level: ECLevel) -> Result<(Vec<u8>,usize),QrError> {
+    return ec_decode_blocks(codewords,blocks_table_get(version,ec_level));
+}
+
+/// Same as ec_decode with the block layout given as (number of data codewords, EC codewords per block, number blocks)
+pub(crate) fn ec_decode_blocks(codewords: &[u8], blocks: (usize,usize,usize)) -> Result<(Vec<u8>,usize),QrError> {
+    let (num_data_codewords, ec_per_block, num_blocks) = blocks;
+    let block_size = num_data_codewords / num_blocks;
+    let group1 = num_blocks - num_data_codewords % num_blocks;
+    let data_len = |idx: usize| block_size + if idx < group1 {0} else {1};
+
+    let mut blocks: Vec<Vec<u8>> = (0..num_blocks).map(|i| Vec::with_capacity(data_len(i) + ec_per_block)).collect();
+    let mut codewords = codewords.iter();
+    let mut next = || codewords.next().copied().ok_or(QrError::MalformedData);
+    // Data codewords, where group2 blocks have one extra at the end
+    for i in 0..=block_size {
+        for (j, block) in blocks.iter_mut().enumerate() {
+            if i < data_len(j) {
+                block.push(next()?);
+            }
+        }
+    }
+    // Error correction codewords
+    for _ in 0..ec_per_block {
+        for block in blocks.iter_mut() {
+            block.push(next()?);
+        }
+    }
+
+    let mut out: Vec<u8> = Vec::with_capacity(num_data_codewords);
+    let mut num_corrected = 0;
+    for (i, block) in blocks.iter_mut().enumerate() {
+        num_corrected += rs_correct(block,ec_per_block,&[]).ok_or(QrError::TooManyErrors { block: i })?;
+        out.extend_from_slice(&block[..data_len(i)]);
+    }
+    return Ok((out,num_corrected));
+}
+
+/// Reed-Solomon decoder for a block of data codewords followed by ec_count error correction codewords
+/// erasures are indices of codewords known to be unreadable, each one costs half as much as an unknown error
+/// Corrects the block in place when 2 * errors + erasures <= ec_count
+/// Returns the number of codewords that were changed, or None if the block cannot be corrected
+pub fn rs_correct(block: &mut [u8], ec_count: usize, erasures: &[usize]) -> Option<usize> {
+    if block.len() > 255 || ec_count > block.len() || erasures.len() > ec_count
+        || erasures.iter().enumerate().any(|(i, pos)| *pos >= block.len() || erasures[..i].contains(pos)) {
+        return None;
+    }
+    let syndromes = calc_syndromes(block,ec_count);
+    if syndromes.iter().all(|x| *x == 0) {
+        return Some(0);
+    }
+    let position_value = |pos: &usize| EXPS.1[(block.len() - 1 - pos) % 255] as usize;
+    // Erasure locator, which has a root at the inverse of each erasure position
+    let mut erasure_locator: Polynomial = vec![1];
+    for pos in erasures.iter() {
+        erasure_locator = poly_mul(&erasure_locator,&vec![1,position_value(pos) as u8]);
+    }
+    // Forney syndromes remove the effect of the erasures, so only unknown errors are left to find
+    let mut forney_syndromes = syndromes.clone();
+    for pos in erasures.iter() {
+        let x = position_value(pos);
+        for j in 0..forney_syndromes.len() - 1 {
+            forney_syndromes[j] = (mul(forney_syndromes[j] as usize,x) ^ forney_syndromes[j+1] as usize) as u8;
+        }
+    }
+    forney_syndromes.truncate(ec_count - erasures.len());
+    let error_locator = error_locator(&forney_syndromes);
+    let num_errors = error_locator.len() - 1;
+    if num_errors * 2 + erasures.len() > ec_count {
+        return None;
+    }
+    // Combined locator of errors and erasures
+    let locator = poly_mul(&error_locator,&erasure_locator);
+    let positions = find_errors(&locator,block.len());
+    if positions.len() != num_errors + erasures.len() {
+        return None;
+    }
+    // Forney: magnitude = X * omega(1/X) / locator'(1/X)
+    // poly_mul does not depend on the coefficient order
+    let mut omega = poly_mul(&syndromes,&locator);
+    omega.truncate(ec_count);
+    let derivative: Vec<u8> = locator.iter().enumerate().skip(1)
+        .map(|(i, coef)| if i % 2 == 1 {*coef} else {0})
+        .collect();
+    let mut num_changed = 0;
+    for pos in positions.iter() {
+        let degree = block.len() - 1 - pos;
+        let x = EXPS.1[degree % 255] as usize;
+        let x_inv = EXPS.1[(255 - degree % 255) % 255] as usize;
+        let denominator = poly_eval_asc(&derivative,x_inv);
+        if denominator == 0 {
+            return None;
+        }
+        let magnitude = div(mul(x,poly_eval_asc(&omega,x_inv)),denominator);
+        if magnitude != 0 {
+            block[*pos] ^= magnitude as u8;
+            num_changed += 1;
+        }
+    }
+    if calc_syndromes(block,ec_count).iter().any(|x| *x != 0) {
+        return None;
+    }
+    return Some(num_changed);
+}
+
+/// Returns the syndromes of a block, which are the block evaluated at each root of the generator polynomial
+/// All syndromes are zero when the block has no errors
+fn calc_syndromes(block: &[u8], ec_count: usize) -> Vec<u8> {
+    return (0..ec_count).map(|i| {
+        block.iter().fold(0, |acc, x| mul(acc,EXPS.1[i] as usize) ^ *x as usize) as u8
+    }).collect();
+}
+
+/// Berlekamp-Massey algorithm
+/// Returns the error locator polynomial, with coefficients from lowest to highest degree
+fn error_locator(syndromes: &[u8]) -> Polynomial {
+    let mut locator: Polynomial = vec![1];
+    let mut prev: Polynomial = vec![1];
+    let mut num_errors = 0;
+    let mut shift = 1;
+    let mut prev_discrepancy = 1;
+    for r in 0..syndromes.len() {
+        let discrepancy = (1..=num_errors)
+            .fold(syndromes[r] as usize, |acc, i| acc ^ mul(*locator.get(i).unwrap_or(&0) as usize,syndromes[r-i] as usize));
+        if discrepancy == 0 {
+            shift += 1;
+            continue;
+        }
+        let factor = div(discrepancy,prev_discrepancy);
+        let mut next = locator.clone();
+        next.resize(std::cmp::max(locator.len(),prev.len() + shift), 0);
+        for (i, coef) in prev.iter().enumerate() {
+            next[i + shift] ^= mul(*coef as usize,factor) as u8;
+        }
+        if 2 * num_errors <= r {
+            num_errors = r + 1 - num_errors;
+            prev = locator;
+            prev_discrepancy = discrepancy;
+            shift = 1;
+        }
+        else {
+            shift += 1;
+        }
+        locator = next;
+    }
+    locator.truncate(num_errors + 1);
+    return locator;
+}
+
+/// Chien search, returns the indices in a block of length len where the error locator has a root
+fn find_errors(locator: &Polynomial, len: usize) -> Vec<usize> {
+    return (0..len).filter(|pos| {
+        let degree = (len - 1 - pos) % 255;
+        poly_eval_asc(locator,EXPS.1[(255 - degree) % 255] as usize) == 0
+    }).collect();
+}
+
+/// Evaluates a polynomial with coefficients from lowest to highest degree at x
+fn poly_eval_asc(poly: &Polynomial, x: usize) -> usize {
+    return poly.iter().rev().fold(0, |acc, coef| mul(acc,x) ^ *coef as usize);
+}
+
 /// Returns error correction on group
 pub(crate) fn ec_group(message: &[u8], ec_count: usize) -> Vec<u8> {
     assert!(ec_count <= 30);
     let mut new_mess = message.to_vec();
-    for _ in 0..ec_count {new_mess.push(0);}
+    new_mess.resize(message.len() + ec_count, 0);
     return poly_rest(&new_mess, &GENERATOR_POLY[ec_count]);
 }
 
@@ -97,6 +269,7 @@ pub(crate) fn mul(a: usize, b: usize) -> usize {
 
 /// Division on GF(256)
 pub(crate) fn div(a: usize, b: usize) -> usize {
+    if a == 0 {return 0;}
     return EXPS.1[(EXPS.0[a] as usize + EXPS.0[b] as usize * 254) % 255].into();
 }
 
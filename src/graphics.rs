@@ -0,0 +1,135 @@
+use image::{ImageResult, RgbaImage};
+use crate::qr::QR;
+use crate::raster::{self, RasterFormat, RasterOptions};
+
+/// Largest number of base64 characters sent in one escape sequence of the Kitty protocol
+const KITTY_CHUNK_SIZE: usize = 4096;
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+impl QR {
+    /// Render qr code as a DEC Sixel escape sequence
+    pub fn to_sixel(&self, options: &RasterOptions) -> String {
+        return sixel(&self.to_image_buffer(options));
+    }
+
+    /// Render qr code as Kitty graphics protocol escape sequences carrying a PNG
+    pub fn to_kitty(&self, options: &RasterOptions) -> ImageResult<String> {
+        let mut png = Vec::new();
+        raster::write_image(&self.bitmap,&mut png,RasterFormat::Png,options)?;
+        return Ok(kitty(&png));
+    }
+}
+
+/// Encode an image as a DEC Sixel escape sequence
+/// Each distinct color gets a palette register, so this is meant for images with few colors.
+/// Alpha is ignored, every pixel is drawn opaque.
+pub fn sixel(image: &RgbaImage) -> String {
+    let (width, height) = image.dimensions();
+    let mut palette: Vec<[u8; 3]> = Vec::new();
+    let mut indices = Vec::with_capacity((width * height) as usize);
+    for pixel in image.pixels() {
+        let color = [pixel.0[0], pixel.0[1], pixel.0[2]];
+        let index = match palette.iter().position(|el| *el == color) {
+            Some(index) => index,
+            None => {
+                palette.push(color);
+                palette.len() - 1
+            },
+        };
+        indices.push(index);
+    }
+
+    // Pixel aspect ratio 1:1 with the raster size, then the palette in RGB percentages
+    let mut out = format!("\x1bP0;1;0q\"1;1;{};{}",width,height);
+    let percent = |channel: u8| (channel as u32 * 100 + 127) / 255;
+    for (i, [r, g, b]) in palette.iter().enumerate() {
+        out.push_str(&format!("#{};2;{};{};{}",i,percent(*r),percent(*g),percent(*b)));
+    }
+    // Each band is six rows of pixels, drawn once per color with $ returning to the start of the band
+    for band in (0..height as usize).step_by(6) {
+        let mut first = true;
+        for color in 0..palette.len() {
+            let mut sixels: Vec<u8> = (0..width as usize).map(|x| {
+                let mut bits = 0;
+                for dy in 0..std::cmp::min(6,height as usize - band) {
+                    if indices[(band + dy) * width as usize + x] == color {
+                        bits |= 1 << dy;
+                    }
+                }
+                return bits;
+            }).collect();
+            // Pixels after the last one of this color are left as they are
+            while sixels.last() == Some(&0) {
+                sixels.pop();
+            }
+            if sixels.is_empty() {
+                continue;
+            }
+            if !first {
+                out.push('$');
+            }
+            first = false;
+            out.push_str(&format!("#{}",color));
+            push_sixel_runs(&mut out,&sixels);
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\");
+    return out;
+}
+
+/// Write sixels as characters, with runs of more than three shortened to !count followed by the character
+fn push_sixel_runs(out: &mut String, sixels: &[u8]) {
+    let mut i = 0;
+    while i < sixels.len() {
+        let ch = (sixels[i] + 63) as char;
+        let run = sixels[i..].iter().take_while(|bits| **bits == sixels[i]).count();
+        if run > 3 {
+            out.push_str(&format!("!{}{}",run,ch));
+        }
+        else {
+            out.extend(std::iter::repeat_n(ch,run));
+        }
+        i += run;
+    }
+}
+
+/// Wrap PNG data in Kitty graphics protocol escape sequences that display it at the cursor
+/// The data is base64 encoded and split into chunks of at most 4096 characters.
+/// Responses from the terminal are suppressed so they do not end up in the input.
+pub fn kitty(png: &[u8]) -> String {
+    let encoded = base64(png);
+    let chunks: Vec<&str> = encoded.as_bytes().chunks(KITTY_CHUNK_SIZE)
+        .map(|chunk| std::str::from_utf8(chunk).unwrap())
+        .collect();
+    let mut out = String::with_capacity(encoded.len() + chunks.len() * 16);
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = if i + 1 < chunks.len() {1} else {0};
+        if i == 0 {
+            out.push_str(&format!("\x1b_Ga=T,f=100,q=2,m={};{}\x1b\\",more,chunk));
+        }
+        else {
+            out.push_str(&format!("\x1b_Gm={};{}\x1b\\",more,chunk));
+        }
+    }
+    return out;
+}
+
+/// Encode data as standard base64 with padding
+fn base64(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let group = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[(group >> (18 - 6 * i) & 63) as usize] as char);
+            }
+            else {
+                out.push('=');
+            }
+        }
+    }
+    return out;
+}
@@ -0,0 +1,169 @@
+/// Number of modules stored in a word
+const WORD_BITS: usize = 64;
+
+/// Rectangular grid of modules stored as bits, where set bits are dark
+/// Each row is packed into 64 bit words, with the module at x in bit x % 64 of word x / 64.
+/// Bits past the width are always clear, so whole words can be counted and compared.
+#[derive(Debug,Clone,PartialEq,Eq,Hash,Default)]
+pub struct Grid {
+    width: usize,
+    height: usize,
+    words_per_row: usize,
+    words: Vec<u64>,
+}
+
+impl Grid {
+    /// Creates a grid of light modules
+    pub fn new(width: usize, height: usize) -> Grid {
+        let words_per_row = width.div_ceil(WORD_BITS);
+        return Grid { width, height, words_per_row, words: vec![0; words_per_row * height] };
+    }
+
+    /// Creates a grid from rows where 1 is dark, rows must have the same length
+    pub fn from_rows(rows: &[Vec<u8>]) -> Grid {
+        let mut grid = Grid::new(rows.first().map_or(0, |row| row.len()),rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            assert_eq!(row.len(),grid.width,"Row {} has a different length",y);
+            for (x, el) in row.iter().enumerate() {
+                grid.set(x,y,*el & 1 == 1);
+            }
+        }
+        return grid;
+    }
+
+    /// Returns the rows of the grid with 1 for dark and 0 for light
+    pub fn to_rows(&self) -> Vec<Vec<u8>> {
+        return self.rows().map(|row| row.map(|dark| dark as u8).collect()).collect();
+    }
+
+    pub fn width(&self) -> usize {
+        return self.width;
+    }
+
+    pub fn height(&self) -> usize {
+        return self.height;
+    }
+
+    /// Returns whether the module at (x, y) is dark
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        assert!(x < self.width && y < self.height,"({}, {}) is outside of the grid",x,y);
+        return self.words[y * self.words_per_row + x / WORD_BITS] >> (x % WORD_BITS) & 1 == 1;
+    }
+
+    /// Sets the module at (x, y) to dark or light
+    pub fn set(&mut self, x: usize, y: usize, dark: bool) {
+        assert!(x < self.width && y < self.height,"({}, {}) is outside of the grid",x,y);
+        let word = &mut self.words[y * self.words_per_row + x / WORD_BITS];
+        let bit = 1 << (x % WORD_BITS);
+        if dark {
+            *word |= bit;
+        }
+        else {
+            *word &= !bit;
+        }
+    }
+
+    /// Swaps the module at (x, y) between dark and light
+    pub fn toggle(&mut self, x: usize, y: usize) {
+        let dark = self.get(x,y);
+        self.set(x,y,!dark);
+    }
+
+    /// Returns the words of row y
+    pub fn row_words(&self, y: usize) -> &[u64] {
+        return &self.words[y * self.words_per_row..(y + 1) * self.words_per_row];
+    }
+
+    /// Returns the words of row y for writing, bits past the width must stay clear
+    pub(crate) fn row_words_mut(&mut self, y: usize) -> &mut [u64] {
+        return &mut self.words[y * self.words_per_row..(y + 1) * self.words_per_row];
+    }
+
+    /// Returns the bits of the last word of a row that are inside the grid
+    pub(crate) fn last_word_mask(&self) -> u64 {
+        return match self.width % WORD_BITS {
+            0 => u64::MAX,
+            bits => (1 << bits) - 1,
+        };
+    }
+
+    /// Iterates over the modules of row y from left to right
+    pub fn row(&self, y: usize) -> impl Iterator<Item = bool> + '_ {
+        let words = self.row_words(y);
+        return (0..self.width).map(move |x| words[x / WORD_BITS] >> (x % WORD_BITS) & 1 == 1);
+    }
+
+    /// Iterates over the rows from top to bottom
+    pub fn rows(&self) -> impl Iterator<Item = impl Iterator<Item = bool> + '_> + '_ {
+        return (0..self.height).map(move |y| self.row(y));
+    }
+
+    /// Iterates over every module as (x, y, dark), row by row
+    pub fn iter(&self) -> impl Iterator<Item = (usize,usize,bool)> + '_ {
+        return (0..self.height).flat_map(move |y| self.row(y).enumerate().map(move |(x, dark)| (x, y, dark)));
+    }
+
+    /// Iterates over the (start, length) of each horizontal run of dark modules in row y
+    pub fn dark_runs(&self, y: usize) -> impl Iterator<Item = (usize,usize)> + '_ {
+        let mut x = 0;
+        return std::iter::from_fn(move || {
+            while x < self.width && !self.get(x,y) {
+                x += 1;
+            }
+            if x == self.width {
+                return None;
+            }
+            let start = x;
+            while x < self.width && self.get(x,y) {
+                x += 1;
+            }
+            return Some((start, x - start));
+        });
+    }
+
+    /// Returns the number of dark modules
+    pub fn count_dark(&self) -> usize {
+        return self.words.iter().map(|word| word.count_ones() as usize).sum();
+    }
+
+    /// Returns the grid flipped over its main diagonal, so columns become rows
+    /// Works on blocks of 64 by 64 modules at a time
+    pub fn transpose(&self) -> Grid {
+        let mut out = Grid::new(self.height,self.width);
+        let mut block = [0u64; WORD_BITS];
+        for block_y in 0..self.height.div_ceil(WORD_BITS) {
+            for block_x in 0..self.words_per_row {
+                for (i, word) in block.iter_mut().enumerate() {
+                    let y = block_y * WORD_BITS + i;
+                    *word = if y < self.height {self.words[y * self.words_per_row + block_x]} else {0};
+                }
+                transpose_block(&mut block);
+                for (i, word) in block.iter().enumerate() {
+                    let y = block_x * WORD_BITS + i;
+                    if y < out.height {
+                        out.words[y * out.words_per_row + block_y] = *word;
+                    }
+                }
+            }
+        }
+        return out;
+    }
+}
+
+/// Transposes a 64 by 64 block of bits in place, where bit j of word i is the module at (j, i)
+/// Swaps the off diagonal quarters of ever smaller squares
+fn transpose_block(block: &mut [u64; WORD_BITS]) {
+    let mut size = 32;
+    let mut mask: u64 = 0x0000_0000_FFFF_FFFF;
+    while size != 0 {
+        let mut i = 0;
+        while i < WORD_BITS {
+            let swap = ((block[i] >> size) ^ block[i + size]) & mask;
+            block[i] ^= swap << size;
+            block[i + size] ^= swap;
+            i = (i + size + 1) & !size;
+        }
+        size >>= 1;
+        mask ^= mask << size;
+    }
+}
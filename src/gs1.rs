@@ -0,0 +1,259 @@
+use crate::bits;
+use crate::error::QrError;
+use crate::error_correction::ECLevel;
+use crate::qr::Encoding;
+use crate::segment::{self, Segment};
+
+/// Group separator that ends a variable length element
+pub const GS: char = '\x1D';
+
+/// FNC1 mode indicator written before the segments
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum Fnc1 {
+    /// Data follows the GS1 application identifier standards
+    First,
+    /// Data follows an industry application identified by the codeword of the application indicator,
+    /// which is 0 to 99 for two digits or the ASCII value of a letter plus 100
+    Second(u8),
+}
+
+impl Fnc1 {
+    /// Returns the mode indicator bits, followed by the application indicator for Second
+    pub(crate) fn header_bits(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(12);
+        match self {
+            Fnc1::First => bits::push_to_bit_list(&mut out,5,4),
+            Fnc1::Second(indicator) => {
+                bits::push_to_bit_list(&mut out,9,4);
+                bits::push_to_bit_list(&mut out,*indicator as u32,8);
+            },
+        }
+        return out;
+    }
+}
+
+/// Application identifier and the value it is followed by
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct Element {
+    pub ai: String,
+    pub value: String,
+}
+
+/// Stores (first AI, last AI, minimum length, maximum length, numeric only, ends in a check digit)
+/// Each row covers the identifiers of the same length between first and last
+const AI_TABLE: [(&str,&str,usize,usize,bool,bool);43] = [
+    ("00", "00", 18, 18, true, true),
+    ("01", "02", 14, 14, true, true),
+    ("10", "10", 1, 20, false, false),
+    ("11", "13", 6, 6, true, false),
+    ("15", "17", 6, 6, true, false),
+    ("20", "20", 2, 2, true, false),
+    ("21", "22", 1, 20, false, false),
+    ("235", "235", 1, 28, false, false),
+    ("240", "241", 1, 30, false, false),
+    ("242", "242", 1, 6, true, false),
+    ("243", "243", 1, 20, false, false),
+    ("250", "251", 1, 30, false, false),
+    ("254", "254", 1, 20, false, false),
+    ("30", "30", 1, 8, true, false),
+    ("3100", "3169", 6, 6, true, false),
+    ("3200", "3379", 6, 6, true, false),
+    ("3400", "3579", 6, 6, true, false),
+    ("3600", "3699", 6, 6, true, false),
+    ("37", "37", 1, 8, true, false),
+    ("3900", "3909", 1, 15, true, false),
+    ("3910", "3919", 4, 18, true, false),
+    ("3920", "3929", 1, 15, true, false),
+    ("3930", "3939", 4, 18, true, false),
+    ("400", "400", 1, 30, false, false),
+    ("401", "401", 1, 30, false, false),
+    ("402", "402", 17, 17, true, true),
+    ("403", "403", 1, 30, false, false),
+    ("410", "417", 13, 13, true, true),
+    ("420", "420", 1, 20, false, false),
+    ("421", "421", 4, 12, false, false),
+    ("422", "422", 3, 3, true, false),
+    ("423", "423", 3, 15, true, false),
+    ("424", "426", 3, 3, true, false),
+    ("7001", "7001", 13, 13, true, false),
+    ("7003", "7003", 10, 10, true, false),
+    ("8003", "8003", 14, 30, false, false),
+    ("8004", "8004", 1, 30, false, false),
+    ("8006", "8006", 18, 18, true, false),
+    ("8017", "8018", 18, 18, true, true),
+    ("8020", "8020", 1, 25, false, false),
+    ("8200", "8200", 1, 70, false, false),
+    ("90", "90", 1, 30, false, false),
+    ("91", "99", 1, 90, false, false),
+];
+
+/// First two digits of the identifiers whose elements never need a separator after them
+/// 23 is left out: the GS1 table only reserves it, and AI 235 which starts with it has a variable length
+const PREDEFINED_LENGTH: [&str;22] = [
+    "00", "01", "02", "03", "04", "11", "12", "13", "14", "15", "16",
+    "17", "18", "19", "20", "31", "32", "33", "34", "35", "36", "41",
+];
+
+/// Characters allowed in the values of GS1 elements, along with letters and digits
+const GS1_PUNCTUATION: &str = "!\"%&'()*+,-./:;<=>?_";
+
+/// Returns the row of AI_TABLE describing ai
+fn table_get(ai: &str) -> Option<(usize,usize,bool,bool)> {
+    return AI_TABLE.iter()
+        .find(|(first, last, ..)| first.len() == ai.len() && *first <= ai && ai <= *last)
+        .map(|(_, _, min, max, numeric, check)| (*min, *max, *numeric, *check));
+}
+
+/// Returns the check digit of a GTIN, SSCC or GLN given the digits before it
+/// Digits are weighted 3 and 1 alternately, starting with 3 on the right
+pub fn check_digit(digits: &str) -> Option<char> {
+    let mut sum = 0;
+    for (i, ch) in digits.chars().rev().enumerate() {
+        sum += ch.to_digit(10)? * if i % 2 == 0 {3} else {1};
+    }
+    return char::from_digit((10 - sum % 10) % 10,10);
+}
+
+/// Checks the length, characters and check digit of the value of an element
+pub fn validate(element: &Element) -> Result<(),QrError> {
+    let ai = &element.ai;
+    let (min, max, numeric, check) = table_get(ai).ok_or_else(|| QrError::UnknownApplicationIdentifier(ai.clone()))?;
+    let value = &element.value;
+    let len = value.chars().count();
+    let valid_char = |ch: char| if numeric {ch.is_ascii_digit()} else {ch.is_ascii_alphanumeric() || GS1_PUNCTUATION.contains(ch)};
+    if len < min || len > max || !value.chars().all(valid_char) {
+        return Err(QrError::InvalidElement(ai.clone()));
+    }
+    if check {
+        let expected = check_digit(&value[..len - 1]).unwrap();
+        if !value.ends_with(expected) {
+            return Err(QrError::CheckDigitMismatch { ai: ai.clone(), expected });
+        }
+    }
+    return Ok(());
+}
+
+/// Parses and validates elements written as "(01)09506000134352(17)201225(10)ABC123"
+/// Values cannot contain an opening parenthesis
+pub fn parse(str: &str) -> Result<Vec<Element>,QrError> {
+    let mut out = Vec::new();
+    let mut rest = str;
+    while !rest.is_empty() {
+        let (ai, after) = rest.strip_prefix('(')
+            .and_then(|rest| rest.split_once(')'))
+            .ok_or(QrError::MalformedElementString)?;
+        if !(2..=4).contains(&ai.len()) || !ai.chars().all(|ch| ch.is_ascii_digit()) {
+            return Err(QrError::MalformedElementString);
+        }
+        let end = after.find('(').unwrap_or(after.len());
+        let element = Element { ai: ai.to_string(), value: after[..end].to_string() };
+        validate(&element)?;
+        out.push(element);
+        rest = &after[end..];
+    }
+    if out.is_empty() {
+        return Err(QrError::MalformedElementString);
+    }
+    return Ok(out);
+}
+
+/// Joins elements into the string written in the code, with GS after every variable length element but the last
+pub fn element_string(elements: &[Element]) -> String {
+    let mut out = String::new();
+    for (i, element) in elements.iter().enumerate() {
+        out.push_str(&element.ai);
+        out.push_str(&element.value);
+        if i + 1 != elements.len() && !PREDEFINED_LENGTH.contains(&&element.ai[..2]) {
+            out.push(GS);
+        }
+    }
+    return out;
+}
+
+/// Splits a decoded element string back into validated elements
+/// Elements end at a GS, or after their fixed length if their identifier has a predefined length
+pub fn parse_element_string(str: &str) -> Result<Vec<Element>,QrError> {
+    let mut out = Vec::new();
+    let mut rest = str;
+    while !rest.is_empty() {
+        let ai = (2..=4)
+            .filter_map(|len| rest.get(..len))
+            .find(|ai| ai.chars().all(|ch| ch.is_ascii_digit()) && table_get(ai).is_some())
+            .ok_or_else(|| QrError::UnknownApplicationIdentifier(rest.chars().take(4).collect()))?;
+        let (min, max, ..) = table_get(ai).unwrap();
+        let after = &rest[ai.len()..];
+        let end = if min == max && PREDEFINED_LENGTH.contains(&&ai[..2]) {
+            std::cmp::min(min,after.len())
+        } else {
+            after.find(GS).unwrap_or(after.len())
+        };
+        let element = Element { ai: ai.to_string(), value: after[..end].to_string() };
+        validate(&element)?;
+        out.push(element);
+        rest = after[end..].strip_prefix(GS).unwrap_or(&after[end..]);
+    }
+    return Ok(out);
+}
+
+/// Returns a segment of str for use after an FNC1 mode indicator
+/// Alphanumeric segments write GS as % and a literal % as %%
+pub(crate) fn fnc1_segment(str: &str, enc: Encoding) -> Segment {
+    if enc != Encoding::Alphanumeric {
+        return Segment::new(str,enc);
+    }
+    let escaped: String = str.chars().map(|ch| match ch {
+        GS => "%".to_string(),
+        '%' => "%%".to_string(),
+        _ => ch.to_string(),
+    }).collect();
+    return Segment::new(&escaped,enc);
+}
+
+/// Splits str into the segments that need the fewest bits for use after an FNC1 mode indicator
+/// GS is written as % in alphanumeric segments and as 0x1D in byte segments
+pub(crate) fn fnc1_segments(str: &str, ec_level: ECLevel) -> Result<Vec<Segment>,QrError> {
+    return Ok(restore_separators(segment::optimize(&swap_separators(str),ec_level)?));
+}
+
+/// Same as fnc1_segments using the length field sizes of version
+pub(crate) fn fnc1_segments_for_version(str: &str, version: u8) -> Result<Vec<Segment>,QrError> {
+    return Ok(restore_separators(segment::optimize_for_version(&swap_separators(str),version)?));
+}
+
+/// Swaps GS and %, so the optimizer can place separators in alphanumeric segments
+/// while literal % characters are kept in byte segments
+fn swap_separators(str: &str) -> String {
+    return str.chars().map(|ch| match ch {
+        GS => '%',
+        '%' => GS,
+        _ => ch,
+    }).collect();
+}
+
+/// Undoes swap_separators in segments where % does not stand for GS
+fn restore_separators(segments: Vec<Segment>) -> Vec<Segment> {
+    return segments.into_iter().map(|seg| match seg.enc {
+        Encoding::Byte | Encoding::ECI => Segment::new(&swap_separators(&seg.data),seg.enc),
+        _ => seg,
+    }).collect();
+}
+
+/// Returns the text of an alphanumeric segment read after an FNC1 mode indicator,
+/// with % replaced by GS and %% by %
+pub(crate) fn unescape_alphanumeric(str: &str) -> String {
+    let mut out = String::with_capacity(str.len());
+    let mut chars = str.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch != '%' {
+            out.push(ch);
+        }
+        else if chars.peek() == Some(&'%') {
+            chars.next();
+            out.push('%');
+        }
+        else {
+            out.push(GS);
+        }
+    }
+    return out;
+}
@@ -1,30 +1,86 @@
-mod bits;
+#![allow(clippy::needless_return)]
+pub mod bits;
+pub mod builder;
+pub mod decode;
 mod tests {
     mod test_bits;
+    mod test_builder;
+    mod test_decode;
     mod test_ec;
+    mod test_graphics;
+    mod test_grid;
+    mod test_gs1;
     mod test_mask;
     mod test_metadata;
+    mod test_micro;
+    mod test_print;
     mod test_qr;
+    mod test_raster;
+    mod test_reader;
+    mod test_rmqr;
+    mod test_segment;
+    mod test_structured;
+    mod test_svg;
+    mod test_text;
 }
+pub mod error;
 pub mod error_correction;
+pub mod graphics;
+pub mod grid;
+pub mod gs1;
+pub mod print;
 pub mod qr;
-mod mask;
+pub mod raster;
+pub mod reader;
+pub mod rmqr;
+pub mod segment;
+pub mod structured;
+pub mod svg;
+pub mod text;
+pub mod mask;
 mod patterns;
 mod message_layout;
-mod metadata;
+pub mod metadata;
+pub mod micro;
 
 use wasm_bindgen::prelude::*;
 use crate::error_correction::ECLevel;
 
+/// Returns the modules of a qr code for text, row by row
+/// Errors are thrown as JS exceptions
 #[wasm_bindgen]
-pub fn make_qr(text: &str) -> Box<[i32]> {
+pub fn make_qr(text: &str) -> Result<Box<[i32]>, JsError> {
     let ec_level = ECLevel::Q;
-    let encoding: qr::Encoding = bits::get_encoding(text);
-    qr::QR::new(text,encoding,ec_level).bitmap
-        .into_iter()
-        .map(|vec| vec.into_iter().map(|val| val as i32).collect::<Vec<i32>>())
-        .flatten()
-        .collect::<Vec<i32>>().into_boxed_slice()
+    return Ok(qr::QR::new_optimized(text,ec_level)?.bitmap
+        .iter()
+        .map(|(_, _, dark)| dark as i32)
+        .collect::<Vec<i32>>().into_boxed_slice());
+}
 
+/// Returns the penalties of every mask of the qr code for text as an object
+/// ec_level is one of L, M, Q or H and scorer is legacy or iso, as with --ec and --mask-scorer in the CLI
+/// Errors are thrown as JS exceptions
+#[wasm_bindgen]
+pub fn mask_report(text: &str, ec_level: &str, scorer: &str) -> Result<JsValue, JsError> {
+    let ec_level = match ec_level.to_uppercase().as_str() {
+        "L" => ECLevel::L,
+        "M" => ECLevel::M,
+        "Q" => ECLevel::Q,
+        "H" => ECLevel::H,
+        _ => return Err(JsError::new(&format!("{} is not one of L, M, Q or H",ec_level))),
+    };
+    let scorer = match scorer.to_lowercase().as_str() {
+        "legacy" => mask::MaskScorer::Legacy,
+        "iso" => mask::MaskScorer::Iso,
+        _ => return Err(JsError::new(&format!("{} is not one of legacy or iso",scorer))),
+    };
+    let (_, report) = builder::QrBuilder::new(text).ec_level(ec_level).mask_scorer(scorer).build_with_mask_report()?;
+    return Ok(serde_wasm_bindgen::to_value(&report)?);
+}
 
-}
\ No newline at end of file
+/// Returns the maximum number of characters of every version, error correction level and mode, flattened
+/// Ordered by version 1 to 40, then error correction level L, M, Q, H, then numeric, alphanumeric, byte and kanji mode
+#[wasm_bindgen]
+pub fn capacity_table() -> Box<[u32]> {
+    return metadata::capacity_table().iter().flatten().flatten().map(|chars| *chars as u32).collect();
+}
@@ -1,63 +1,468 @@
-mod bits;
-mod tests {
-    mod test_bits;
-    mod test_ec;
-    mod test_mask;
-    mod test_metadata;
-    mod test_qr;
-}
-pub mod error_correction;
-pub mod qr;
-mod mask;
-mod patterns;
-mod message_layout;
-mod metadata;
-extern crate image;
-use std::io::Write;
-
-fn main() -> Result<(),std::io::Error> {
-
-    // Read string to encode
-    let mut str = String::new();
-    print!("Enter encoded string: ");
-    std::io::stdout().flush()?;
-    std::io::stdin().read_line(&mut str).unwrap();
-    // Remove trailing new line
-    if str.ends_with('\n') {
-        str = str[..str.len()-1].to_string();
-    }
-
-    // Read error correction level
-    let mut ec_str = String::new();
-    print!("Enter error correction level (LMQH): ");
-    std::io::stdout().flush()?;
-    std::io::stdin().read_line(&mut ec_str).unwrap();
-    let ec_level = match ec_str.to_lowercase().chars().nth(0).unwrap() {
-        'l' => error_correction::ECLevel::L,
-        'q' => error_correction::ECLevel::Q,
-        'm' => error_correction::ECLevel::M,
-        'h' => error_correction::ECLevel::H,
-        _ => panic!("Invalid EC format {}",ec_str),
-    };
-
-    let encoding: qr::Encoding = bits::get_encoding(str.as_str());
-
-    let file_path = "output.png";
-    
-    let qr_code = qr::QR::new(&str,encoding,ec_level);
-
-    // Print information on QR encoding
-    println!("Encoded string: \"{}\"",str);
-    println!("Encoding type: {:?}",encoding);
-    println!("Version: {}",qr_code.version);
-    println!("Error correction: {:?}",qr_code.ec_level);
-    println!("Number of codewords: {}",metadata::blocks_table_get(qr_code.version,qr_code.ec_level).0);
-    println!("Max number of modules: {}", qr::QR::get_available_modules(qr_code.version.into()));
-    println!("Mask index: {}",qr_code.mask_index);
-
-    if qr_code.to_image(file_path).is_err() {
-        panic!("Unable to save to {}",file_path);
-    }
-    println!("Saved to {}",file_path);
+#![allow(clippy::needless_return)]
+use qr_generator::builder::QrBuilder;
+use qr_generator::error::QrError;
+use qr_generator::error_correction::ECLevel;
+use qr_generator::print::{Length, PrintColor, PrintOptions};
+use qr_generator::qr::{Encoding, QR};
+use qr_generator::raster::{RasterFormat, RasterOptions};
+use qr_generator::segment::{self, Segment};
+use qr_generator::svg::SvgOptions;
+use qr_generator::text::{TerminalOptions, TerminalStyle, TextOptions};
+use qr_generator::mask::MaskScorer;
+use qr_generator::metadata;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use std::io::{Read, Write};
+use std::process::ExitCode;
+
+mod batch;
+
+/// Exit code for errors that have no code of their own, such as failing to write the output
+const EXIT_ERROR: u8 = 1;
+/// Exit code when the data does not fit in the largest allowed version
+const EXIT_DATA_TOO_LONG: u8 = 3;
+/// Exit code when a character cannot be written in the chosen encoding
+const EXIT_INVALID_CHARACTER: u8 = 4;
+
+/// Generate a qr code from text
+///
+/// Exit codes: 0 on success, 1 on other errors, 2 on invalid arguments,
+/// 3 when the data is too long and 4 when a character does not fit the encoding
+#[derive(Parser,Debug)]
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    generate: GenerateArgs,
+}
+
+#[derive(Subcommand,Debug)]
+enum Command {
+    /// Generate one code per row of a CSV or JSON Lines manifest
+    Batch(batch::BatchArgs),
+    /// Print every code of a manifest to the terminal without writing any files
+    Preview(batch::PreviewArgs),
+}
+
+/// Arguments for generating a single code
+#[derive(Args,Debug)]
+struct GenerateArgs {
+    /// Text to encode, read from stdin if neither this nor --input is given
+    data: Option<String>,
+    /// Read the text to encode from a file, or from stdin with -
+    #[arg(short, long, conflicts_with = "data")]
+    input: Option<String>,
+    /// Error correction level
+    #[arg(long, default_value = "M", value_parser = parse_ec_level)]
+    ec: ECLevel,
+    /// Always use this version instead of the smallest that fits
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=40))]
+    version: Option<u8>,
+    /// Always use this mask instead of the one with the lowest penalty
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=7))]
+    mask: Option<u8>,
+    /// Penalty rules used to choose the mask
+    #[arg(long, value_enum, default_value_t = MaskScorerArg::Legacy, conflicts_with = "mask")]
+    mask_scorer: MaskScorerArg,
+    /// Encoding mode, auto splits the text into the segments that need the fewest bits
+    #[arg(long, value_enum, default_value_t = EncodingArg::Auto)]
+    encoding: EncodingArg,
+    /// File to write to or - for stdout, defaults to stdout for terminal formats and output.<format> otherwise
+    #[arg(short, long)]
+    output: Option<String>,
+    /// Output format, taken from the extension of --output if not given
+    #[arg(short, long, value_enum)]
+    format: Option<Format>,
+    /// Width of the light border around the code in modules
+    #[arg(long, default_value_t = 4)]
+    quiet_zone: u32,
+    /// Size of a module in pixels
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u32).range(1..))]
+    scale: u32,
+    /// Size of a module in pdf and eps, in mm or pt such as 0.5mm or 1.5pt
+    #[arg(long, default_value = "0.5mm", value_parser = parse_length)]
+    module_size: Length,
+    /// Margin added around the quiet zone in pdf and eps for the printer to cut into, in mm or pt
+    #[arg(long, default_value = "0pt", value_parser = parse_length)]
+    bleed: Length,
+    /// Swap dark and light in the ansi and utf8 formats, for terminals with light text on a dark background
+    #[arg(long)]
+    invert: bool,
+    /// Print the segments, version, error correction level and mask to stderr
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+#[derive(ValueEnum,Copy,Clone,Debug,PartialEq,Eq)]
+enum EncodingArg {
+    Auto,
+    Numeric,
+    Alphanumeric,
+    Byte,
+    Kanji,
+    /// UTF-8 bytes after an ECI header
+    Utf8,
+}
+
+#[derive(ValueEnum,Copy,Clone,Debug,PartialEq,Eq)]
+enum MaskScorerArg {
+    /// Penalties used by earlier versions, so codes stay the same
+    Legacy,
+    /// ISO/IEC 18004 penalties, matching the masks chosen by other encoders
+    Iso,
+}
+
+impl MaskScorerArg {
+    fn scorer(self) -> MaskScorer {
+        return match self {
+            MaskScorerArg::Legacy => MaskScorer::Legacy,
+            MaskScorerArg::Iso => MaskScorer::Iso,
+        };
+    }
+}
+
+#[derive(ValueEnum,Copy,Clone,Debug,PartialEq,Eq)]
+enum Format {
+    Png,
+    Jpeg,
+    Bmp,
+    Gif,
+    Webp,
+    Svg,
+    /// Two characters per module, # for dark and space for light
+    Txt,
+    /// Spaces with ANSI background colors, for printing to a terminal
+    Ansi,
+    /// Unicode half blocks with two rows of modules per line, for printing to a terminal
+    Utf8,
+    /// DEC Sixel graphics, for printing to a terminal that supports them
+    Sixel,
+    /// Kitty terminal graphics protocol carrying a PNG
+    Kitty,
+    /// PDF with trim and bleed boxes and CMYK colors
+    Pdf,
+    /// Encapsulated PostScript with CMYK colors
+    Eps,
+}
+
+impl Format {
+    /// Returns the format matching the extension of path
+    fn from_path(path: &str) -> Option<Format> {
+        let extension = std::path::Path::new(path).extension()?.to_str()?.to_lowercase();
+        return match extension.as_str() {
+            "jpg" => Some(Format::Jpeg),
+            "text" => Some(Format::Txt),
+            "ans" => Some(Format::Ansi),
+            "six" => Some(Format::Sixel),
+            _ => Format::from_str(&extension,true).ok(),
+        };
+    }
+
+    fn extension(&self) -> &'static str {
+        return match self {
+            Format::Png => "png",
+            Format::Jpeg => "jpg",
+            Format::Bmp => "bmp",
+            Format::Gif => "gif",
+            Format::Webp => "webp",
+            Format::Svg => "svg",
+            Format::Txt => "txt",
+            Format::Ansi => "ans",
+            Format::Utf8 => "txt",
+            Format::Sixel => "six",
+            Format::Kitty => "kitty",
+            Format::Pdf => "pdf",
+            Format::Eps => "eps",
+        };
+    }
+
+    /// Whether the format is text meant to be printed, which is written to stdout by default
+    fn is_terminal(&self) -> bool {
+        return matches!(self, Format::Txt | Format::Ansi | Format::Utf8 | Format::Sixel | Format::Kitty);
+    }
+}
+
+/// Size and colors used by every output format
+#[derive(Debug,Clone,PartialEq)]
+struct Style {
+    scale: u32,
+    quiet_zone: u32,
+    foreground: [u8; 4],
+    background: [u8; 4],
+    /// Swap dark and light in terminal formats
+    invert: bool,
+    /// Size of a module in print formats
+    module_size: Length,
+    /// Margin around the quiet zone in print formats
+    bleed: Length,
+}
+
+impl Style {
+    fn new(scale: u32, quiet_zone: u32) -> Style {
+        let defaults = RasterOptions::default();
+        return Style {
+            scale,
+            quiet_zone,
+            foreground: defaults.foreground,
+            background: defaults.background,
+            invert: false,
+            module_size: Length::Millimeters(0.5),
+            bleed: Length::Points(0.0),
+        };
+    }
+}
+
+/// Information on how a code was encoded
+#[derive(Serialize,Debug,Clone,PartialEq)]
+struct Summary {
+    version: u8,
+    ec_level: String,
+    mask: u8,
+    /// Modes of the segments joined by +
+    encoding: String,
+    /// Bits taken by the segments and their headers
+    data_bits: usize,
+    /// Bits available for data in the version and error correction level
+    capacity_bits: usize,
+}
+
+impl Summary {
+    fn new(qr_code: &QR, segments: &[Segment]) -> Summary {
+        let encoding: Vec<String> = segments.iter().map(|seg| format!("{:?}",seg.enc)).collect();
+        return Summary {
+            version: qr_code.version,
+            ec_level: format!("{:?}",qr_code.ec_level),
+            mask: qr_code.mask_index,
+            encoding: encoding.join("+"),
+            data_bits: segments.iter().map(|seg| seg.num_bits(qr_code.version).unwrap_or(0)).sum(),
+            capacity_bits: metadata::blocks_table_get(qr_code.version,qr_code.ec_level).0 * 8,
+        };
+    }
+
+    /// Percentage of the capacity taken by the data, rounded down to a tenth
+    fn usage(&self) -> f64 {
+        return (self.data_bits * 1000 / self.capacity_bits) as f64 / 10.0;
+    }
+}
+
+fn parse_ec_level(str: &str) -> Result<ECLevel,String> {
+    return match str.to_uppercase().as_str() {
+        "L" => Ok(ECLevel::L),
+        "M" => Ok(ECLevel::M),
+        "Q" => Ok(ECLevel::Q),
+        "H" => Ok(ECLevel::H),
+        _ => Err(format!("{} is not one of L, M, Q or H",str)),
+    };
+}
+
+/// Parses a length in millimetres or points such as 0.5mm or 1.5pt
+fn parse_length(str: &str) -> Result<Length,String> {
+    let str = str.trim().to_lowercase();
+    let (value, unit): (&str, fn(f64) -> Length) = match (str.strip_suffix("mm"), str.strip_suffix("pt")) {
+        (Some(value), _) => (value, Length::Millimeters),
+        (_, Some(value)) => (value, Length::Points),
+        _ => return Err(format!("{} must end in mm or pt",str)),
+    };
+    return match value.trim().parse::<f64>() {
+        Ok(value) if value.is_finite() && value >= 0.0 => Ok(unit(value)),
+        _ => Err(format!("{} is not a length",str)),
+    };
+}
+
+/// Returns the text to encode from the argument, a file or stdin
+/// A single trailing newline is removed from files and stdin
+fn read_data(args: &GenerateArgs) -> std::io::Result<String> {
+    if let Some(data) = &args.data {
+        return Ok(data.clone());
+    }
+    let mut data = String::new();
+    match args.input.as_deref() {
+        None | Some("-") => std::io::stdin().read_to_string(&mut data)?,
+        Some(path) => std::fs::File::open(path)?.read_to_string(&mut data)?,
+    };
+    if data.ends_with('\n') {
+        data.pop();
+        if data.ends_with('\r') {
+            data.pop();
+        }
+    }
+    return Ok(data);
+}
+
+/// Returns the qr code and the segments it was made from
+fn build(args: &GenerateArgs, data: &str) -> Result<(QR,Vec<Segment>),QrError> {
+    let enc = match args.encoding {
+        EncodingArg::Auto => None,
+        EncodingArg::Numeric => Some(Encoding::Numeric),
+        EncodingArg::Alphanumeric => Some(Encoding::Alphanumeric),
+        EncodingArg::Byte => Some(Encoding::Byte),
+        EncodingArg::Kanji => Some(Encoding::Kanji),
+        EncodingArg::Utf8 => Some(Encoding::ECI),
+    };
+    let segments = match (enc, args.version) {
+        (Some(enc), _) => vec![Segment::new(data,enc)],
+        (None, Some(version)) => segment::optimize_for_version(data,version)?,
+        (None, None) => segment::optimize(data,args.ec)?,
+    };
+    let mut builder = QrBuilder::from_segments(&segments).ec_level(args.ec);
+    if let Some(version) = args.version {
+        builder = builder.version(version);
+    }
+    if let Some(mask_index) = args.mask {
+        builder = builder.mask(mask_index);
+    }
+    builder = builder.mask_scorer(args.mask_scorer.scorer());
+    return Ok((builder.build()?, segments));
+}
+
+/// Returns color as a CSS hex color, with an alpha channel if it is not opaque
+fn css_color(color: [u8; 4]) -> String {
+    let [r, g, b, a] = color;
+    if a == 255 {
+        return format!("#{:02x}{:02x}{:02x}",r,g,b);
+    }
+    return format!("#{:02x}{:02x}{:02x}{:02x}",r,g,b,a);
+}
+
+/// Renders qr_code as format into writer
+fn write_output<W: Write>(qr_code: &QR, format: Format, style: &Style, mut writer: W) -> Result<(),Box<dyn std::error::Error>> {
+    let options = RasterOptions {
+        scale: style.scale,
+        quiet_zone: style.quiet_zone,
+        foreground: style.foreground,
+        background: style.background,
+    };
+    let raster_format = match format {
+        Format::Png => RasterFormat::Png,
+        Format::Jpeg => RasterFormat::Jpeg(90),
+        Format::Bmp => RasterFormat::Bmp,
+        Format::Gif => RasterFormat::Gif,
+        Format::Webp => RasterFormat::WebP,
+        Format::Svg => {
+            let options = SvgOptions {
+                module_size: style.scale as f64,
+                quiet_zone: style.quiet_zone as usize,
+                foreground: css_color(style.foreground),
+                background: (style.background[3] != 0).then(|| css_color(style.background)),
+                ..Default::default()
+            };
+            writer.write_all(qr_code.to_svg(&options).as_bytes())?;
+            return Ok(writer.flush()?);
+        },
+        Format::Txt => {
+            let options = TextOptions { quiet_zone: style.quiet_zone as usize, ..Default::default() };
+            writer.write_all(qr_code.to_text(&options).as_bytes())?;
+            return Ok(writer.flush()?);
+        },
+        Format::Ansi | Format::Utf8 => {
+            let options = TerminalOptions {
+                style: if format == Format::Ansi {TerminalStyle::Ansi} else {TerminalStyle::HalfBlocks},
+                quiet_zone: style.quiet_zone as usize,
+                invert: style.invert,
+            };
+            writer.write_all(qr_code.to_terminal(&options).as_bytes())?;
+            return Ok(writer.flush()?);
+        },
+        Format::Sixel => {
+            writer.write_all(qr_code.to_sixel(&options).as_bytes())?;
+            writer.write_all(b"\n")?;
+            return Ok(writer.flush()?);
+        },
+        Format::Kitty => {
+            writer.write_all(qr_code.to_kitty(&options)?.as_bytes())?;
+            writer.write_all(b"\n")?;
+            return Ok(writer.flush()?);
+        },
+        Format::Pdf | Format::Eps => {
+            let color = |[r, g, b, _]: [u8; 4]| PrintColor::cmyk_from_rgb(r,g,b);
+            let options = PrintOptions {
+                module_size: style.module_size,
+                quiet_zone: style.quiet_zone as usize,
+                foreground: color(style.foreground),
+                background: (style.background[3] != 0).then(|| color(style.background)),
+                bleed: style.bleed,
+                title: None,
+                creation_date: None,
+            };
+            if format == Format::Pdf {
+                writer.write_all(&qr_code.to_pdf(&options))?;
+            }
+            else {
+                writer.write_all(qr_code.to_eps(&options).as_bytes())?;
+            }
+            return Ok(writer.flush()?);
+        },
+    };
+    qr_code.write_image(&mut writer,raster_format,&options)?;
+    writer.flush()?;
+    return Ok(());
+}
+
+fn generate(args: &GenerateArgs) -> Result<(),Box<dyn std::error::Error>> {
+    let data = read_data(args)?;
+    let (qr_code, segments) = build(args,&data)?;
+
+    let format = args.format
+        .or_else(|| args.output.as_deref().and_then(Format::from_path))
+        .unwrap_or(Format::Png);
+    let output = match &args.output {
+        Some(output) => output.clone(),
+        None if format.is_terminal() => String::from("-"),
+        None => format!("output.{}",format.extension()),
+    };
+
+    if args.verbose {
+        // Print information on QR encoding
+        for segment in segments.iter() {
+            eprintln!("Segment: {:?} \"{}\"",segment.enc,segment.data);
+        }
+        let summary = Summary::new(&qr_code,&segments);
+        eprintln!("Version: {}",summary.version);
+        eprintln!("Error correction: {}",summary.ec_level);
+        eprintln!("Capacity used: {} of {} bits ({}%)",summary.data_bits,summary.capacity_bits,summary.usage());
+        eprintln!("Mask index: {}",summary.mask);
+        let report = qr_code.mask_report(args.mask_scorer.scorer());
+        eprintln!("Mask penalties ({:?} scorer):",report.scorer);
+        eprintln!("  mask   line  square  finder  color  total  dark");
+        for penalty in report.masks.iter() {
+            let chosen = if penalty.mask_index == report.chosen {" *"} else {""};
+            eprintln!("  {:>4} {:>6} {:>7} {:>7} {:>6} {:>6} {:>4.1}%{}",penalty.mask_index,penalty.line_penalty,penalty.square_penalty,
+                penalty.finder_penalty,penalty.same_color_penalty,penalty.total,penalty.dark_ratio * 100.0,chosen);
+        }
+    }
+
+    let mut style = Style::new(args.scale,args.quiet_zone);
+    style.invert = args.invert;
+    style.module_size = args.module_size;
+    style.bleed = args.bleed;
+    if output == "-" {
+        write_output(&qr_code,format,&style,std::io::stdout().lock())?;
+    }
+    else {
+        let file = std::fs::File::create(&output).map_err(|err| format!("Unable to save to {}: {}",output,err))?;
+        write_output(&qr_code,format,&style,std::io::BufWriter::new(file))?;
+        if args.verbose {
+            eprintln!("Saved to {}",output);
+        }
+    }
     return Ok(());
 }
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match &cli.command {
+        Some(Command::Batch(args)) => batch::run(args),
+        Some(Command::Preview(args)) => batch::preview(args),
+        None => generate(&cli.generate),
+    };
+    if let Err(err) = result {
+        eprintln!("Error: {}",err);
+        return ExitCode::from(match err.downcast_ref::<QrError>() {
+            Some(QrError::DataTooLong { .. }) => EXIT_DATA_TOO_LONG,
+            Some(QrError::InvalidCharacter { .. }) => EXIT_INVALID_CHARACTER,
+            _ => EXIT_ERROR,
+        });
+    }
+    return ExitCode::SUCCESS;
+}
@@ -1,150 +1,513 @@
-use crate::qr::QR;
-use crate::patterns::PatternMaskType;
+use serde::Serialize;
+use crate::qr::{SymbolKind, QR};
+use crate::grid::Grid;
 
+/// List of mask functions, indexed by mask index
+pub(crate) const MASK_FNS: [fn(usize,usize)->bool; 8] = [
+    |x, y| {(x+y)%2 == 0},
+    |_, y| {y%2 == 0},
+    |x, _| {x%3 == 0},
+    |x, y| {(x + y)%3 == 0},
+    |x, y| {(y/2 + x/3)%2 == 0},
+    |x, y| {(x*y)%2 + (x*y)%3 == 0},
+    |x, y| {((x*y)%2+(y*x)%3)%2 == 0},
+    |x, y| {((x+y)%2+(y*x)%3)%2 == 0},
+];
+
+/// Indices in MASK_FNS of the four masks used by micro qr codes
+pub(crate) const MICRO_MASKS: [usize; 4] = [1, 4, 6, 7];
+
+/// Index in MASK_FNS of the only mask used by rectangular micro qr codes
+pub(crate) const RMQR_MASK: usize = 4;
+
+/// Rule used to score each mask when choosing the one to apply
+/// Micro qr codes are always scored with micro_score, and rectangular micro qr codes have a single mask
+#[derive(Debug,Copy,Clone,PartialEq,Eq,Hash,Default,Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MaskScorer {
+    /// Penalties this crate has always used, which keep the codes it made before unchanged
+    #[default]
+    Legacy,
+    /// Penalties N1 to N4 of ISO/IEC 18004, with the area around the symbol counted as light
+    /// Chooses the same masks as other encoders that follow the standard, such as qrcodegen
+    Iso,
+}
+
+/// Penalties of a code with one of its masks applied
+/// Micro qr codes are compared by micro_score alone, so their penalties other than total are 0
+#[derive(Debug,Clone,PartialEq,Serialize)]
+pub struct MaskPenalty {
+    pub mask_index: u8,
+    /// Runs of one color in rows and columns
+    pub line_penalty: i32,
+    /// 2x2 blocks of one color
+    pub square_penalty: i32,
+    /// Patterns that look like a finder pattern in rows and columns
+    pub finder_penalty: i32,
+    /// Distance of the share of dark modules from half
+    pub same_color_penalty: i32,
+    /// Sum of the penalties, or the negated micro_score for micro qr codes, the mask with the lowest is chosen
+    pub total: i32,
+    /// Share of the modules that are dark, from 0 to 1
+    pub dark_ratio: f64,
+}
+
+/// Penalties of every mask of a code, to show why its mask was chosen
+#[derive(Debug,Clone,PartialEq,Serialize)]
+pub struct MaskReport {
+    pub scorer: MaskScorer,
+    /// Penalties of each mask the kind of symbol has, by mask index
+    pub masks: Vec<MaskPenalty>,
+    /// Index of the mask of the code, which is not the lowest when a mask was given
+    pub chosen: u8,
+    /// Share of the modules of the code that are dark, from 0 to 1
+    pub dark_ratio: f64,
+}
+
+lazy_static::lazy_static! {
+    /// Words of each mask function for a row, indexed by mask, y % 12 and word index % 3
+    /// Every mask repeats every 6 columns and 12 rows, and 64 * 3 columns is a multiple of 6
+    static ref MASK_WORDS: [[[u64; 3]; 12]; 8] = {
+        let mut out = [[[0; 3]; 12]; 8];
+        for (func, rows) in MASK_FNS.iter().zip(out.iter_mut()) {
+            for (y, words) in rows.iter_mut().enumerate() {
+                for x in 0..192 {
+                    if func(x,y) {
+                        words[x / 64] |= 1 << (x % 64);
+                    }
+                }
+            }
+        }
+        out
+    };
+}
 
 impl QR {
     /// Find best mask and apply it
-    /// The best mask is the one with the lowest penalty score
+    /// The best mask is the one with the lowest penalty score, or the highest micro_score for micro qr codes
+    /// Each mask is scored on its own copy of the grid, in parallel with the rayon feature
     pub fn apply_masking(&mut self) {
-        let bitmap_copy = self.bitmap.clone();
-        // List of mask functions
-        let mask_fns: Vec<&dyn Fn(usize,usize)->bool> = vec![
-            &|x, y| {(x+y)%2 == 0},
-            &|_, y| {y%2 == 0},
-            &|x, _| {x%3 == 0},
-            &|x, y| {(x + y)%3 == 0},
-            &|x, y| {(y/2 + x/3)%2 == 0},
-            &|x, y| {(x*y)%2 + (x*y)%3 == 0},
-            &|x, y| {((x*y)%2+(y*x)%3)%2 == 0},
-            &|x, y| {((x+y)%2+(y*x)%3)%2 == 0},
-        ];
-        let mut best_mask_idx = 0;
-        let mut best_mask_penalty = i32::MAX;
-        // Apply each mask
-        for (i, mask) in mask_fns.iter().enumerate() {
-            self.mask_index = i as u8;
-            self.format_pattern();
-            let pen = self.mask(mask);
-            if pen < best_mask_penalty {
-                best_mask_idx = i;
-                best_mask_penalty = pen;
+        self.apply_masking_with(MaskScorer::Legacy);
+    }
+
+    /// Find the best mask as scored by scorer and apply it
+    pub fn apply_masking_with(&mut self, scorer: MaskScorer) {
+        let num_masks: u8 = match self.kind {
+            SymbolKind::Qr => MASK_FNS.len() as u8,
+            SymbolKind::Micro => MICRO_MASKS.len() as u8,
+            SymbolKind::Rmqr => 1,
+        };
+        if num_masks == 1 {
+            self.apply_mask(0);
+            return;
+        }
+        let evaluate = |mask_index: u8| {
+            let (bitmap, penalty) = self.evaluate_mask(mask_index,scorer);
+            return (mask_index, bitmap, penalty.total);
+        };
+        // Ties go to the lowest mask index, in parallel too
+        #[cfg(feature = "rayon")]
+        let best = {
+            use rayon::prelude::*;
+            (0..num_masks).into_par_iter().map(evaluate).min_by_key(|(_, _, penalty)| *penalty)
+        };
+        #[cfg(not(feature = "rayon"))]
+        let best = (0..num_masks).map(evaluate).min_by_key(|(_, _, penalty)| *penalty);
+        let (mask_index, bitmap, _) = best.unwrap();
+        self.mask_index = mask_index;
+        self.bitmap = bitmap;
+    }
+
+    /// Returns the penalties of every mask as scored by scorer, along with the mask of this code
+    /// The code must be masked, as it is after construction
+    pub fn mask_report(&self, scorer: MaskScorer) -> MaskReport {
+        // Masking again removes the mask
+        let mut unmasked = self.clone();
+        unmasked.mask(self.mask_function(self.mask_index));
+        let num_masks = match self.kind {
+            SymbolKind::Qr => MASK_FNS.len(),
+            SymbolKind::Micro => MICRO_MASKS.len(),
+            SymbolKind::Rmqr => 1,
+        };
+        return MaskReport {
+            scorer,
+            masks: (0..num_masks as u8).map(|mask_index| unmasked.evaluate_mask(mask_index,scorer).1).collect(),
+            chosen: self.mask_index,
+            dark_ratio: self.bitmap.count_dark() as f64 / (self.bitmap.width() * self.bitmap.height()) as f64,
+        };
+    }
+
+    /// Returns the grid with the mask at mask_index and its format information applied, with its penalties
+    /// The code is left unchanged, so masks can be evaluated independently
+    pub(crate) fn evaluate_mask(&self, mask_index: u8, scorer: MaskScorer) -> (Grid,MaskPenalty) {
+        let mut bitmap = self.bitmap.clone();
+        xor_mask(&mut bitmap,&self.pattern_mask,self.mask_function(mask_index));
+        let format = self.format_modules(mask_index);
+        if self.kind == SymbolKind::Micro {
+            for (y, x, bit) in format {
+                bitmap.set(x,y,bit == 1);
             }
-            self.bitmap = bitmap_copy.clone();
+            let penalty = MaskPenalty {
+                mask_index,
+                line_penalty: 0,
+                square_penalty: 0,
+                finder_penalty: 0,
+                same_color_penalty: 0,
+                total: -micro_score(&bitmap),
+                dark_ratio: bitmap.count_dark() as f64 / (bitmap.width() * bitmap.height()) as f64,
+            };
+            return (bitmap, penalty);
         }
-        // Get best masked qr code
-        self.mask_index = best_mask_idx as u8;
+        // Only the lines through the format information are scored again after it is placed
+        let mut penalty = Penalty::new(bitmap,scorer);
+        penalty.update(&format);
+        let report = penalty.report(mask_index);
+        return (penalty.rows, report);
+    }
+
+    /// Apply the mask at mask_index without comparing penalties
+    /// Micro qr codes number their masks from 0 to 3, see MICRO_MASKS
+    /// Rectangular micro qr codes only have mask 0, which is the same as mask 4 of regular codes
+    pub fn apply_mask(&mut self, mask_index: u8) {
+        self.mask_index = mask_index;
         self.format_pattern();
-        self.mask(mask_fns[best_mask_idx]);
+        self.mask(self.mask_function(mask_index));
     }
 
-    /// Appy a mask function, xors when func(x,y) returns true and pattern mask is none
-    fn mask(&mut self, func: &dyn Fn(usize,usize)->bool) -> i32 {
-        let width = self.bitmap.len();
-        for x in 0..width {
-            for y in 0..width {
-                if self.pattern_mask[y][x] == PatternMaskType::None 
-                    && func(x,y) {
-                        self.bitmap[y][x] ^= 1;
-                    }
+    /// Returns the index in MASK_FNS of the mask at mask_index for this kind of symbol
+    pub(crate) fn mask_function(&self, mask_index: u8) -> usize {
+        return match self.kind {
+            SymbolKind::Qr => mask_index as usize,
+            SymbolKind::Micro => MICRO_MASKS[mask_index as usize],
+            SymbolKind::Rmqr => RMQR_MASK,
+        };
+    }
+
+    /// Xors the mask function at index func of MASK_FNS onto every module outside of the pattern mask
+    pub(crate) fn mask(&mut self, func: usize) {
+        xor_mask(&mut self.bitmap,&self.pattern_mask,func);
+    }
+}
+
+/// Xors the mask function at index func of MASK_FNS onto every module of bitmap that is not reserved
+/// Works a row word at a time
+fn xor_mask(bitmap: &mut Grid, reserved: &Grid, func: usize) {
+    let last_word = bitmap.last_word_mask();
+    for y in 0..bitmap.height() {
+        let mask_words = &MASK_WORDS[func][y % 12];
+        let reserved = reserved.row_words(y);
+        let row = bitmap.row_words_mut(y);
+        let len = row.len();
+        for (i, word) in row.iter_mut().enumerate() {
+            let mut mask = mask_words[i % 3] & !reserved[i];
+            if i + 1 == len {
+                mask &= last_word;
             }
+            *word ^= mask;
         }
-        return sum_penalty(&self.bitmap);
     }
-    
 }
 
-/// Returns the sum penalty of a bitmap
-pub fn sum_penalty(bitmap: &Vec<Vec<u8>>) -> i32 {
-    return line_penalty(bitmap)
-        + square_penalty(bitmap)
-        + finder_penalty(bitmap)
-        + same_color_penalty(bitmap);
+/// Penalty of a grid kept per row, column and pair of rows
+/// Changing a module only scores its row, its column and the pairs of rows around it again
+struct Penalty {
+    scorer: MaskScorer,
+    rows: Grid,
+    /// Transposed copy of rows, so columns are scored a word at a time
+    columns: Grid,
+    /// Line and finder penalty of each row
+    row_penalties: Vec<[i32; 2]>,
+    /// Line and finder penalty of each column
+    column_penalties: Vec<[i32; 2]>,
+    /// Square penalty of each row and the row below it
+    square_penalties: Vec<i32>,
+    num_dark: usize,
+}
+
+impl Penalty {
+    fn new(rows: Grid, scorer: MaskScorer) -> Penalty {
+        let columns = rows.transpose();
+        let line = |grid: &Grid, y: usize| row_penalty(grid.row_words(y),grid.width(),scorer);
+        return Penalty {
+            scorer,
+            row_penalties: (0..rows.height()).map(|y| line(&rows,y)).collect(),
+            column_penalties: (0..columns.height()).map(|x| line(&columns,x)).collect(),
+            square_penalties: (0..rows.height().saturating_sub(1)).map(|y| square_penalty_rows(rows.row_words(y),rows.row_words(y+1),rows.width())).collect(),
+            num_dark: rows.count_dark(),
+            rows,
+            columns,
+        };
+    }
+
+    /// Sets each (y, x, bit) module and scores the lines through the changed ones again
+    fn update(&mut self, modules: &[(usize,usize,u8)]) {
+        let (width, height) = (self.rows.width(), self.rows.height());
+        let mut dirty_rows = Vec::new();
+        let mut dirty_columns = Vec::new();
+        for (y, x, bit) in modules.iter().copied() {
+            let dark = bit == 1;
+            if self.rows.get(x,y) == dark {
+                continue;
+            }
+            self.rows.set(x,y,dark);
+            self.columns.set(y,x,dark);
+            if dark {self.num_dark += 1;} else {self.num_dark -= 1;}
+            dirty_rows.push(y);
+            dirty_columns.push(x);
+        }
+        dirty_rows.sort_unstable();
+        dirty_rows.dedup();
+        dirty_columns.sort_unstable();
+        dirty_columns.dedup();
+        for y in dirty_rows.iter().copied() {
+            self.row_penalties[y] = row_penalty(self.rows.row_words(y),width,self.scorer);
+        }
+        for x in dirty_columns {
+            self.column_penalties[x] = row_penalty(self.columns.row_words(x),height,self.scorer);
+        }
+        // A row changes the squares it shares with the rows above and below it
+        let mut pairs: Vec<usize> = dirty_rows.iter().flat_map(|y| y.saturating_sub(1)..std::cmp::min(y + 1,height - 1)).collect();
+        pairs.dedup();
+        for pair in pairs {
+            self.square_penalties[pair] = square_penalty_rows(self.rows.row_words(pair),self.rows.row_words(pair+1),width);
+        }
+    }
+
+    /// Returns the penalties of the grid, which has the mask at mask_index
+    fn report(&self, mask_index: u8) -> MaskPenalty {
+        let lines = |i: usize| self.row_penalties.iter().chain(self.column_penalties.iter()).map(|penalty| penalty[i]).sum::<i32>();
+        let size = self.rows.width() * self.rows.height();
+        let (line_penalty, finder_penalty) = (lines(0), lines(1));
+        let square_penalty = self.square_penalties.iter().sum();
+        let same_color_penalty = match self.scorer {
+            MaskScorer::Legacy => color_penalty(self.num_dark,size),
+            MaskScorer::Iso => iso_color_penalty(self.num_dark,size),
+        };
+        return MaskPenalty {
+            mask_index,
+            line_penalty,
+            square_penalty,
+            finder_penalty,
+            same_color_penalty,
+            total: line_penalty + square_penalty + finder_penalty + same_color_penalty,
+            dark_ratio: self.num_dark as f64 / size as f64,
+        };
+    }
+}
+
+/// Returns the score of a masked micro qr code, where higher is better
+/// Counts the dark modules on the right and bottom edges, leaving out the timing patterns
+pub fn micro_score(bitmap: &Grid) -> i32 {
+    let width = bitmap.width();
+    let right = (1..width).filter(|y| bitmap.get(width-1,*y)).count() as i32;
+    let bottom = bitmap.row(width-1).skip(1).filter(|dark| *dark).count() as i32;
+    return std::cmp::min(right,bottom) * 16 + std::cmp::max(right,bottom);
+}
+
+/// Returns the line and finder penalty of a row of width modules
+fn row_penalty(words: &[u64], width: usize, scorer: MaskScorer) -> [i32; 2] {
+    return match scorer {
+        MaskScorer::Legacy => [line_penalty_row(words,width), finder_penalty_row(words,width)],
+        MaskScorer::Iso => [run_penalty(words,width,0,4), iso_finder_penalty_row(words,width)],
+    };
+}
+
+/// Returns the sum penalty of a bitmap as scored by MaskScorer::Legacy
+pub fn sum_penalty(bitmap: &Grid) -> i32 {
+    return Penalty::new(bitmap.clone(),MaskScorer::Legacy).report(0).total;
+}
+
+/// Returns the penalty of a bitmap as scored by ISO/IEC 18004, the sum of N1 to N4
+pub fn iso_penalty(bitmap: &Grid) -> i32 {
+    return Penalty::new(bitmap.clone(),MaskScorer::Iso).report(0).total;
 }
 
 /// Calculate penalty caused by consecutive lines.
 /// Add count-2 to penalty for each consecutive line longer than or equal to 4
 /// Consecutive lines can be either in the x or y direction
-pub fn line_penalty(bitmap: &Vec<Vec<u8>>) -> i32 {
-    let mut counting;
-    let mut count = 0;
+#[cfg(test)]
+pub fn line_penalty(bitmap: &Grid) -> i32 {
+    let columns = bitmap.transpose();
+    return (0..bitmap.height()).map(|y| line_penalty_row(bitmap.row_words(y),bitmap.width())).sum::<i32>()
+        + (0..columns.height()).map(|x| line_penalty_row(columns.row_words(x),columns.width())).sum::<i32>();
+}
+
+/// Returns the line penalty of a row of width modules
+/// The count restarts at 0 rather than 1 after a change of color, so a run scores its length minus 2
+/// from a length of 5 if it starts the row, and its length minus 3 from a length of 6 otherwise
+fn line_penalty_row(words: &[u64], width: usize) -> i32 {
+    // The first run ends at the first change of color
+    let first_len = (0..words.len())
+        .find_map(|i| {
+            let changes = !same_as_previous(words,i,width) & valid_bits(i * 64,width) & if i == 0 {!1} else {u64::MAX};
+            return if changes == 0 {None} else {Some(i * 64 + changes.trailing_zeros() as usize)};
+        })
+        .unwrap_or(width);
+    let first = if first_len >= 5 {first_len as i32 - 2} else {0};
+    return first + run_penalty(words,width,first_len,5);
+}
+
+/// Returns the penalty of the runs of a row that start at or after module start
+/// A run of length modules scores length - min_len + 2 when it is at least min_len + 1 modules long
+/// Each such run has min_len modules in a row with the color of the one before them at length - min_len places
+fn run_penalty(words: &[u64], width: usize, start: usize, min_len: usize) -> i32 {
+    let same = |i: usize| {
+        return if i < words.len() {same_as_previous(words,i,width) & !valid_bits(i * 64,start)} else {0};
+    };
     let mut penalty = 0;
-    for y in 0..bitmap.len() {
-        counting = bitmap[y][0];
-        for x in 0..bitmap[0].len() {
-            if bitmap[y][x] != counting {
-                if count >= 5 {penalty+=count-2;}
-                counting = bitmap[y][x];
-                count=0;
-            }
-            else {count+=1;}
+    let mut current = same(0);
+    let mut previous_runs = 0;
+    for i in 0..words.len() {
+        let next = same(i + 1);
+        let mut runs = current;
+        for offset in 1..min_len {
+            runs &= current >> offset | next << (64 - offset);
         }
-        if count >= 5 {penalty+=count-2;}
-        count = 0;
-    }
-    count = 0;
-    for x in 0..bitmap[0].len() {
-        counting = bitmap[0][x];
-        for y in 0..bitmap.len() {
-            if bitmap[y][x] != counting {
-                if count >= 5 {penalty+=count-2;}
-                counting = bitmap[y][x];
-                count=0;
-            }
-            else {count+=1;}
-        }
-        if count >= 5 {penalty+=count-2;}
-        count = 0;
+        let starts = runs & !(runs << 1 | previous_runs >> 63);
+        penalty += runs.count_ones() as i32 + 2 * starts.count_ones() as i32;
+        previous_runs = runs;
+        current = next;
     }
     return penalty;
 }
 
+/// Returns word i of a row with bit x set when module x has the same color as module x - 1
+fn same_as_previous(words: &[u64], i: usize, width: usize) -> u64 {
+    let word = words[i];
+    let previous = if i == 0 {!word & 1} else {words[i-1] >> 63};
+    return !(word ^ (word << 1 | previous)) & valid_bits(i * 64,width);
+}
+
 /// Returns a penalty of 3 for each 2x2 block with the same color
-pub fn square_penalty(bitmap: &Vec<Vec<u8>>) -> i32 {
+#[cfg(test)]
+pub fn square_penalty(bitmap: &Grid) -> i32 {
+    return (0..bitmap.height().saturating_sub(1))
+        .map(|y| square_penalty_rows(bitmap.row_words(y),bitmap.row_words(y+1),bitmap.width()))
+        .sum();
+}
+
+/// Returns the square penalty of the 2x2 blocks in two rows of width modules
+fn square_penalty_rows(top: &[u64], bottom: &[u64], width: usize) -> i32 {
     let mut penalty = 0;
-    for x in 0..(bitmap[0].len()-1) {
-        for y in 0..(bitmap.len()-1) {
-            if bitmap[y][x] == bitmap[y+1][x] 
-                && bitmap[y][x] == bitmap[y][x+1]
-                && bitmap[y][x] == bitmap[y+1][x+1] {
-                    penalty += 3;
-                }
-        }
+    for i in 0..top.len() {
+        let same = !(top[i] ^ bottom[i]) & !(top[i] ^ window(top,i,1)) & !(bottom[i] ^ window(bottom,i,1));
+        penalty += 3 * (same & valid_bits(i * 64,width.saturating_sub(1))).count_ones() as i32;
     }
     return penalty;
 }
 
 /// Returns occurences of a pattern that looks like the finder pattern
-pub fn finder_penalty(bitmap: &Vec<Vec<u8>>) -> i32 {
-    let matches = |x: usize, y: usize, dir: usize, buffer: &[u8]| {
-        for (i, el) in buffer.iter().enumerate() {
-            if bitmap[y+dir*i][x+(1-dir)*i] != *el {return false;}
-        }
-        return true;
-    };
+#[cfg(test)]
+pub fn finder_penalty(bitmap: &Grid) -> i32 {
+    let columns = bitmap.transpose();
+    return (0..bitmap.height()).map(|y| finder_penalty_row(bitmap.row_words(y),bitmap.width())).sum::<i32>()
+        + (0..columns.height()).map(|x| finder_penalty_row(columns.row_words(x),columns.width())).sum::<i32>();
+}
+
+/// Returns the finder penalty of a row of width modules
+/// Each bit of a word is matched against both patterns at once
+fn finder_penalty_row(words: &[u64], width: usize) -> i32 {
+    const PATTERNS: [[u8; 11]; 2] = [[1,0,1,1,1,0,1,0,0,0,0], [0,0,0,0,1,0,1,1,1,0,1]];
+    if width < 11 {
+        return 0;
+    }
     let mut penalty = 0;
-    for (y, vec) in bitmap.iter().enumerate() {
-        for x in 0..vec.len() {
-            if x+10 < vec.len() && (matches(x,y,0,&[1,0,1,1,1,0,1,0,0,0,0])
-                || matches(x,y,0,&[0,0,0,0,1,0,1,1,1,0,1])) {
-                    penalty += 40;
-                }
-            if y+10 < bitmap.len() && (matches(x,y,1,&[1,0,1,1,1,0,1,0,0,0,0])
-                || matches(x,y,1,&[0,0,0,0,1,0,1,1,1,0,1])) {
-                    penalty += 40;
-                }
+    for i in 0..words.len() {
+        let mut matches = [u64::MAX; 2];
+        for offset in 0..11 {
+            let shifted = window(words,i,offset);
+            for (found, pattern) in matches.iter_mut().zip(PATTERNS.iter()) {
+                *found &= if pattern[offset] == 1 {shifted} else {!shifted};
+            }
         }
+        let found = (matches[0] | matches[1]) & valid_bits(i * 64,width - 10);
+        penalty += 40 * found.count_ones() as i32;
     }
     return penalty;
 }
 
 /// Returns penalty based on the number of colored cells
-pub fn same_color_penalty(bitmap: &Vec<Vec<u8>>) -> i32 {
-    let mut count: usize = 0;
-    for vec in bitmap.iter() {
-        for x in vec {
-            count += *x as usize;
+#[cfg(test)]
+pub fn same_color_penalty(bitmap: &Grid) -> i32 {
+    return color_penalty(bitmap.count_dark(),bitmap.height()*bitmap.width());
+}
+
+/// Returns the N3 penalty of a row of width modules as scored by ISO/IEC 18004
+/// Looks for dark and light runs in the ratio 1:1:3:1:1 of any module size, with a light run 4 times as long on
+/// either side. The area around the symbol is light, and a pattern with light on both sides counts twice.
+fn iso_finder_penalty_row(words: &[u64], width: usize) -> i32 {
+    // Lengths of the last 7 runs, newest first, where a light run at the edge is extended by the width
+    let mut history = [0usize; 7];
+    let push = |history: &mut [usize; 7], len: usize| {
+        let len = if history[0] == 0 {len + width} else {len};
+        history.copy_within(0..6,1);
+        history[0] = len;
+    };
+    let count_patterns = |history: &[usize; 7]| {
+        let n = history[1];
+        let core = n > 0 && history[2] == n && history[3] == n * 3 && history[4] == n && history[5] == n;
+        return (core && history[0] >= n * 4 && history[6] >= n) as i32 + (core && history[6] >= n * 4 && history[0] >= n) as i32;
+    };
+    let mut penalty = 0;
+    let mut start = 0;
+    // A row is treated as starting with an empty light run
+    let mut dark = false;
+    for i in 0..words.len() {
+        let mut changes = !same_as_previous(words,i,width) & valid_bits(i * 64,width);
+        if i == 0 && words[0] & 1 == 0 {
+            changes &= !1;
         }
+        while changes != 0 {
+            let x = i * 64 + changes.trailing_zeros() as usize;
+            push(&mut history,x - start);
+            // The newest run of a pattern is light, so patterns are counted when a light run ends
+            if !dark {
+                penalty += count_patterns(&history);
+            }
+            dark = !dark;
+            start = x;
+            changes &= changes - 1;
+        }
+    }
+    let mut len = width - start;
+    if dark {
+        push(&mut history,len);
+        len = 0;
     }
-    let fraction = count as f32 / (bitmap.len()*bitmap[0].len()) as f32 * 100.0;
+    push(&mut history,len + width);
+    return 40 * (penalty + count_patterns(&history));
+}
+
+/// Returns the N4 penalty of num_dark dark modules out of size as scored by ISO/IEC 18004
+/// Scores 10 for each full 5% step of the dark modules away from half, rounding as qrcodegen does
+/// Exactly half only happens with an even number of modules, which no qr code has
+fn iso_color_penalty(num_dark: usize, size: usize) -> i32 {
+    let (num_dark, size) = (num_dark as i64, size as i64);
+    let steps = ((num_dark * 20 - size * 10).abs() + size - 1) / size - 1;
+    return 10 * std::cmp::max(steps,0) as i32;
+}
+
+/// Returns the same color penalty of num_dark dark modules out of size
+fn color_penalty(num_dark: usize, size: usize) -> i32 {
+    let fraction = num_dark as f32 / size as f32 * 100.0;
     return (((fraction/5.0).trunc()-10.0).abs()*2.0) as i32;
 }
+
+/// Returns the 64 modules of a row starting offset modules after the start of word i
+fn window(words: &[u64], i: usize, offset: usize) -> u64 {
+    if offset == 0 {
+        return words[i];
+    }
+    return words[i] >> offset | words.get(i+1).map_or(0, |next| next << (64 - offset));
+}
+
+/// Returns the bits of a word starting at module start that are before module end
+fn valid_bits(start: usize, end: usize) -> u64 {
+    if end <= start {
+        return 0;
+    }
+    if end - start >= 64 {
+        return u64::MAX;
+    }
+    return (1 << (end - start)) - 1;
+}
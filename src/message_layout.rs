@@ -1,63 +1,68 @@
-use crate::qr::QR;
-use crate::patterns;
+use crate::qr::{SymbolKind, QR};
+use crate::error::QrError;
 
 impl QR {
     /// Take series of bits and write the message on bitmap
-    pub fn write_message(&mut self, message: Vec<u8>) {
-        let width = self.bitmap.len();
-        let mut pos_x: usize = width-1;
-        let mut pos_y: usize = width-1;
-        let mut mode = 1;
+    /// Returns DataTooLong if the message does not fit in the available modules
+    pub fn write_message(&mut self, message: Vec<u8>) -> Result<(),QrError> {
         // Iterates through each bit
-        for bit in message.iter().flat_map(|x| (0..8).map(|el| *x >> ((7-el)) & 1)) {
-            loop {
-                if mode == 1 {
-                    mode = 2;
-                    break;
-                }
-                if mode == 2 {
-                    pos_x -= 1;
-                    mode = 3;
-                }
-                else if mode == 3 {
-                    // Go left
-                    if pos_y == 0 {
-                        mode = 5;
-                        //Twice if in 8th column
-                        if pos_x == 7 { pos_x-=2; }
-                        else { pos_x-=1; }
-                    }
-                    else {
-                        pos_y -= 1;
-                        pos_x += 1;
-                        mode = 2;
-                    }
-                }
-                else if mode == 5 {
-                    pos_x -= 1;
-                    mode = 6;
-                }
-                else if mode == 6 {
-                    if pos_y == width-1 {
-                        // Should not reach this point, means there are not enough modules
-                        if pos_x == 0 {
-                            panic!("Reached end of QR code");
-                        }
-                        else {
-                            pos_x -= 1;
-                            mode = 2;
-                        }
-                    }
-                    else {
-                        pos_y += 1;
-                        pos_x += 1;
-                        mode = 5;
+        let bits: Vec<u8> = message.iter().flat_map(|x| (0..8).map(move |el| (*x >> (7-el)) & 1)).collect();
+        return self.write_bits(&bits);
+    }
+
+    /// Write a list of bits on bitmap in the same order as write_message
+    /// Returns DataTooLong if there are more bits than available modules
+    pub(crate) fn write_bits(&mut self, bits: &[u8]) -> Result<(),QrError> {
+        let positions = self.data_positions();
+        if bits.len() > positions.len() {
+            return Err(QrError::DataTooLong {
+                needed_bits: bits.len(),
+                capacity: positions.len(),
+            });
+        }
+        for (bit, (pos_y, pos_x)) in bits.iter().zip(positions) {
+            self.bitmap.set(pos_x,pos_y,*bit == 1);
+        }
+        return Ok(());
+    }
+
+    /// Read the codewords written by write_message back from bitmap
+    /// Trailing bits that do not fill a whole codeword are ignored
+    pub(crate) fn read_message(&self) -> Vec<u8> {
+        let positions = self.data_positions();
+        return positions.chunks_exact(8)
+            .map(|byte| byte.iter().fold(0u8, |acc, (y, x)| acc << 1 | self.bitmap.get(*x,*y) as u8))
+            .collect();
+    }
+
+    /// Returns the (y, x) position of every module not covered by a pattern, in the order bits are written
+    /// Bits go up and down in two module wide columns starting at the bottom right corner
+    /// The vertical timing pattern column is skipped, micro and rectangular codes have it on the left edge instead
+    /// Rectangular codes also have a timing pattern on the right edge, so their columns start one module in
+    pub(crate) fn data_positions(&self) -> Vec<(usize,usize)> {
+        let height = self.bitmap.height();
+        let width = self.bitmap.width();
+        let mut out = Vec::with_capacity(width * height);
+        let mut upward = true;
+        let mut right = match self.kind {
+            SymbolKind::Rmqr => width as isize - 2,
+            _ => width as isize - 1,
+        };
+        while right >= 1 {
+            if right == 6 && self.kind == SymbolKind::Qr {
+                right = 5;
+            }
+            for i in 0..height {
+                let y = if upward {height - 1 - i} else {i};
+                for x in [right as usize, right as usize - 1] {
+                    if !self.pattern_mask.get(x,y) {
+                        out.push((y, x));
                     }
                 }
-                if self.pattern_mask[pos_y][pos_x] == patterns::PatternMaskType::None { break; }
             }
-            self.bitmap[pos_y][pos_x] = bit;
+            upward = !upward;
+            right -= 2;
         }
-
+        return out;
     }
-}
\ No newline at end of file
+}
@@ -1,7 +1,9 @@
 use crate::bits;
 use crate::qr::Encoding;
+use crate::segment::Segment;
 use crate::qr::QR;
 use crate::error_correction::ECLevel;
+use crate::error::QrError;
 
 /// Stores tuple of (number of EC codewords per block, number of blocks) 
 /// Accessed by BLOCKS_TABLE[ version ][ ECLevel ]
@@ -45,7 +47,7 @@ pub(crate) const BLOCKS_TABLE: [[(usize,usize,usize);4];40] = [
   [(2566, 30, 21), (1992, 28, 43), (1426, 30, 59), (1096, 30, 70)],
   [(2702, 30, 22), (2102, 28, 45), (1502, 30, 62), (1142, 30, 74)],
   [(2812, 30, 24), (2216, 28, 47), (1582, 30, 65), (1222, 30, 77)],
-  [(2956, 30, 25), (2334, 28, 49), (1666, 30, 68), (1276, 30, 8)],
+  [(2956, 30, 25), (2334, 28, 49), (1666, 30, 68), (1276, 30, 81)],
 ];
 /// Get element in the QR code block layout table
 /// Returns (number of data codewords, EC codewords per block, number blocks)
@@ -54,67 +56,162 @@ pub fn blocks_table_get(version: u8, ec_level: ECLevel) -> (usize,usize,usize) {
     return BLOCKS_TABLE[version as usize-1][match ec_level {L => 0, M => 1, Q => 2, H => 3}];
 }
 
+/// Returns the maximum number of characters a qr code of version and ec_level can hold in a single segment of enc
+/// ECI segments are counted in UTF-8 bytes
+/// Panics if version is not between 1 and 40
+pub fn capacity(version: u8, ec_level: ECLevel, enc: Encoding) -> usize {
+    let header_bits = crate::segment::header_bits(enc,version).expect("Version out of range");
+    let available = blocks_table_get(version,ec_level).0 * 8 - header_bits;
+    let num_chars = match enc {
+        Encoding::Numeric => available / 10 * 3 + match available % 10 {7..=9 => 2, 4..=6 => 1, _ => 0},
+        Encoding::Alphanumeric => available / 11 * 2 + (available % 11 >= 6) as usize,
+        Encoding::Byte | Encoding::ECI => available / 8,
+        Encoding::Kanji => available / 13,
+    };
+    // The length field also limits the number of characters
+    let max_chars = (1 << num_length_bits(version,enc).expect("Version out of range")) - 1;
+    return std::cmp::min(num_chars,max_chars);
+}
+
+/// Encodings in the order of the capacity table
+pub const CAPACITY_TABLE_MODES: [Encoding; 4] = [Encoding::Numeric, Encoding::Alphanumeric, Encoding::Byte, Encoding::Kanji];
+
+lazy_static::lazy_static! {
+    /// Capacity of every version, error correction level and mode
+    /// Accessed by CAPACITY_TABLE[ version - 1 ][ ECLevel ][ mode in CAPACITY_TABLE_MODES ]
+    static ref CAPACITY_TABLE: [[[usize; 4]; 4]; 40] = {
+        let mut table = [[[0; 4]; 4]; 40];
+        for (version, row) in (1..=40).zip(table.iter_mut()) {
+            for (ec_level, capacities) in [ECLevel::L, ECLevel::M, ECLevel::Q, ECLevel::H].into_iter().zip(row.iter_mut()) {
+                for (enc, el) in CAPACITY_TABLE_MODES.into_iter().zip(capacities.iter_mut()) {
+                    *el = capacity(version,ec_level,enc);
+                }
+            }
+        }
+        table
+    };
+}
+
+/// Returns the maximum number of characters of every version, error correction level and mode
+/// Accessed by capacity_table()[ version - 1 ][ ECLevel ][ mode in CAPACITY_TABLE_MODES ]
+pub fn capacity_table() -> &'static [[[usize; 4]; 4]; 40] {
+    return &CAPACITY_TABLE;
+}
+
+/// Returns the smallest version of qr code that can store segments at ec_level, or None if they do not fit in version 40
+pub fn min_version_for(segments: &[Segment], ec_level: ECLevel) -> Option<u8> {
+    return QR::get_min_version_segments(segments,ec_level).ok().map(|(version, _)| version);
+}
+
+/// Number of bits taken by the ECI mode indicator and designator before the byte segment
+pub(crate) const ECI_HEADER_BITS: usize = 4 + 8;
+
 /// Return the number of bits needed to encode message length
-fn num_length_bits(version: u8, enc: Encoding) -> u32 {
-    return match version {
+pub(crate) fn num_length_bits(version: u8, enc: Encoding) -> Result<u32,QrError> {
+    return Ok(match version {
         1..=9 => [10u32,9,8,8],
         10..=26 => [12u32,11,16,10],
         27..=40 => [14u32,13,16,12],
-        0 | 41..=u8::MAX => panic!("Version out of bounds")
+        0 | 41..=u8::MAX => return Err(QrError::VersionOutOfRange(version)),
     }[match enc {
         crate::qr::Encoding::Numeric => 0,
         crate::qr::Encoding::Alphanumeric => 1,
         crate::qr::Encoding::Byte => 2,
-        //crate::qr::Encoding::Kanji => 3,
+        crate::qr::Encoding::Kanji => 3,
         crate::qr::Encoding::ECI => 2,
-    }]
+    }]);
 }
 
-/// Given list of encoded bits and metadata, returns bit list with metadata encoded
-pub(crate) fn get_codewords(bits: &Vec<u8>, num_chars: usize, enc: Encoding, version: u8, num_codewords: usize) -> Vec<u8> {
-    let length_len = num_length_bits(version,enc);
-    let mut message_metadata: Vec<u8> = Vec::with_capacity(4+length_len as usize);
-    bits::push_to_bit_list(&mut message_metadata,enc as u32,4);
-    bits::push_to_bit_list(&mut message_metadata,num_chars as u32,length_len);
+/// Returns the number of characters written in the length field of a message
+/// ECI messages are stored as a byte segment, so their length is counted in bytes
+pub(crate) fn char_count(str: &str, enc: Encoding) -> usize {
+    return match enc {
+        Encoding::ECI => str.len(),
+        _ => str.chars().count(),
+    };
+}
+
+/// Given list of segments and metadata, returns the message as codewords
+/// Each segment is written with its mode indicator and length, followed by a terminator and padding
+/// header is written before the first segment, such as a structured append header
+/// Returns an error with the position of the first character that its segment cannot encode
+pub(crate) fn get_codewords(segments: &[Segment], version: u8, num_codewords: usize, header: &[u8]) -> Result<Vec<u8>,QrError> {
     let mut message: Vec<u8> = Vec::with_capacity(num_codewords * 8);
-    for x in message_metadata.iter() {message.push(*x);}
-    for x in bits.iter() {message.push(*x);}
-    let leftover = ((message.len() + 8 - 1)/8)*8 - message.len();
-    for _ in 0..leftover {message.push(0);}
+    message.extend_from_slice(header);
+    // Number of characters in the segments before the current one
+    let mut position = 0;
+    for segment in segments.iter() {
+        let enc = segment.enc;
+        let bits = segment.encode().map_err(|ch| QrError::InvalidCharacter {
+            ch,
+            position: position + segment.data.chars().position(|x| x == ch).unwrap_or(0),
+            mode: enc,
+        })?;
+        position += segment.data.chars().count();
+        if let Encoding::ECI = enc {
+            // ECI header followed by a byte mode segment holding the UTF-8 bytes
+            bits::push_to_bit_list(&mut message,enc as u32,4);
+            bits::push_to_bit_list(&mut message,bits::ECI_UTF8,8);
+            bits::push_to_bit_list(&mut message,Encoding::Byte as u32,4);
+        }
+        else {
+            bits::push_to_bit_list(&mut message,enc as u32,4);
+        }
+        bits::push_to_bit_list(&mut message,segment.num_chars() as u32,num_length_bits(version,enc)?);
+        message.extend(bits);
+    }
+    // Terminator of up to four zeros, then zeros up to the end of the codeword
+    let terminator_len = std::cmp::min(4, (num_codewords * 8).saturating_sub(message.len()));
+    message.resize(message.len() + terminator_len, 0);
+    message.resize(message.len().div_ceil(8) * 8, 0);
     
     // Convert bit list to 8-bit codewords
     let mut message_as_codewords: Vec<u8> = Vec::with_capacity(num_codewords);
     for x in message.chunks(8) {
         message_as_codewords.push(bits::collect_bits(x).try_into().unwrap());
     }
+    // Fill remaining space with alternating pad codewords
+    let data_len = message_as_codewords.len();
     while message_as_codewords.len() < num_codewords {
-        message_as_codewords.push(if message_as_codewords.len() % 2 == 0 {236} else {17});
+        message_as_codewords.push(if (message_as_codewords.len() - data_len).is_multiple_of(2) {236} else {17});
     }
-    return message_as_codewords;
+    return Ok(message_as_codewords);
 }
 
 impl QR {
     /// Returns the minimum QR version needed to store a message
-    pub(crate) fn get_min_version(str: &str, enc: Encoding, ec_level: ECLevel) -> (u8,usize) {
-        let num_chars = str.chars().count();
-        let num_bits = match enc {
-            Encoding::Numeric => num_chars / 3 * 10 
-                + (num_chars % 3 == 2) as usize * 7
-                + (num_chars % 3 == 1) as usize * 4,
-            Encoding::Byte => num_chars * 8,
-            Encoding::Alphanumeric => num_chars / 2 * 11
-                + (num_chars % 2 == 1) as usize * 6,
-            //Encoding::Kanji => num_chars * 13,
-            Encoding::ECI => str.len(),
-        };
+    pub fn get_min_version(str: &str, enc: Encoding, ec_level: ECLevel) -> Result<(u8,usize),QrError> {
+        return QR::get_min_version_segments(&[Segment::new(str,enc)],ec_level);
+    }
+
+    /// Returns the minimum QR version needed to store a list of segments
+    /// Returns DataTooLong if the segments do not fit in version 40
+    pub fn get_min_version_segments(segments: &[Segment], ec_level: ECLevel) -> Result<(u8,usize),QrError> {
+        return QR::get_min_version_in_range(segments,ec_level,1,40);
+    }
+
+    /// Returns the minimum QR version between min_version and max_version that can store a list of segments
+    /// Returns DataTooLong if the segments do not fit in max_version
+    pub fn get_min_version_in_range(segments: &[Segment], ec_level: ECLevel, min_version: u8, max_version: u8) -> Result<(u8,usize),QrError> {
+        return QR::get_min_version_with_header(segments,ec_level,min_version,max_version,0);
+    }
 
-        // Find first version that has enough codewords
-        for i in 1..=40 {
-            if (num_bits + 4 + num_length_bits(i,enc) as usize) / 8 < blocks_table_get(i,ec_level).0 {
-                return (i as u8,blocks_table_get(i,ec_level).0);
+    /// Same as get_min_version_in_range with header_bits more bits written before the segments
+    pub(crate) fn get_min_version_with_header(segments: &[Segment], ec_level: ECLevel, min_version: u8, max_version: u8, header_bits: usize) -> Result<(u8,usize),QrError> {
+        if !(1..=40).contains(&min_version) {
+            return Err(QrError::VersionOutOfRange(min_version));
+        }
+        if !(min_version..=40).contains(&max_version) {
+            return Err(QrError::VersionOutOfRange(max_version));
+        }
+        let mut num_bits = 0;
+        // Find first version that has enough codewords, the terminator can be left out when the message fills them
+        for i in min_version..=max_version {
+            num_bits = header_bits + segments.iter().map(|seg| seg.num_bits(i)).sum::<Result<usize,QrError>>()?;
+            if num_bits <= blocks_table_get(i,ec_level).0 * 8 {
+                return Ok((i,blocks_table_get(i,ec_level).0));
             }
         }
-        panic!("Cannot fit {} characters with error correction {:?}",num_chars,ec_level);
-        
+        return Err(QrError::DataTooLong { needed_bits: num_bits, capacity: blocks_table_get(max_version,ec_level).0 * 8 });
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,169 @@
+use crate::bits;
+use crate::error::QrError;
+use crate::error_correction;
+use crate::error_correction::ECLevel;
+use crate::grid::Grid;
+use crate::qr::{Encoding, SymbolKind, QR};
+use crate::segment::Segment;
+
+/// Stores tuple of (number of data bits, number of EC codewords) of micro qr codes, which have a single block
+/// Accessed by MICRO_BLOCKS_TABLE[ version - 1 ][ ECLevel ], None when the level cannot be used in that version
+pub(crate) const MICRO_BLOCKS_TABLE: [[Option<(usize,usize)>;3];4] = [
+    [Some((20, 2)),  None,            None],
+    [Some((40, 5)),  Some((32, 6)),   None],
+    [Some((84, 6)),  Some((68, 8)),   None],
+    [Some((128, 8)), Some((112, 10)), Some((80, 14))],
+];
+
+/// Get element in the micro qr code block layout table
+/// Returns (number of data bits, number of EC codewords), or None if ec_level cannot be used in version
+/// M1, M3-L and M3-M end with a data codeword of only 4 bits
+pub fn micro_blocks_table_get(version: u8, ec_level: ECLevel) -> Option<(usize,usize)> {
+    use ECLevel::*;
+    let idx = match ec_level {L => 0, M => 1, Q => 2, H => return None};
+    return MICRO_BLOCKS_TABLE.get((version as usize).wrapping_sub(1))?[idx];
+}
+
+/// Returns (mode indicator, number of length bits) of a segment in a micro qr code
+/// The mode indicator is version - 1 bits long
+/// Returns UnsupportedMode if the mode cannot be written in version
+fn micro_header(version: u8, enc: Encoding) -> Result<(u32,usize),QrError> {
+    let v = version as usize;
+    let (indicator, min_version, length_bits) = match enc {
+        Encoding::Numeric => (0, 1, v + 2),
+        Encoding::Alphanumeric => (1, 2, v + 1),
+        Encoding::Byte => (2, 3, v + 1),
+        Encoding::Kanji => (3, 3, v),
+        Encoding::ECI => return Err(QrError::UnsupportedMode(enc)),
+    };
+    if version < min_version {
+        return Err(QrError::UnsupportedMode(enc));
+    }
+    return Ok((indicator,length_bits));
+}
+
+/// Returns the number of bits segments take in a micro qr code, including the headers
+fn micro_num_bits(segments: &[Segment], version: u8) -> Result<usize,QrError> {
+    return segments.iter()
+        .map(|seg| Ok(version as usize - 1 + micro_header(version,seg.enc)?.1 + seg.data_bits()))
+        .sum();
+}
+
+/// Given list of segments, returns the message of a micro qr code as data codewords
+/// When data_bits is not a multiple of 8, the last codeword only uses its 4 high bits
+fn get_micro_codewords(segments: &[Segment], version: u8, data_bits: usize) -> Result<Vec<u8>,QrError> {
+    let mut message: Vec<u8> = Vec::with_capacity(data_bits);
+    // Number of characters in the segments before the current one
+    let mut position = 0;
+    for segment in segments.iter() {
+        let (indicator, length_bits) = micro_header(version,segment.enc)?;
+        let bits = segment.encode().map_err(|ch| QrError::InvalidCharacter {
+            ch,
+            position: position + segment.data.chars().position(|x| x == ch).unwrap_or(0),
+            mode: segment.enc,
+        })?;
+        position += segment.data.chars().count();
+        bits::push_to_bit_list(&mut message,indicator,version as u32 - 1);
+        bits::push_to_bit_list(&mut message,segment.num_chars() as u32,length_bits as u32);
+        message.extend(bits);
+    }
+    if message.len() > data_bits {
+        return Err(QrError::DataTooLong { needed_bits: message.len(), capacity: data_bits });
+    }
+    // Terminator of 2 * version + 1 zeros, then zeros up to the end of the codeword
+    let terminator_len = std::cmp::min(2 * version as usize + 1, data_bits - message.len());
+    message.resize(message.len() + terminator_len, 0);
+    message.resize(std::cmp::min(message.len().div_ceil(8) * 8, data_bits), 0);
+    // Fill remaining full codewords with alternating pad codewords, a half codeword is left as zeros
+    let mut pad = [236u32, 17].into_iter().cycle();
+    while message.len() + 8 <= data_bits {
+        bits::push_to_bit_list(&mut message,pad.next().unwrap(),8);
+    }
+    message.resize(data_bits, 0);
+
+    return Ok(message.chunks(8)
+        .map(|x| (bits::collect_bits(x) << (8 - x.len())) as u8)
+        .collect());
+}
+
+impl QR {
+    /// Create a micro qr code from str using encoding
+    pub fn new_micro(str: &str, enc: Encoding, ec_level: ECLevel) -> Result<QR,QrError> {
+        return QR::micro_from_segments(&[Segment::new(str,enc)],ec_level);
+    }
+
+    /// Create a micro qr code from a list of segments written one after another
+    pub fn micro_from_segments(segments: &[Segment], ec_level: ECLevel) -> Result<QR,QrError> {
+        let version = QR::get_min_micro_version(segments,ec_level)?;
+        return QR::create_micro(segments,version,ec_level,None);
+    }
+
+    /// Returns the minimum micro qr version needed to store a list of segments
+    pub fn get_min_micro_version(segments: &[Segment], ec_level: ECLevel) -> Result<u8,QrError> {
+        return QR::get_min_micro_version_in_range(segments,ec_level,1,4);
+    }
+
+    /// Returns the minimum micro qr version between min_version and max_version that can store a list of segments
+    /// Returns UnsupportedEcLevel if no version in the range has ec_level, UnsupportedMode if a segment
+    /// cannot be written in any of them, and DataTooLong otherwise
+    pub fn get_min_micro_version_in_range(segments: &[Segment], ec_level: ECLevel, min_version: u8, max_version: u8) -> Result<u8,QrError> {
+        if !(1..=4).contains(&min_version) {
+            return Err(QrError::VersionOutOfRange(min_version));
+        }
+        if !(min_version..=4).contains(&max_version) {
+            return Err(QrError::VersionOutOfRange(max_version));
+        }
+        let mut error = QrError::UnsupportedEcLevel(ec_level);
+        for version in min_version..=max_version {
+            let Some((data_bits, _)) = micro_blocks_table_get(version,ec_level) else {continue};
+            match micro_num_bits(segments,version) {
+                Ok(num_bits) if num_bits <= data_bits => return Ok(version),
+                Ok(num_bits) => error = QrError::DataTooLong { needed_bits: num_bits, capacity: data_bits },
+                Err(err) => error = err,
+            }
+        }
+        return Err(error);
+    }
+
+    /// Create a micro qr code of a fixed version from a list of segments
+    /// Uses the mask with the highest score unless mask_index (0-3) is given
+    pub(crate) fn create_micro(segments: &[Segment], version: u8, ec_level: ECLevel, mask_index: Option<u8>) -> Result<QR,QrError> {
+        if !(1..=4).contains(&version) {
+            return Err(QrError::VersionOutOfRange(version));
+        }
+        let (data_bits, ec_count) = micro_blocks_table_get(version,ec_level).ok_or(QrError::UnsupportedEcLevel(ec_level))?;
+        let data = get_micro_codewords(segments,version,data_bits)?;
+        let ec = error_correction::ec_group(&data,ec_count);
+        // Every codeword takes 8 modules except a trailing half data codeword
+        let mut message: Vec<u8> = Vec::with_capacity(data_bits + ec_count * 8);
+        for (i, codeword) in data.iter().enumerate() {
+            let len = std::cmp::min(8, data_bits - i * 8);
+            bits::push_to_bit_list(&mut message,(*codeword >> (8 - len)) as u32,len as u32);
+        }
+        for codeword in ec {
+            bits::push_to_bit_list(&mut message,codeword as u32,8);
+        }
+
+        let version_size = QR::get_micro_version_size(version);
+        let mut out = QR {
+            kind: SymbolKind::Micro,
+            bitmap: Grid::new(version_size,version_size),
+            pattern_mask: Grid::new(version_size,version_size),
+            version,
+            ec_level,
+            mask_index: 0,
+        };
+        out.apply_patterns();
+        out.write_bits(&message)?;
+        match mask_index {
+            Some(mask_index) => out.apply_mask(mask_index),
+            None => out.apply_masking(),
+        }
+        return Ok(out);
+    }
+
+    /// Get width of a micro qr code of size version
+    pub fn get_micro_version_size(version: u8) -> usize {
+        9 + (version as usize) * 2
+    }
+}
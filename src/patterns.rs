@@ -1,6 +1,7 @@
-use crate::qr::QR;
+use crate::qr::{SymbolKind, QR};
 use crate::bits;
 use crate::error_correction;
+use crate::error_correction::ECLevel;
 
 /*
 
@@ -32,23 +33,75 @@ Version 2 QR code patterns
 [1,1,1,1,1,1,1,1,4,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0]
 
  */
-#[derive(Copy,Clone,PartialEq,Eq)]
-/// Extra information on what pattern type is being covered
-/// Data type for QR.pattern_mask
-pub enum PatternMaskType {
-    None = 0,
-    Finder = 1,
-    Timing = 2,
-    Alignment = 3,
-    Format = 4,
-    Version = 5,
-    DarkModule = 6
+/// Centers of the alignment patterns along each axis, from ISO/IEC 18004 Annex E
+/// Accessed by ALIGNMENT_POSITIONS[ version - 1 ]
+const ALIGNMENT_POSITIONS: [&[usize];40] = [
+    &[],
+    &[6, 18],
+    &[6, 22],
+    &[6, 26],
+    &[6, 30],
+    &[6, 34],
+    &[6, 22, 38],
+    &[6, 24, 42],
+    &[6, 26, 46],
+    &[6, 28, 50],
+    &[6, 30, 54],
+    &[6, 32, 58],
+    &[6, 34, 62],
+    &[6, 26, 46, 66],
+    &[6, 26, 48, 70],
+    &[6, 26, 50, 74],
+    &[6, 30, 54, 78],
+    &[6, 30, 56, 82],
+    &[6, 30, 58, 86],
+    &[6, 34, 62, 90],
+    &[6, 28, 50, 72, 94],
+    &[6, 26, 50, 74, 98],
+    &[6, 30, 54, 78, 102],
+    &[6, 28, 54, 80, 106],
+    &[6, 32, 58, 84, 110],
+    &[6, 30, 58, 86, 114],
+    &[6, 34, 62, 90, 118],
+    &[6, 26, 50, 74, 98, 122],
+    &[6, 30, 54, 78, 102, 126],
+    &[6, 26, 52, 78, 104, 130],
+    &[6, 30, 56, 82, 108, 134],
+    &[6, 34, 60, 86, 112, 138],
+    &[6, 30, 58, 86, 114, 142],
+    &[6, 34, 62, 90, 118, 146],
+    &[6, 30, 54, 78, 102, 126, 150],
+    &[6, 24, 50, 76, 102, 128, 154],
+    &[6, 28, 54, 80, 106, 132, 158],
+    &[6, 32, 58, 84, 110, 136, 162],
+    &[6, 26, 54, 82, 110, 138, 166],
+    &[6, 30, 58, 86, 114, 142, 170],
+];
+
+/// Returns the centers of the alignment patterns along each axis of a qr code of version
+/// Patterns are placed at every pair of centers apart from the three that overlap finder patterns
+pub(crate) fn alignment_positions(version: u8) -> &'static [usize] {
+    return ALIGNMENT_POSITIONS[version as usize - 1];
 }
 
 impl QR {
     /// Applies all fixed patterns
+    /// Micro qr codes only have one finder pattern, timing patterns and format information
     pub fn apply_patterns(&mut self) {
-        let length = self.bitmap.len();
+        if self.kind == SymbolKind::Micro {
+            self.create_finder(0,0);
+            self.micro_timing_patterns();
+            self.format_pattern();
+            return;
+        }
+        if self.kind == SymbolKind::Rmqr {
+            self.rmqr_finder_patterns();
+            self.rmqr_alignment_patterns();
+            self.rmqr_timing_patterns();
+            self.format_pattern();
+            return;
+        }
+        let length = self.bitmap.height();
         self.create_finder(0,0);
         self.create_finder(0,length-8);
         self.create_finder(length-8,0);
@@ -61,65 +114,133 @@ impl QR {
     
     /// Creates a finder pattern given the top left position
     fn create_finder(&mut self, left: usize, top: usize) {
-        use PatternMaskType::Finder;
         let x_center: usize = if left==0 {3} else {4};
         let y_center: usize = if top==0 {3} else {4};
         for x in 0i32..8 {
             for y in 0i32..8 {
-                self.bitmap[y as usize+top][x as usize+left] = (std::cmp::max(
-                        (x-x_center as i32).abs(),
-                        (y-y_center as i32).abs()
-                    ) % 2) .try_into().unwrap();
-                self.pattern_mask[y as usize+top][x as usize+left] = Finder;
+                let ring = std::cmp::max((x-x_center as i32).abs(),(y-y_center as i32).abs());
+                self.set_pattern(y as usize+top,x as usize+left,(ring % 2) as u8);
             }
         }
-        self.bitmap[y_center+top][x_center+left] = 1;
+        self.bitmap.set(x_center+left,y_center+top,true);
     }
 
     /// Creates alternating timing pattern between the finder patterns
     fn timing_patterns(&mut self) {
-        use PatternMaskType::Timing;
-        let len = self.bitmap.len();
+        let len = self.bitmap.height();
         for x in 8..(len-8) {
             // Horizontal timing pattern
-            self.bitmap[6][x] = (1 - x % 2).try_into().unwrap();
-            self.pattern_mask[6][x] = Timing;
+            self.set_pattern(6,x,(1 - x % 2) as u8);
 
             // Vertical timing pattern
-            self.bitmap[x][6] = (1 - x % 2).try_into().unwrap();
-            self.pattern_mask[x][6] = Timing;
+            self.set_pattern(x,6,(1 - x % 2) as u8);
+        }
+    }
+
+    /// Creates alternating timing patterns along the top and left edges of a micro qr code
+    fn micro_timing_patterns(&mut self) {
+        for x in 8..self.bitmap.height() {
+            self.set_pattern(0,x,(1 - x % 2) as u8);
+            self.set_pattern(x,0,(1 - x % 2) as u8);
+        }
+    }
+
+    /// Sets a module that belongs to a fixed pattern and marks it in the pattern mask
+    fn set_pattern(&mut self, y: usize, x: usize, value: u8) {
+        self.bitmap.set(x,y,value == 1);
+        self.pattern_mask.set(x,y,true);
+    }
+
+    /// Places the finder pattern, sub-finder pattern and corner patterns of a rectangular micro qr code
+    /// The finder pattern is on the left with a separator on its right, and below it when the code is taller than 7 modules
+    fn rmqr_finder_patterns(&mut self) {
+        let height = self.bitmap.height();
+        let width = self.bitmap.width();
+        for y in 0..std::cmp::min(8,height) {
+            for x in 0..8 {
+                let ring = std::cmp::max((x as i32 - 3).abs(),(y as i32 - 3).abs());
+                self.set_pattern(y,x,(ring == 0 || ring % 2 == 1) as u8);
+            }
+        }
+        // Sub-finder pattern in the bottom right corner
+        for y in 0..5 {
+            for x in 0..5 {
+                let ring = std::cmp::max((x as i32 - 2).abs(),(y as i32 - 2).abs());
+                self.set_pattern(height-5+y,width-5+x,(ring % 2 == 0) as u8);
+            }
+        }
+        // Corner patterns in the bottom left and top right corners
+        for x in 0..3 {
+            self.set_pattern(height-1,x,1);
+        }
+        if height >= 11 {
+            self.set_pattern(height-2,0,1);
+            self.set_pattern(height-2,1,0);
+        }
+        self.set_pattern(0,width-2,1);
+        self.set_pattern(0,width-1,1);
+        self.set_pattern(1,width-2,0);
+        self.set_pattern(1,width-1,1);
+    }
+
+    /// Places the 3x3 alignment patterns on the top and bottom edges of a rectangular micro qr code
+    fn rmqr_alignment_patterns(&mut self) {
+        let height = self.bitmap.height();
+        for center_x in crate::rmqr::rmqr_alignment_columns(self.bitmap.width()) {
+            for y in 0..3 {
+                for x in center_x-1..=center_x+1 {
+                    let value = (y != 1 || x != *center_x) as u8;
+                    self.set_pattern(y,x,value);
+                    self.set_pattern(height-1-y,x,value);
+                }
+            }
+        }
+    }
+
+    /// Creates alternating timing patterns on every edge of a rectangular micro qr code
+    /// Vertical timing patterns also join the top and bottom alignment patterns
+    fn rmqr_timing_patterns(&mut self) {
+        let height = self.bitmap.height();
+        let width = self.bitmap.width();
+        for x in 0..width {
+            for y in [0, height-1] {
+                if !self.pattern_mask.get(x,y) {
+                    self.set_pattern(y,x,(x % 2 == 0) as u8);
+                }
+            }
+        }
+        let columns = crate::rmqr::rmqr_alignment_columns(width);
+        for x in columns.iter().copied().chain([0, width-1]) {
+            for y in 0..height {
+                if !self.pattern_mask.get(x,y) {
+                    self.set_pattern(y,x,(y % 2 == 0) as u8);
+                }
+            }
         }
     }
 
     /// Places all alignment patterns
     /// These patterns are in the main area of the QR code
     fn alignment_patterns(&mut self) {
-        if self.version == 1 {return;}
-        let n_gaps: i32 = self.version as i32 / 7 + 1;
-        let width: i32 = self.bitmap.len() as i32;
-        // Width of screen divided by number of gaps rounded up to nearest even number
-        let spacing = ((width - 13) as f32 / n_gaps as f32 / 2.0).ceil() as i32 * 2;
-        //Alignment patterns on the top and left edges
-        for i in 1..n_gaps {
-            self.alignment_pattern(width as i32-7-i*spacing,6);
-            self.alignment_pattern(6,width as i32-7-i*spacing);
-        }
-        //Alignment patterns on main grid part
-        for i in 0..n_gaps {
-            for j in 0..n_gaps {
-                self.alignment_pattern(width-7-i*spacing,width-7-j*spacing);
+        let positions = alignment_positions(self.version);
+        let last = positions.len().saturating_sub(1);
+        for (i, center_y) in positions.iter().enumerate() {
+            for (j, center_x) in positions.iter().enumerate() {
+                // Skip the corners taken by finder patterns
+                if (i == 0 && (j == 0 || j == last)) || (i == last && j == 0) {
+                    continue;
+                }
+                self.alignment_pattern(*center_x as i32,*center_y as i32);
             }
         }
     }
 
     /// Places singular alignment pattern at (center_x,center_y)
     fn alignment_pattern(&mut self,center_x: i32, center_y: i32) {
-        use PatternMaskType::Alignment;
-        for x in -2..=2 {
-            for y in -2..=2 {
-                self.bitmap[(x+center_x) as usize][(y+center_y) as usize] = 
-                    (std::cmp::max(x.abs()+1,y.abs()+1) % 2).try_into().unwrap();
-                self.pattern_mask[(x+center_x) as usize][(y+center_y) as usize] = Alignment;
+        for x in -2i32..=2 {
+            for y in -2i32..=2 {
+                let value = (std::cmp::max(x.abs()+1,y.abs()+1) % 2) as u8;
+                self.set_pattern((x+center_x) as usize,(y+center_y) as usize,value);
             }
         }
         
@@ -128,77 +249,156 @@ impl QR {
     /// Places formatting pattern for error correction level and mask id
     /// This pattern wraps around the finder patterns
     pub fn format_pattern(&mut self) {
-        use PatternMaskType::Format;
-        use error_correction::ECLevel::*;
-        let ec = match self.ec_level {
-            L => 1,
-            M => 0,
-            Q => 3,
-            H => 2,
-        };
-        let mut info: Vec<u8> = Vec::with_capacity(15);
-        bits::push_to_bit_list(&mut info, ec, 2);
-        bits::push_to_bit_list(&mut info, self.mask_index as u32, 3);
-        for _ in 0..10 {info.push(0)}
-        let format_divisor: Vec<u8> = vec![1,0,1,0,0,1,1,0,1,1,1];
-        let format_mask: Vec<u8> = vec![1,0,1,0,1,0,0,0,0,0,1,0,0,1,0];
-
-        // Polynomial division for error correction
-        let rest = error_correction::poly_rest(&info, &format_divisor);
-        for (i, x) in rest.into_iter().enumerate() {info[i+5] = x;}
-
-        let width = self.bitmap.len();
-        for (i, x) in info.iter().enumerate() {
-            let bit = *x ^ format_mask[i];
-            if i < 7 {
-                self.bitmap[8][i + if i==6 {1} else {0}] = bit;
-                self.pattern_mask[8][i + if i==6 {1} else {0}] = Format;
-
-                self.bitmap[width-1-i][8] = bit;
-                self.pattern_mask[width-1-i][8] = Format;
+        for (y, x, bit) in self.format_modules(self.mask_index) {
+            self.set_pattern(y,x,bit);
+        }
+    }
+
+    /// Returns the (y, x, bit) of every module of the format information for the mask at mask_index
+    /// Regular qr codes and rectangular micro qr codes place each bit twice
+    pub(crate) fn format_modules(&self, mask_index: u8) -> Vec<(usize,usize,u8)> {
+        let mut out = Vec::with_capacity(36);
+        if self.kind == SymbolKind::Micro {
+            let info = micro_format_info(self.version,self.ec_level,mask_index);
+            for (i, bit) in info.into_iter().enumerate() {
+                let (y, x) = micro_format_position(i);
+                out.push((y, x, bit));
             }
-            else {
-                self.bitmap[if i<9 {15} else {14} - i][8] = bit;
-                self.pattern_mask[if i<9 {15} else {14} - i][8] = Format;
-                
-                self.bitmap[8][width-15+i] = bit;
-                self.pattern_mask[8][width-15+i] = Format;
+            return out;
+        }
+        if self.kind == SymbolKind::Rmqr {
+            let (height, width) = (self.bitmap.height(), self.bitmap.width());
+            let info = rmqr_format_info(self.version,self.ec_level);
+            for (i, bits) in info.into_iter().enumerate() {
+                for (bit, (y, x)) in bits.into_iter().zip(rmqr_format_positions(i,height,width)) {
+                    out.push((y, x, bit));
+                }
+            }
+            return out;
+        }
+        let width = self.bitmap.height();
+        let info = format_info(self.ec_level,mask_index);
+        for (i, bit) in info.into_iter().enumerate() {
+            for (y, x) in format_positions(i,width) {
+                out.push((y, x, bit));
             }
         }
+        return out;
     }
 
 
     /// Places version information for codes at version 7 or higher
     /// These are located next to the bottom left and top right finder patterns
     fn version_information_pattern(&mut self) {
-        use PatternMaskType::Version;
-        if self.version < 7 {return ();}
-        // Version divisor polynomial x^12 + x^11 + ... + x^2 + 1
-        let version_divisor: Vec<u8> = vec![1,1,1,1,1,0,0,1,0,0,1,0,1];
-        // Get version bit_list
-        let mut version_bits: Vec<u8> = vec![];
-        bits::push_to_bit_list(&mut version_bits,self.version as u32,6);
-        bits::push_to_bit_list(&mut version_bits, 0, 12);
-        // Get error correction
-        let ec_version = error_correction::poly_rest(&version_bits,&version_divisor);
-        for (i, x) in ec_version.into_iter().enumerate() {
-            version_bits[i+6] = x;
-        }
+        if self.version < 7 {return;}
+        let version_bits = version_info(self.version);
         // Place on image
-        let width = self.bitmap[0].len();
-        let height = self.bitmap.len();
+        let width = self.bitmap.height();
         for (i, bit) in version_bits.iter().rev().enumerate() {
-            self.bitmap[i/3][i%3 + width-11] = *bit;
-            self.pattern_mask[i/3][i%3 + width-11] = Version;
-            self.bitmap[i%3 + height-11][i/3] = *bit;
-            self.pattern_mask[i%3 + height-11][i/3] = Version;
+            for (y, x) in version_positions(i,width) {
+                self.set_pattern(y,x,*bit);
+            }
         }
     }
     
     /// Places single dark module on the top right corner of the bottom left finder pattern
     fn dark_module(&mut self) {
-        use PatternMaskType::DarkModule;
-        self.bitmap[4 * self.version as usize + 9][8] = 1;
-        self.pattern_mask[4 * self.version as usize+ 9][8] = DarkModule;
+        self.set_pattern(4 * self.version as usize + 9,8,1);
+    }
+}
+
+/// Returns the 15 format bits for an error correction level and mask id, after the format mask is applied
+pub(crate) fn format_info(ec_level: ECLevel, mask_index: u8) -> Vec<u8> {
+    use ECLevel::*;
+    let ec = match ec_level {
+        L => 1,
+        M => 0,
+        Q => 3,
+        H => 2,
+    };
+    return format_bits(ec << 3 | mask_index as u32, 0b101010000010010);
+}
+
+/// Returns the 15 format bits of a micro qr code, after the format mask is applied
+/// The symbol number counts every allowed (version, ec_level) pair, from 0 for M1 to 7 for M4-Q
+pub(crate) fn micro_format_info(version: u8, ec_level: ECLevel, mask_index: u8) -> Vec<u8> {
+    use ECLevel::*;
+    let symbol_number = if version == 1 {0} else {
+        version as u32 * 2 - 3 + match ec_level {L => 0, M => 1, _ => 2}
+    };
+    return format_bits(symbol_number << 2 | mask_index as u32, 0b100010001000101);
+}
+
+/// Adds 10 error correction bits to 5 bits of format data and xors the result with format_mask
+fn format_bits(data: u32, format_mask: u32) -> Vec<u8> {
+    let mut info: Vec<u8> = Vec::with_capacity(15);
+    bits::push_to_bit_list(&mut info, data, 5);
+    for _ in 0..10 {info.push(0)}
+    let format_divisor: Vec<u8> = vec![1,0,1,0,0,1,1,0,1,1,1];
+    let mut mask_bits: Vec<u8> = Vec::with_capacity(15);
+    bits::push_to_bit_list(&mut mask_bits, format_mask, 15);
+
+    // Polynomial division for error correction
+    let rest = error_correction::poly_rest(&info, &format_divisor);
+    for (i, x) in rest.into_iter().enumerate() {info[i+5] = x;}
+    for (i, x) in info.iter_mut().enumerate() {*x ^= mask_bits[i];}
+    return info;
+}
+
+/// Returns the two (y, x) positions of format bit i in a code of size width
+pub(crate) fn format_positions(i: usize, width: usize) -> [(usize,usize);2] {
+    if i < 7 {
+        return [(8, i + if i==6 {1} else {0}), (width-1-i, 8)];
+    }
+    return [(if i<9 {15} else {14} - i, 8), (8, width-15+i)];
+}
+
+/// Returns the (y, x) position of format bit i in a micro qr code
+/// Bits go along row 8 under the finder pattern, then up column 8
+pub(crate) fn micro_format_position(i: usize) -> (usize,usize) {
+    if i < 8 {
+        return (8, i + 1);
+    }
+    return (15 - i, 8);
+}
+
+/// Returns the 18 format bits of a rectangular micro qr code, counted from the least significant bit
+/// Each bit is returned as (finder side, sub-finder side), because the two copies use different masks
+pub(crate) fn rmqr_format_info(version: u8, ec_level: ECLevel) -> Vec<[u8;2]> {
+    // Error correction bit followed by the 5 bit version indicator, then the same BCH code as version information
+    let info = version_info((ec_level == ECLevel::H) as u8 * 32 + version - 1);
+    let mut masks = [vec![], vec![]];
+    bits::push_to_bit_list(&mut masks[0],0b011111101010110010,18);
+    bits::push_to_bit_list(&mut masks[1],0b100000101001111011,18);
+    return (0..18).rev().map(|i| [info[i] ^ masks[0][i], info[i] ^ masks[1][i]]).collect();
+}
+
+/// Returns the (y, x) positions of format bit i of a rectangular micro qr code next to the finder and sub-finder patterns
+/// Both copies fill three columns of five modules, the last three bits go in a fourth column or a row above the sub-finder
+pub(crate) fn rmqr_format_positions(i: usize, height: usize, width: usize) -> [(usize,usize);2] {
+    if i < 15 {
+        return [(1 + i % 5, 8 + i / 5), (height - 6 + i % 5, width - 8 + i / 5)];
     }
+    return [(i - 14, 11), (height - 6, width - 20 + i)];
+}
+
+/// Returns the 18 version information bits for versions 7 and higher
+pub(crate) fn version_info(version: u8) -> Vec<u8> {
+    // Version divisor polynomial x^12 + x^11 + ... + x^2 + 1
+    let version_divisor: Vec<u8> = vec![1,1,1,1,1,0,0,1,0,0,1,0,1];
+    // Get version bit_list
+    let mut version_bits: Vec<u8> = vec![];
+    bits::push_to_bit_list(&mut version_bits,version as u32,6);
+    bits::push_to_bit_list(&mut version_bits, 0, 12);
+    // Get error correction
+    let ec_version = error_correction::poly_rest(&version_bits,&version_divisor);
+    for (i, x) in ec_version.into_iter().enumerate() {
+        version_bits[i+6] = x;
+    }
+    return version_bits;
+}
+
+/// Returns the two (y, x) positions of the i-th version bit, counted from the least significant bit
+pub(crate) fn version_positions(i: usize, width: usize) -> [(usize,usize);2] {
+    return [(i/3, i%3 + width-11), (i%3 + width-11, i/3)];
 }
@@ -0,0 +1,317 @@
+use crate::grid::Grid;
+use crate::qr::QR;
+
+/// Points in a millimetre, with 72 points in an inch
+const POINTS_PER_MM: f64 = 72.0 / 25.4;
+
+/// Output intent of PDF/X documents, the registered characterization of offset printing on coated paper
+const OUTPUT_INTENT: &str = "<< /Type /OutputIntent /S /GTS_PDFX /OutputConditionIdentifier (FOGRA39) \
+    /OutputCondition (Offset printing on coated paper, ISO 12647-2:2004) /RegistryName (http://www.color.org) /Info (Coated FOGRA39) >>";
+
+/// Physical length for print output
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub enum Length {
+    Millimeters(f64),
+    Points(f64),
+}
+
+impl Length {
+    /// Returns the length in points
+    pub fn points(&self) -> f64 {
+        return match self {
+            Length::Millimeters(mm) => mm * POINTS_PER_MM,
+            Length::Points(pt) => *pt,
+        };
+    }
+}
+
+/// Color for print output, with every component from 0 to 1
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub enum PrintColor {
+    /// Cyan, magenta, yellow and black
+    Cmyk(f64,f64,f64,f64),
+    Rgb(f64,f64,f64),
+    Gray(f64),
+}
+
+impl PrintColor {
+    /// Converts an RGB color to CMYK without a color profile, so black becomes pure black ink
+    pub fn cmyk_from_rgb(r: u8, g: u8, b: u8) -> PrintColor {
+        let (r, g, b) = (r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0);
+        let k = 1.0 - r.max(g).max(b);
+        if k == 1.0 {
+            return PrintColor::Cmyk(0.0,0.0,0.0,1.0);
+        }
+        return PrintColor::Cmyk((1.0 - r - k) / (1.0 - k),(1.0 - g - k) / (1.0 - k),(1.0 - b - k) / (1.0 - k),k);
+    }
+
+    /// Returns the PDF operator that sets the fill color
+    fn pdf_operator(&self) -> String {
+        return match self {
+            PrintColor::Cmyk(c, m, y, k) => format!("{} {} {} {} k",number(*c),number(*m),number(*y),number(*k)),
+            PrintColor::Rgb(r, g, b) => format!("{} {} {} rg",number(*r),number(*g),number(*b)),
+            PrintColor::Gray(gray) => format!("{} g",number(*gray)),
+        };
+    }
+
+    /// Returns the PostScript operator that sets the color
+    fn postscript_operator(&self) -> String {
+        return match self {
+            PrintColor::Cmyk(c, m, y, k) => format!("{} {} {} {} setcmykcolor",number(*c),number(*m),number(*y),number(*k)),
+            PrintColor::Rgb(r, g, b) => format!("{} {} {} setrgbcolor",number(*r),number(*g),number(*b)),
+            PrintColor::Gray(gray) => format!("{} setgray",number(*gray)),
+        };
+    }
+}
+
+/// Options for rendering a qr code as PDF or EPS
+#[derive(Debug,Clone,PartialEq)]
+pub struct PrintOptions {
+    /// Width and height of a module
+    pub module_size: Length,
+    /// Width of the light border around the code in modules, included in the trim box
+    pub quiet_zone: usize,
+    /// Color of the dark modules
+    pub foreground: PrintColor,
+    /// Color of the light modules, quiet zone and bleed, None leaves the paper unprinted
+    pub background: Option<PrintColor>,
+    /// Margin around the trim box that is printed and cut off, added to the media box
+    pub bleed: Length,
+    /// Title written to the document information
+    pub title: Option<String>,
+    /// Creation time of PDF documents in seconds since the Unix epoch, None uses the current time
+    pub creation_date: Option<u64>,
+}
+
+impl Default for PrintOptions {
+    fn default() -> PrintOptions {
+        PrintOptions {
+            module_size: Length::Millimeters(0.5),
+            quiet_zone: 4,
+            foreground: PrintColor::Cmyk(0.0,0.0,0.0,1.0),
+            background: None,
+            bleed: Length::Points(0.0),
+            title: None,
+            creation_date: None,
+        }
+    }
+}
+
+/// Size of the trim box and bleed around it in points
+struct Boxes {
+    width: f64,
+    height: f64,
+    bleed: f64,
+}
+
+impl Boxes {
+    fn new(bitmap: &Grid, options: &PrintOptions) -> Boxes {
+        let size = options.module_size.points();
+        return Boxes {
+            width: (bitmap.width() + 2 * options.quiet_zone) as f64 * size,
+            height: (bitmap.height() + 2 * options.quiet_zone) as f64 * size,
+            bleed: options.bleed.points(),
+        };
+    }
+
+    /// Returns the media box, which is also the crop and bleed box
+    fn media(&self) -> [f64; 4] {
+        return [0.0, 0.0, self.width + 2.0 * self.bleed, self.height + 2.0 * self.bleed];
+    }
+
+    /// Returns the trim box, the code with its quiet zone
+    fn trim(&self) -> [f64; 4] {
+        return [self.bleed, self.bleed, self.bleed + self.width, self.bleed + self.height];
+    }
+}
+
+impl QR {
+    /// Render qr code as a PDF document
+    pub fn to_pdf(&self, options: &PrintOptions) -> Vec<u8> {
+        return to_pdf(&self.bitmap,options);
+    }
+
+    /// Render qr code as an Encapsulated PostScript file
+    pub fn to_eps(&self, options: &PrintOptions) -> String {
+        return to_eps(&self.bitmap,options);
+    }
+}
+
+/// Returns (x, y, width, height) in points of each horizontal run of dark modules
+/// The origin is the bottom left corner of the media box, as in PDF and PostScript
+fn module_rects(bitmap: &Grid, options: &PrintOptions) -> Vec<[f64; 4]> {
+    let size = options.module_size.points();
+    let offset = options.bleed.points() + options.quiet_zone as f64 * size;
+    let mut out = Vec::new();
+    for y in 0..bitmap.height() {
+        let bottom = offset + (bitmap.height() - y - 1) as f64 * size;
+        for (start, len) in bitmap.dark_runs(y) {
+            out.push([offset + start as f64 * size, bottom, len as f64 * size, size]);
+        }
+    }
+    return out;
+}
+
+/// Render a grid of modules as a single page PDF document
+/// The page has crop, bleed and trim boxes, with the trim box around the code and its quiet zone.
+/// Dark modules are filled rectangles, with horizontal runs merged into one rectangle.
+/// Without RGB colors the document is PDF/X-1a:2001, with the output intent of coated FOGRA39 paper.
+pub fn to_pdf(bitmap: &Grid, options: &PrintOptions) -> Vec<u8> {
+    let boxes = Boxes::new(bitmap,options);
+    let mut content = String::from("q\n");
+    if let Some(background) = &options.background {
+        content.push_str(&format!("{}\n{} re\nf\n",background.pdf_operator(),numbers(&boxes.media())));
+    }
+    content.push_str(&options.foreground.pdf_operator());
+    content.push('\n');
+    for rect in module_rects(bitmap,options) {
+        content.push_str(&format!("{} re\n",numbers(&rect)));
+    }
+    content.push_str("f\nQ\n");
+
+    let media = format!("[{}]",numbers(&boxes.media()));
+    // PDF/X-1a only allows CMYK and gray
+    let pdfx = ![Some(options.foreground), options.background].iter().flatten().any(|color| matches!(color, PrintColor::Rgb(..)));
+    let date = pdf_date(options.creation_date.unwrap_or_else(|| {
+        std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |time| time.as_secs())
+    }));
+    let mut info = format!("/Creator (qr_generator) /Producer (qr_generator) /CreationDate ({}) /ModDate ({}) /Trapped /False",date,date);
+    match &options.title {
+        Some(title) => info = format!("/Title {} {}",pdf_text_string(title),info),
+        None if pdfx => info = format!("/Title (QR code) {}",info),
+        None => {},
+    }
+    let mut catalog = String::from("<< /Type /Catalog /Pages 2 0 R >>");
+    if pdfx {
+        info.push_str(" /GTS_PDFXVersion (PDF/X-1:2001) /GTS_PDFXConformance (PDF/X-1a:2001)");
+        catalog = String::from("<< /Type /Catalog /Pages 2 0 R /OutputIntents [6 0 R] >>");
+    }
+    let mut objects = vec![
+        catalog,
+        String::from("<< /Type /Pages /Kids [3 0 R] /Count 1 >>"),
+        format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox {} /CropBox {} /BleedBox {} /TrimBox [{}] /Resources << >> /Contents 4 0 R >>",
+            media, media, media, numbers(&boxes.trim()),
+        ),
+        format!("<< /Length {} >>\nstream\n{}endstream",content.len(),content),
+        format!("<< {} >>",info),
+    ];
+    if pdfx {
+        objects.push(String::from(OUTPUT_INTENT));
+    }
+
+    // The comment of high bytes marks the file as binary for transfer programs
+    let mut out = b"%PDF-1.3\n%\xe2\xe3\xcf\xd3\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        out.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n",i + 1,object).as_bytes());
+    }
+    let xref = out.len();
+    // Each cross reference entry is exactly 20 bytes including its end of line
+    let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n",objects.len() + 1);
+    for offset in offsets {
+        trailer.push_str(&format!("{:010} 00000 n \n",offset));
+    }
+    let id = pdf_id(&out);
+    trailer.push_str(&format!("trailer\n<< /Size {} /Root 1 0 R /Info 5 0 R /ID [<{}> <{}>] >>\nstartxref\n{}\n%%EOF\n",objects.len() + 1,id,id,xref));
+    out.extend_from_slice(trailer.as_bytes());
+    return out;
+}
+
+/// Render a grid of modules as an Encapsulated PostScript file
+/// The bounding box includes the bleed, and the trim and bleed boxes are passed on to PDF converters with pdfmark.
+/// Dark modules are filled rectangles, with horizontal runs merged into one rectangle.
+pub fn to_eps(bitmap: &Grid, options: &PrintOptions) -> String {
+    let boxes = Boxes::new(bitmap,options);
+    let media = boxes.media();
+    let mut out = String::from("%!PS-Adobe-3.0 EPSF-3.0\n");
+    out.push_str(&format!("%%BoundingBox: 0 0 {} {}\n",media[2].ceil(),media[3].ceil()));
+    out.push_str(&format!("%%HiResBoundingBox: {}\n",numbers(&media)));
+    if let Some(title) = &options.title {
+        // Comments end at the line, so line breaks in the title are replaced
+        out.push_str(&format!("%%Title: {}\n",title.replace(['\r', '\n']," ")));
+    }
+    out.push_str("%%Creator: qr_generator\n%%LanguageLevel: 2\n%%Pages: 1\n%%EndComments\n");
+    out.push_str("%%BeginProlog\n/R /rectfill load def\n%%EndProlog\n%%Page: 1 1\n");
+    // Interpreters without pdfmark discard it
+    out.push_str("/pdfmark where {pop} {userdict /pdfmark /cleartomark load put} ifelse\n");
+    out.push_str(&format!("[ {{ThisPage}} << /TrimBox [{}] /BleedBox [{}] >> /PUT pdfmark\n",numbers(&boxes.trim()),numbers(&media)));
+    if let Some(background) = &options.background {
+        out.push_str(&format!("{}\n{} R\n",background.postscript_operator(),numbers(&media)));
+    }
+    out.push_str(&options.foreground.postscript_operator());
+    out.push('\n');
+    for rect in module_rects(bitmap,options) {
+        out.push_str(&format!("{} R\n",numbers(&rect)));
+    }
+    out.push_str("showpage\n%%Trailer\n%%EOF\n");
+    return out;
+}
+
+/// Formats a number with at most four decimal places and no trailing zeros
+fn number(value: f64) -> String {
+    let out = format!("{:.4}",value);
+    let out = out.trim_end_matches('0').trim_end_matches('.');
+    return if out == "-0" {String::from("0")} else {out.to_string()};
+}
+
+/// Formats numbers separated by spaces
+fn numbers(values: &[f64]) -> String {
+    return values.iter().map(|value| number(*value)).collect::<Vec<String>>().join(" ");
+}
+
+/// Returns the time in seconds since the Unix epoch as a PDF date in UTC
+fn pdf_date(seconds: u64) -> String {
+    // Days to the civil date, with years starting in March so that leap days come last
+    let days = seconds / 86400 + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 {month + 3} else {month - 9};
+    let year = era * 400 + year_of_era + (month <= 2) as u64;
+    let time = seconds % 86400;
+    return format!("D:{:04}{:02}{:02}{:02}{:02}{:02}Z",year,month,day,time / 3600,time / 60 % 60,time % 60);
+}
+
+/// Returns the file identifier of a PDF document as 32 hex digits, derived from its contents
+fn pdf_id(contents: &[u8]) -> String {
+    use std::hash::{Hash, Hasher};
+    let mut out = String::with_capacity(32);
+    for seed in 0..2u8 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        seed.hash(&mut hasher);
+        contents.hash(&mut hasher);
+        out.push_str(&format!("{:016X}",hasher.finish()));
+    }
+    return out;
+}
+
+/// Returns str as a PDF string, literal if it is ASCII and UTF-16 in hex otherwise
+fn pdf_text_string(str: &str) -> String {
+    if str.is_ascii() {
+        let mut out = String::from("(");
+        for ch in str.chars() {
+            match ch {
+                '(' | ')' | '\\' => {
+                    out.push('\\');
+                    out.push(ch);
+                },
+                '\r' => out.push_str("\\r"),
+                '\n' => out.push_str("\\n"),
+                _ => out.push(ch),
+            }
+        }
+        out.push(')');
+        return out;
+    }
+    let mut out = String::from("<FEFF");
+    for unit in str.encode_utf16() {
+        out.push_str(&format!("{:04X}",unit));
+    }
+    out.push('>');
+    return out;
+}
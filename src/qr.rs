@@ -1,40 +1,73 @@
 use crate::error_correction;
-use crate::bits;
+use crate::segment;
+use crate::segment::Segment;
 use error_correction::ECLevel;
 use crate::metadata;
-use crate::patterns::PatternMaskType;
+use crate::error::QrError;
+use crate::grid::Grid;
+use crate::mask::MaskScorer;
 
+#[derive(Clone)]
 pub struct QR {
-    pub bitmap: Vec<Vec<u8>>,
-    pub pattern_mask: Vec<Vec<PatternMaskType>>,
+    pub kind: SymbolKind,
+    /// Modules of the code, without a quiet zone
+    pub bitmap: Grid,
+    /// Modules covered by fixed patterns and format information, which are never masked
+    pub pattern_mask: Grid,
     pub ec_level: ECLevel,
     pub version: u8,
     pub mask_index: u8,
 }
 
-#[derive(Debug,Copy,Clone)]
+/// Family of symbol, which decides the patterns, sizes and capacity of each version
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum SymbolKind {
+    /// Regular qr code, versions 1 to 40
+    Qr,
+    /// Micro qr code with a single finder pattern, versions M1 to M4
+    Micro,
+    /// Rectangular micro qr code, versions 1 to 32 from R7x43 to R17x139
+    Rmqr,
+}
+
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
 pub enum Encoding {
     Numeric = 1,
     Alphanumeric = 2,
     Byte = 4,
-    //Kanji = 8,
+    Kanji = 8,
     ECI = 7,
 }
 
 impl QR {
     /// Create a qr code from str using encoding
-    pub fn new(str: &str, enc: Encoding, ec_level: ECLevel) -> QR {
-        // Get encoded string
-        let bits: Vec<u8> = match enc {
-            Encoding::Alphanumeric => bits::encode_alphanumeric(&str),
-            Encoding::Numeric => bits::encode_numeric(&str),
-            Encoding::Byte => bits::encode_latin(&str),
-            _ => Err(0 as char),
-        }.unwrap_or_else(|ch| panic!("Unrecognized character '{}' in encoding {:?}",ch,enc));
+    pub fn new(str: &str, enc: Encoding, ec_level: ECLevel) -> Result<QR,QrError> {
+        return QR::from_segments(&[Segment::new(str,enc)],ec_level);
+    }
+
+    /// Create a qr code from str, split into the segments that need the fewest bits
+    pub fn new_optimized(str: &str, ec_level: ECLevel) -> Result<QR,QrError> {
+        return QR::from_segments(&segment::optimize(str,ec_level)?,ec_level);
+    }
+
+    /// Create a qr code from a list of segments written one after another
+    pub fn from_segments(segments: &[Segment], ec_level: ECLevel) -> Result<QR,QrError> {
         // Get minimum version
-        let (version, num_codewords) = QR::get_min_version(&str,enc, ec_level);
+        let (version, _) = QR::get_min_version_segments(segments,ec_level)?;
+        return QR::create(segments,version,ec_level,None,MaskScorer::Legacy);
+    }
+
+    /// Create a qr code of a fixed version from a list of segments
+    /// Uses the mask with the lowest penalty as scored by scorer unless mask_index is given
+    pub(crate) fn create(segments: &[Segment], version: u8, ec_level: ECLevel, mask_index: Option<u8>, scorer: MaskScorer) -> Result<QR,QrError> {
+        return QR::create_with_header(segments,version,ec_level,mask_index,scorer,&[]);
+    }
+
+    /// Same as create with header bits written before the segments
+    pub(crate) fn create_with_header(segments: &[Segment], version: u8, ec_level: ECLevel, mask_index: Option<u8>, scorer: MaskScorer, header: &[u8]) -> Result<QR,QrError> {
+        let num_codewords = metadata::blocks_table_get(version,ec_level).0;
         // Encode message
-        let message = metadata::get_codewords(&bits,str.chars().count(),enc,version,num_codewords);
+        let message = metadata::get_codewords(segments,version,num_codewords,header)?;
         assert_eq!(message.len(),num_codewords);
         // Apply error correction
         let message_ec = error_correction::ec_encode(message,version,ec_level);
@@ -42,16 +75,20 @@ impl QR {
         // Create output code
         let version_size = QR::get_version_size(version);
         let mut out = QR {
-            bitmap: vec![vec![0;version_size];version_size],
-            pattern_mask: vec![vec![PatternMaskType::None;version_size];version_size],
+            kind: SymbolKind::Qr,
+            bitmap: Grid::new(version_size,version_size),
+            pattern_mask: Grid::new(version_size,version_size),
             version,
             ec_level,
             mask_index: 0,
         };
         out.apply_patterns();
-        out.write_message(message_ec);
-        out.apply_masking();
-        return out;
+        out.write_message(message_ec)?;
+        match mask_index {
+            Some(mask_index) => out.apply_mask(mask_index),
+            None => out.apply_masking_with(scorer),
+        }
+        return Ok(out);
     }
 
     /// Get width of a puzzle of size version
@@ -61,28 +98,14 @@ impl QR {
 
     /// Get number of modules that can encode a message
     pub fn get_available_modules(version: usize) -> usize {
-        let alignment_count = version / 7 + 2;
+        let alignment_count = crate::patterns::alignment_positions(version as u8).len();
         return (version * 4 + 17).pow(2) // Num total blocks
             - 3 * 8 * 8 // Finder patterns
-            - if version == 1 {0} else {(alignment_count.pow(2) - 3) * 5 * 5} // Alignment patterns
+            - alignment_count.pow(2).saturating_sub(3) * 5 * 5 // Alignment patterns
             - 2 * (version * 4 + 1) // Timing patterns
-            + (alignment_count - 2) * 5 * 2 //Add timing patterns overlapped with alignment
+            + alignment_count.saturating_sub(2) * 5 * 2 //Add timing patterns overlapped with alignment
             - 2 * 15 // Error and mask info
             - 1 // Dark module
             - if version > 6 {2 * 3 * 6} else {0} // Version format data 
     }
-    
-    /// Save qr code as an image at file_path
-    pub fn to_image(&self, file_path: &str) -> image::ImageResult<()> {
-        let size = self.version as usize * 4 + 17 + 8;
-        let mut as_u8: Vec<u8> = vec![255;size*size];
-        
-        for (i, vec) in self.bitmap.iter().enumerate() {
-            for (j, el) in vec.iter().enumerate() {
-                as_u8[size * (i+4) + (j+4)] = if *el == 1 {0} else {255};
-            }
-        }
-        image::save_buffer(file_path,&as_u8[..],size as u32,size as u32,image::ColorType::L8)
-
-    }
 }
\ No newline at end of file
@@ -0,0 +1,120 @@
+use std::io::Write;
+use image::{DynamicImage, ImageEncoder, ImageResult, Rgba, RgbaImage};
+use image::codecs::{bmp, gif, jpeg, png, webp};
+use crate::grid::Grid;
+use crate::qr::QR;
+
+/// Options for rendering a qr code as a raster image
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct RasterOptions {
+    /// Width and height of a module in pixels
+    pub scale: u32,
+    /// Width of the light border around the code in modules
+    pub quiet_zone: u32,
+    /// RGBA color of the dark modules
+    pub foreground: [u8; 4],
+    /// RGBA color of the light modules and quiet zone
+    pub background: [u8; 4],
+}
+
+impl Default for RasterOptions {
+    fn default() -> RasterOptions {
+        RasterOptions {
+            scale: 1,
+            quiet_zone: 4,
+            foreground: [0, 0, 0, 255],
+            background: [255, 255, 255, 255],
+        }
+    }
+}
+
+/// Encoded image formats that a qr code can be written as
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum RasterFormat {
+    Png,
+    /// JPEG with a quality from 1 to 100, the alpha channel is dropped
+    Jpeg(u8),
+    Bmp,
+    Gif,
+    /// Lossless WebP
+    WebP,
+}
+
+impl RasterFormat {
+    /// MIME type of the format, for use in a Content-Type header
+    pub fn mime_type(&self) -> &'static str {
+        return match self {
+            RasterFormat::Png => "image/png",
+            RasterFormat::Jpeg(_) => "image/jpeg",
+            RasterFormat::Bmp => "image/bmp",
+            RasterFormat::Gif => "image/gif",
+            RasterFormat::WebP => "image/webp",
+        };
+    }
+}
+
+impl QR {
+    /// Render qr code as an RGBA image buffer
+    pub fn to_image_buffer(&self, options: &RasterOptions) -> RgbaImage {
+        return to_image_buffer(&self.bitmap,options);
+    }
+
+    /// Render qr code as a dynamic image
+    pub fn to_dynamic_image(&self, options: &RasterOptions) -> DynamicImage {
+        return DynamicImage::ImageRgba8(self.to_image_buffer(options));
+    }
+
+    /// Render qr code and encode it into writer
+    /// The writer does not need to be seekable, so it can be a socket or response body
+    pub fn write_image<W: Write>(&self, writer: W, format: RasterFormat, options: &RasterOptions) -> ImageResult<()> {
+        return write_image(&self.bitmap,writer,format,options);
+    }
+
+    /// Save qr code as an image at file_path
+    /// Uses the default options and picks the format from the file extension
+    pub fn to_image(&self, file_path: &str) -> ImageResult<()> {
+        return DynamicImage::ImageLuma8(self.to_dynamic_image(&RasterOptions::default()).to_luma8()).save(file_path);
+    }
+}
+
+/// Render a grid of modules as an RGBA image buffer
+/// Each module becomes a square of scale by scale pixels
+pub fn to_image_buffer(bitmap: &Grid, options: &RasterOptions) -> RgbaImage {
+    let border = options.quiet_zone * options.scale;
+    let width = bitmap.width() as u32 * options.scale + 2 * border;
+    let height = bitmap.height() as u32 * options.scale + 2 * border;
+    return RgbaImage::from_fn(width, height, |x, y| {
+        if x < border || y < border {
+            return Rgba(options.background);
+        }
+        let (x, y) = (((x - border) / options.scale) as usize, ((y - border) / options.scale) as usize);
+        if x < bitmap.width() && y < bitmap.height() && bitmap.get(x,y) {
+            return Rgba(options.foreground);
+        }
+        return Rgba(options.background);
+    });
+}
+
+/// Render a grid of modules and encode it into writer as format
+pub fn write_image<W: Write>(bitmap: &Grid, writer: W, format: RasterFormat, options: &RasterOptions) -> ImageResult<()> {
+    let image = to_image_buffer(bitmap,options);
+    let (width, height) = image.dimensions();
+    return match format {
+        RasterFormat::Png => png::PngEncoder::new(writer)
+            .write_image(image.as_raw(),width,height,image::ExtendedColorType::Rgba8),
+        RasterFormat::Jpeg(quality) => {
+            let rgb = DynamicImage::ImageRgba8(image).to_rgb8();
+            jpeg::JpegEncoder::new_with_quality(writer,quality.clamp(1,100))
+                .write_image(rgb.as_raw(),width,height,image::ExtendedColorType::Rgb8)
+        },
+        RasterFormat::Bmp => {
+            let mut writer = writer;
+            bmp::BmpEncoder::new(&mut writer)
+                .write_image(image.as_raw(),width,height,image::ExtendedColorType::Rgba8)
+        },
+        RasterFormat::Gif => gif::GifEncoder::new(writer)
+            .write_image(image.as_raw(),width,height,image::ExtendedColorType::Rgba8),
+        RasterFormat::WebP => webp::WebPEncoder::new_lossless(writer)
+            .write_image(image.as_raw(),width,height,image::ExtendedColorType::Rgba8),
+    };
+}
@@ -0,0 +1,490 @@
+use image::{DynamicImage, GrayImage, ImageResult};
+use crate::decode;
+use crate::decode::DecodedQr;
+use crate::grid::Grid;
+
+/// Position in an image, in pixels from the top left corner
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// A qr code found in an image
+#[derive(Debug,Clone,PartialEq)]
+pub struct ScannedQr {
+    pub decoded: DecodedQr,
+    /// Outer corners of the code in the order top left, top right, bottom right, bottom left
+    /// Top left is the corner whose finder pattern is diagonal from the empty corner
+    pub bounds: [Point; 4],
+}
+
+/// Find and decode every qr code in an image
+/// Codes that are found but cannot be decoded are skipped
+pub fn read_image(image: &DynamicImage) -> Vec<ScannedQr> {
+    let bits = binarize(&image.to_luma8());
+    let mut finders = find_finder_patterns(&bits);
+    let mut out: Vec<ScannedQr> = Vec::new();
+    for (tl, tr, bl) in finder_triples(&finders) {
+        if [tl, tr, bl].iter().any(|i| finders[*i].used) {
+            continue;
+        }
+        if let Some(scanned) = read_code(&bits,&finders[tl],&finders[tr],&finders[bl]) {
+            for i in [tl, tr, bl] {
+                finders[i].used = true;
+            }
+            out.push(scanned);
+        }
+    }
+    return out;
+}
+
+/// Find and decode every qr code in an encoded image such as a PNG or JPEG file
+pub fn read_image_bytes(bytes: &[u8]) -> ImageResult<Vec<ScannedQr>> {
+    return Ok(read_image(&image::load_from_memory(bytes)?));
+}
+
+/// Image where each pixel is either dark or light
+struct BitImage {
+    width: usize,
+    height: usize,
+    dark: Vec<bool>,
+}
+
+impl BitImage {
+    /// Returns whether the pixel is dark, pixels outside of the image are light
+    fn get(&self, x: i64, y: i64) -> bool {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return false;
+        }
+        return self.dark[y as usize * self.width + x as usize];
+    }
+
+    /// Returns whether the pixel containing a point is dark
+    fn sample(&self, point: Point) -> bool {
+        return self.get(point.x.floor() as i64,point.y.floor() as i64);
+    }
+}
+
+/// Side length in pixels of the blocks used for local thresholds
+const BLOCK_SIZE: usize = 8;
+/// Blocks with less contrast than this are treated as a flat area
+const MIN_CONTRAST: u32 = 24;
+
+/// Converts a grayscale image to dark and light pixels
+/// Each 8x8 block gets a threshold from the average of the 5x5 blocks around it, so uneven lighting is handled
+/// Flat blocks take the threshold of their neighbors, or are assumed to be light background
+fn binarize(image: &GrayImage) -> BitImage {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let pixels = image.as_raw();
+    let mut dark = vec![false; width * height];
+    let blocks_x = width.div_ceil(BLOCK_SIZE);
+    let blocks_y = height.div_ceil(BLOCK_SIZE);
+    if blocks_x < 5 || blocks_y < 5 {
+        // Too small for local thresholds, use the middle of the darkest and lightest pixel
+        let min = pixels.iter().min().copied().unwrap_or(0) as u32;
+        let max = pixels.iter().max().copied().unwrap_or(0) as u32;
+        for (out, pixel) in dark.iter_mut().zip(pixels.iter()) {
+            *out = max - min >= MIN_CONTRAST && (*pixel as u32) * 2 < min + max;
+        }
+        return BitImage { width, height, dark };
+    }
+    // Average of each block
+    let mut averages = vec![0u32; blocks_x * blocks_y];
+    for by in 0..blocks_y {
+        for bx in 0..blocks_x {
+            let (mut sum, mut count, mut min, mut max) = (0u32, 0u32, 255u32, 0u32);
+            for y in by * BLOCK_SIZE..std::cmp::min((by + 1) * BLOCK_SIZE, height) {
+                for x in bx * BLOCK_SIZE..std::cmp::min((bx + 1) * BLOCK_SIZE, width) {
+                    let pixel = pixels[y * width + x] as u32;
+                    sum += pixel;
+                    count += 1;
+                    min = std::cmp::min(min, pixel);
+                    max = std::cmp::max(max, pixel);
+                }
+            }
+            let mut average = sum / count;
+            if max - min < MIN_CONTRAST {
+                // Flat block, assume it is light unless the blocks above and to the left are darker
+                average = min / 2;
+                if bx > 0 && by > 0 {
+                    let neighbors = (averages[(by - 1) * blocks_x + bx]
+                        + 2 * averages[by * blocks_x + bx - 1]
+                        + averages[(by - 1) * blocks_x + bx - 1]) / 4;
+                    if min < neighbors {
+                        average = neighbors;
+                    }
+                }
+            }
+            averages[by * blocks_x + bx] = average;
+        }
+    }
+    // Threshold from the surrounding 5x5 blocks
+    for by in 0..blocks_y {
+        let cy = by.clamp(2, blocks_y - 3);
+        for bx in 0..blocks_x {
+            let cx = bx.clamp(2, blocks_x - 3);
+            let mut sum = 0;
+            for y in cy - 2..=cy + 2 {
+                for x in cx - 2..=cx + 2 {
+                    sum += averages[y * blocks_x + x];
+                }
+            }
+            let threshold = sum / 25;
+            for y in by * BLOCK_SIZE..std::cmp::min((by + 1) * BLOCK_SIZE, height) {
+                for x in bx * BLOCK_SIZE..std::cmp::min((bx + 1) * BLOCK_SIZE, width) {
+                    dark[y * width + x] = pixels[y * width + x] as u32 <= threshold;
+                }
+            }
+        }
+    }
+    return BitImage { width, height, dark };
+}
+
+/// Center of a possible finder pattern
+struct FinderPattern {
+    x: f64,
+    y: f64,
+    module_size: f64,
+    /// Number of scanned rows that found this pattern
+    count: usize,
+    /// Already part of a decoded code
+    used: bool,
+}
+
+/// Returns whether dark, light, dark, light, dark run lengths are close to the 1:1:3:1:1 ratio of a finder pattern
+fn is_finder_ratio(counts: &[usize; 5]) -> bool {
+    let total: usize = counts.iter().sum();
+    if total < 7 || counts.contains(&0) {
+        return false;
+    }
+    let module_size = total as f64 / 7.0;
+    let variance = module_size / 2.0;
+    return (counts[0] as f64 - module_size).abs() < variance
+        && (counts[1] as f64 - module_size).abs() < variance
+        && (counts[2] as f64 - 3.0 * module_size).abs() < 3.0 * variance
+        && (counts[3] as f64 - module_size).abs() < variance
+        && (counts[4] as f64 - module_size).abs() < variance;
+}
+
+/// Scans every row for the 1:1:3:1:1 pattern of the finder, then confirms it vertically and horizontally
+/// Returns the centers of finder patterns found on at least two rows
+fn find_finder_patterns(bits: &BitImage) -> Vec<FinderPattern> {
+    let mut found: Vec<FinderPattern> = Vec::new();
+    for y in 0..bits.height {
+        let mut counts = [0usize; 5];
+        let mut state = 0;
+        for x in 0..=bits.width {
+            let dark = x < bits.width && bits.get(x as i64,y as i64);
+            if dark {
+                // Light to dark moves to the next dark run
+                if state % 2 == 1 {
+                    state += 1;
+                }
+                counts[state] += 1;
+                continue;
+            }
+            if state % 2 == 1 {
+                counts[state] += 1;
+                continue;
+            }
+            if state < 4 {
+                state += 1;
+                counts[state] += 1;
+                continue;
+            }
+            // End of the fifth run
+            if is_finder_ratio(&counts) {
+                handle_possible_center(bits,&counts,x,y,&mut found);
+            }
+            counts = [counts[2], counts[3], counts[4], 1, 0];
+            state = 3;
+        }
+    }
+    found.retain(|finder| finder.count >= 2);
+    found.sort_by_key(|finder| std::cmp::Reverse(finder.count));
+    found.truncate(MAX_FINDERS);
+    return found;
+}
+
+/// Limit on the finder candidates considered, so noisy images do not try too many combinations
+const MAX_FINDERS: usize = 40;
+
+/// Cross checks a row match in the other directions and adds it to found
+fn handle_possible_center(bits: &BitImage, counts: &[usize; 5], end: usize, y: usize, found: &mut Vec<FinderPattern>) {
+    let total: usize = counts.iter().sum();
+    let center_x = end as f64 - counts[4] as f64 - counts[3] as f64 - counts[2] as f64 / 2.0;
+    let Some((center_y, _)) = cross_check(bits,center_x as i64,y as i64,(0, 1),counts[2],total) else {return;};
+    let Some((center_x, total_x)) = cross_check(bits,center_x as i64,center_y as i64,(1, 0),counts[2],total) else {return;};
+    let Some((center_y, total_y)) = cross_check(bits,center_x as i64,center_y as i64,(0, 1),counts[2],total) else {return;};
+    let module_size = (total_x + total_y) as f64 / 14.0;
+    for finder in found.iter_mut() {
+        let close = (finder.x - center_x).abs() <= module_size.max(2.0) && (finder.y - center_y).abs() <= module_size.max(2.0);
+        if close && (finder.module_size / module_size - 1.0).abs() < 0.5 {
+            // Running average of every row that found the same pattern
+            let n = finder.count as f64;
+            finder.x = (finder.x * n + center_x) / (n + 1.0);
+            finder.y = (finder.y * n + center_y) / (n + 1.0);
+            finder.module_size = (finder.module_size * n + module_size) / (n + 1.0);
+            finder.count += 1;
+            return;
+        }
+    }
+    found.push(FinderPattern { x: center_x, y: center_y, module_size, count: 1, used: false });
+}
+
+/// Counts the runs through (x, y) in direction dir and checks that they have the finder ratio
+/// Returns the center coordinate along dir and the total length of the runs
+fn cross_check(bits: &BitImage, x: i64, y: i64, dir: (i64, i64), max_count: usize, original_total: usize) -> Option<(f64,usize)> {
+    let get = |t: i64| bits.get(x + t * dir.0,y + t * dir.1);
+    let in_bounds = |t: i64| {
+        let (px, py) = (x + t * dir.0, y + t * dir.1);
+        px >= 0 && py >= 0 && (px as usize) < bits.width && (py as usize) < bits.height
+    };
+    if !get(0) {
+        return None;
+    }
+    let mut counts = [0usize; 5];
+    // Walk backwards from the center through the runs 2, 1 and 0
+    let mut t = 0;
+    while get(t) {
+        counts[2] += 1;
+        t -= 1;
+    }
+    while in_bounds(t) && !get(t) && counts[1] <= max_count {
+        counts[1] += 1;
+        t -= 1;
+    }
+    while get(t) && counts[0] <= max_count {
+        counts[0] += 1;
+        t -= 1;
+    }
+    // Walk forwards through the runs 2, 3 and 4
+    t = 1;
+    while get(t) {
+        counts[2] += 1;
+        t += 1;
+    }
+    while in_bounds(t) && !get(t) && counts[3] <= max_count {
+        counts[3] += 1;
+        t += 1;
+    }
+    while get(t) && counts[4] <= max_count {
+        counts[4] += 1;
+        t += 1;
+    }
+    if counts[0] > max_count || counts[1] > max_count || counts[3] > max_count || counts[4] > max_count {
+        return None;
+    }
+    let total: usize = counts.iter().sum();
+    // Must be within 40% of the length found by the row scan
+    if 5 * total.abs_diff(original_total) >= 2 * original_total || !is_finder_ratio(&counts) {
+        return None;
+    }
+    let start = if dir.0 == 0 {y} else {x};
+    return Some((start as f64 + t as f64 - counts[4] as f64 - counts[3] as f64 - counts[2] as f64 / 2.0, total));
+}
+
+/// Returns (top left, top right, bottom left) index triples of finders that could belong to one code
+/// Triples closest to a right isosceles triangle come first
+fn finder_triples(finders: &[FinderPattern]) -> Vec<(usize,usize,usize)> {
+    let mut out: Vec<(f64,(usize,usize,usize))> = Vec::new();
+    let dist = |a: &FinderPattern, b: &FinderPattern| (a.x - b.x).hypot(a.y - b.y);
+    for i in 0..finders.len() {
+        for j in i + 1..finders.len() {
+            for k in j + 1..finders.len() {
+                let [a, b, c] = [&finders[i], &finders[j], &finders[k]];
+                let sizes = [a.module_size, b.module_size, c.module_size];
+                let min_size = sizes.iter().cloned().fold(f64::MAX, f64::min);
+                let max_size = sizes.iter().cloned().fold(0.0, f64::max);
+                if max_size > min_size * 1.5 {
+                    continue;
+                }
+                // The top left finder is opposite the longest side
+                let (bc, ac, ab) = (dist(b,c), dist(a,c), dist(a,b));
+                let (tl, p, q) = if bc >= ac && bc >= ab {(i, j, k)} else if ac >= ab {(j, i, k)} else {(k, i, j)};
+                let (o, p_f, q_f) = (&finders[tl], &finders[p], &finders[q]);
+                let (vx, vy) = (p_f.x - o.x, p_f.y - o.y);
+                let (wx, wy) = (q_f.x - o.x, q_f.y - o.y);
+                let (leg1, leg2) = (vx.hypot(vy), wx.hypot(wy));
+                let cos = (vx * wx + vy * wy) / (leg1 * leg2);
+                let module_size = (sizes[0] + sizes[1] + sizes[2]) / 3.0;
+                if cos.abs() > 0.4 || leg1.max(leg2) > leg1.min(leg2) * 1.6 || leg1.min(leg2) < 10.0 * module_size {
+                    continue;
+                }
+                // Top right is clockwise from bottom left, since y points down
+                let (tr, bl) = if vx * wy - vy * wx > 0.0 {(p, q)} else {(q, p)};
+                let score = (leg1 - leg2).abs() / leg1.max(leg2) + cos.abs();
+                out.push((score, (tl, tr, bl)));
+            }
+        }
+    }
+    out.sort_by(|a, b| a.0.total_cmp(&b.0));
+    return out.into_iter().map(|(_, triple)| triple).collect();
+}
+
+/// Samples and decodes the code with the given finder patterns
+fn read_code(bits: &BitImage, tl: &FinderPattern, tr: &FinderPattern, bl: &FinderPattern) -> Option<ScannedQr> {
+    let (tl, tr, bl, module_size) = (
+        Point { x: tl.x, y: tl.y },
+        Point { x: tr.x, y: tr.y },
+        Point { x: bl.x, y: bl.y },
+        (tl.module_size + tr.module_size + bl.module_size) / 3.0,
+    );
+    // Runs were measured along rows and columns, which are longer than a module when the code is rotated
+    let angle = (tr.y - tl.y).atan2(tr.x - tl.x);
+    let module_size = module_size * angle.cos().abs().max(angle.sin().abs());
+    let legs = ((tr.x - tl.x).hypot(tr.y - tl.y) + (bl.x - tl.x).hypot(bl.y - tl.y)) / 2.0;
+    let estimate = legs / module_size + 7.0;
+    // Try the closest valid sizes first
+    let mut dimensions: Vec<usize> = (1..=40).map(|version| version * 4 + 17).collect();
+    dimensions.sort_by(|a, b| (*a as f64 - estimate).abs().total_cmp(&(*b as f64 - estimate).abs()));
+    for dimension in dimensions.into_iter().take(3) {
+        let d = dimension as f64;
+        let bottom_right = find_alignment(bits,tl,tr,bl,dimension);
+        let transform = PerspectiveTransform::quad_to_quad(
+            [Point { x: 3.5, y: 3.5 }, Point { x: d - 3.5, y: 3.5 }, bottom_right.0, Point { x: 3.5, y: d - 3.5 }],
+            [tl, tr, bottom_right.1, bl],
+        );
+        let mut grid = Grid::new(dimension,dimension);
+        for y in 0..dimension {
+            for x in 0..dimension {
+                grid.set(x,y,bits.sample(transform.apply(Point { x: x as f64 + 0.5, y: y as f64 + 0.5 })));
+            }
+        }
+        if let Ok(decoded) = decode::decode(&grid) {
+            let bounds = [(0.0, 0.0), (d, 0.0), (d, d), (0.0, d)].map(|(x, y)| transform.apply(Point { x, y }));
+            return Some(ScannedQr { decoded, bounds });
+        }
+    }
+    return None;
+}
+
+/// Returns a point near the bottom right corner in module coordinates and its position in the image
+/// This is the center of the bottom right alignment pattern when it can be found
+/// Otherwise the corner is guessed by completing the parallelogram of the finder patterns
+fn find_alignment(bits: &BitImage, tl: Point, tr: Point, bl: Point, dimension: usize) -> (Point,Point) {
+    let d = dimension as f64;
+    // Vectors of one module along each axis, ignoring perspective
+    let ex = Point { x: (tr.x - tl.x) / (d - 7.0), y: (tr.y - tl.y) / (d - 7.0) };
+    let ey = Point { x: (bl.x - tl.x) / (d - 7.0), y: (bl.y - tl.y) / (d - 7.0) };
+    let affine = |u: f64, v: f64| Point {
+        x: tl.x + (u - 3.5) * ex.x + (v - 3.5) * ey.x,
+        y: tl.y + (u - 3.5) * ex.y + (v - 3.5) * ey.y,
+    };
+    let corner = (Point { x: d - 3.5, y: d - 3.5 }, affine(d - 3.5, d - 3.5));
+    if dimension == 21 {
+        return corner;
+    }
+    // Match the 5x5 alignment pattern at each pixel near where it should be
+    // Perspective changes the module size near the corner, so a few sizes are tried
+    let estimate = affine(d - 6.5, d - 6.5);
+    let radius = (7.0 * ex.x.hypot(ex.y)).ceil() as i64;
+    let mut best_score = 0;
+    let mut best: Vec<Point> = Vec::new();
+    for scale in [1.0, 0.85, 1.15, 1.3] {
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let center = Point { x: estimate.x.floor() + dx as f64 + 0.5, y: estimate.y.floor() + dy as f64 + 0.5 };
+                let mut score = 0;
+                for j in -2i32..=2 {
+                    for i in -2i32..=2 {
+                        let point = Point {
+                            x: center.x + scale * (i as f64 * ex.x + j as f64 * ey.x),
+                            y: center.y + scale * (i as f64 * ex.y + j as f64 * ey.y),
+                        };
+                        if bits.sample(point) == (i.abs().max(j.abs()) != 1) {
+                            score += 1;
+                        }
+                    }
+                }
+                if score > best_score {
+                    best_score = score;
+                    best.clear();
+                }
+                if score == best_score {
+                    best.push(center);
+                }
+            }
+        }
+        if best_score == 25 {
+            break;
+        }
+    }
+    if best_score < 23 {
+        return corner;
+    }
+    let n = best.len() as f64;
+    let center = Point { x: best.iter().map(|p| p.x).sum::<f64>() / n, y: best.iter().map(|p| p.y).sum::<f64>() / n };
+    return (Point { x: d - 6.5, y: d - 6.5 }, center);
+}
+
+/// Projective transform between two quadrilaterals
+/// Maps (x, y) to ((a11 x + a21 y + a31) / w, (a12 x + a22 y + a32) / w) with w = a13 x + a23 y + a33
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub(crate) struct PerspectiveTransform {
+    m: [[f64; 3]; 3],
+}
+
+impl PerspectiveTransform {
+    /// Transform that maps each corner of from to the same corner of to
+    pub(crate) fn quad_to_quad(from: [Point; 4], to: [Point; 4]) -> PerspectiveTransform {
+        return PerspectiveTransform::square_to_quad(from).adjoint().times(&PerspectiveTransform::square_to_quad(to));
+    }
+
+    /// Transform that maps the unit square (0,0), (1,0), (1,1), (0,1) to the corners of quad
+    fn square_to_quad(quad: [Point; 4]) -> PerspectiveTransform {
+        let [p0, p1, p2, p3] = quad;
+        let dx3 = p0.x - p1.x + p2.x - p3.x;
+        let dy3 = p0.y - p1.y + p2.y - p3.y;
+        if dx3 == 0.0 && dy3 == 0.0 {
+            // Parallelogram, the transform is affine
+            return PerspectiveTransform { m: [
+                [p1.x - p0.x, p1.y - p0.y, 0.0],
+                [p2.x - p1.x, p2.y - p1.y, 0.0],
+                [p0.x, p0.y, 1.0],
+            ] };
+        }
+        let (dx1, dx2, dy1, dy2) = (p1.x - p2.x, p3.x - p2.x, p1.y - p2.y, p3.y - p2.y);
+        let denominator = dx1 * dy2 - dx2 * dy1;
+        let a13 = (dx3 * dy2 - dx2 * dy3) / denominator;
+        let a23 = (dx1 * dy3 - dx3 * dy1) / denominator;
+        return PerspectiveTransform { m: [
+            [p1.x - p0.x + a13 * p1.x, p1.y - p0.y + a13 * p1.y, a13],
+            [p3.x - p0.x + a23 * p3.x, p3.y - p0.y + a23 * p3.y, a23],
+            [p0.x, p0.y, 1.0],
+        ] };
+    }
+
+    /// Adjoint matrix, which is the inverse transform up to a scale factor
+    fn adjoint(&self) -> PerspectiveTransform {
+        let m = &self.m;
+        let cofactor = |r1: usize, r2: usize, c1: usize, c2: usize| m[r1][c1] * m[r2][c2] - m[r1][c2] * m[r2][c1];
+        return PerspectiveTransform { m: [
+            [cofactor(1, 2, 1, 2), -cofactor(0, 2, 1, 2), cofactor(0, 1, 1, 2)],
+            [-cofactor(1, 2, 0, 2), cofactor(0, 2, 0, 2), -cofactor(0, 1, 0, 2)],
+            [cofactor(1, 2, 0, 1), -cofactor(0, 2, 0, 1), cofactor(0, 1, 0, 1)],
+        ] };
+    }
+
+    /// Transform that applies self, then other
+    fn times(&self, other: &PerspectiveTransform) -> PerspectiveTransform {
+        let mut m = [[0.0; 3]; 3];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, el) in row.iter_mut().enumerate() {
+                *el = (0..3).map(|k| self.m[i][k] * other.m[k][j]).sum();
+            }
+        }
+        return PerspectiveTransform { m };
+    }
+
+    /// Maps a point through the transform
+    pub(crate) fn apply(&self, point: Point) -> Point {
+        let m = &self.m;
+        let w = m[0][2] * point.x + m[1][2] * point.y + m[2][2];
+        return Point {
+            x: (m[0][0] * point.x + m[1][0] * point.y + m[2][0]) / w,
+            y: (m[0][1] * point.x + m[1][1] * point.y + m[2][1]) / w,
+        };
+    }
+}
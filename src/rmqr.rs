@@ -0,0 +1,226 @@
+use crate::bits;
+use crate::error::QrError;
+use crate::error_correction;
+use crate::error_correction::ECLevel;
+use crate::grid::Grid;
+use crate::qr::{Encoding, SymbolKind, QR};
+use crate::segment::Segment;
+
+/// Stores (height, width) of each rectangular micro qr code, indexed by version - 1
+/// Versions are ordered by height then width, from R7x43 to R17x139
+pub(crate) const RMQR_SIZES: [(usize,usize);32] = [
+    (7, 43), (7, 59), (7, 77), (7, 99), (7, 139),
+    (9, 43), (9, 59), (9, 77), (9, 99), (9, 139),
+    (11, 27), (11, 43), (11, 59), (11, 77), (11, 99), (11, 139),
+    (13, 27), (13, 43), (13, 59), (13, 77), (13, 99), (13, 139),
+    (15, 43), (15, 59), (15, 77), (15, 99), (15, 139),
+    (17, 43), (17, 59), (17, 77), (17, 99), (17, 139),
+];
+
+/// Stores tuple of (number of data codewords, EC codewords per block, number of blocks)
+/// Accessed by RMQR_BLOCKS_TABLE[ version - 1 ][ ECLevel ], where only M and H exist
+pub(crate) const RMQR_BLOCKS_TABLE: [[(usize,usize,usize);2];32] = [
+  [(6, 7, 1),    (3, 10, 1)],
+  [(12, 9, 1),   (7, 14, 1)],
+  [(20, 12, 1),  (10, 22, 1)],
+  [(28, 16, 1),  (14, 30, 1)],
+  [(44, 24, 1),  (24, 22, 2)],
+  [(12, 9, 1),   (7, 14, 1)],
+  [(21, 12, 1),  (11, 22, 1)],
+  [(31, 18, 1),  (17, 16, 2)],
+  [(42, 24, 1),  (22, 22, 2)],
+  [(63, 18, 2),  (33, 22, 3)],
+  [(7, 8, 1),    (5, 10, 1)],
+  [(19, 12, 1),  (11, 20, 1)],
+  [(31, 16, 1),  (15, 16, 2)],
+  [(43, 24, 1),  (23, 22, 2)],
+  [(57, 16, 2),  (29, 30, 2)],
+  [(84, 24, 2),  (42, 30, 3)],
+  [(12, 9, 1),   (7, 14, 1)],
+  [(27, 14, 1),  (13, 28, 1)],
+  [(38, 22, 1),  (20, 20, 2)],
+  [(53, 16, 2),  (29, 28, 2)],
+  [(73, 20, 2),  (35, 26, 3)],
+  [(106, 20, 3), (54, 28, 4)],
+  [(33, 18, 1),  (15, 18, 2)],
+  [(48, 26, 1),  (26, 24, 2)],
+  [(67, 18, 2),  (31, 24, 3)],
+  [(88, 24, 2),  (48, 22, 4)],
+  [(127, 24, 3), (69, 26, 5)],
+  [(39, 22, 1),  (21, 20, 2)],
+  [(56, 16, 2),  (28, 30, 2)],
+  [(78, 22, 2),  (38, 28, 3)],
+  [(100, 20, 3), (56, 26, 4)],
+  [(152, 20, 4), (76, 26, 6)],
+];
+
+/// Number of bits of the length field for numeric, alphanumeric, byte and kanji segments, indexed by version - 1
+const RMQR_LENGTH_BITS: [[u32;4];32] = [
+    [4, 3, 3, 2], [5, 5, 4, 3], [6, 5, 5, 4], [7, 6, 5, 5], [7, 6, 6, 5],
+    [5, 5, 4, 3], [6, 5, 5, 4], [7, 6, 5, 5], [7, 6, 6, 5], [8, 7, 6, 6],
+    [4, 4, 3, 2], [6, 5, 5, 4], [7, 6, 5, 5], [7, 6, 6, 5], [8, 7, 6, 6], [8, 7, 7, 6],
+    [5, 5, 4, 3], [6, 6, 5, 5], [7, 6, 6, 5], [7, 7, 6, 6], [8, 7, 7, 6], [8, 8, 7, 7],
+    [7, 6, 6, 5], [7, 7, 6, 5], [8, 7, 7, 6], [8, 7, 7, 6], [9, 8, 7, 7],
+    [7, 6, 6, 5], [8, 7, 6, 6], [8, 7, 7, 6], [8, 8, 7, 6], [9, 8, 8, 7],
+];
+
+/// Number of bits of the mode indicator and of the terminator
+const RMQR_MODE_BITS: u32 = 3;
+
+/// Get element in the rectangular micro qr code block layout table
+/// Returns (number of data codewords, EC codewords per block, number blocks), or None for levels other than M and H
+pub fn rmqr_blocks_table_get(version: u8, ec_level: ECLevel) -> Option<(usize,usize,usize)> {
+    let idx = match ec_level {ECLevel::M => 0, ECLevel::H => 1, _ => return None};
+    return Some(RMQR_BLOCKS_TABLE.get((version as usize).wrapping_sub(1))?[idx]);
+}
+
+/// Returns the version of the rectangular micro qr code that is height modules tall and width modules wide
+pub fn rmqr_version(height: usize, width: usize) -> Option<u8> {
+    return RMQR_SIZES.iter().position(|size| *size == (height, width)).map(|idx| idx as u8 + 1);
+}
+
+/// Returns the x coordinates of the centers of the alignment patterns in a code of width
+pub(crate) fn rmqr_alignment_columns(width: usize) -> &'static [usize] {
+    return match width {
+        43 => &[21],
+        59 => &[19, 39],
+        77 => &[25, 51],
+        99 => &[23, 49, 75],
+        139 => &[27, 55, 83, 111],
+        _ => &[],
+    };
+}
+
+/// Returns (mode indicator, number of length bits) of a segment in a rectangular micro qr code
+fn rmqr_header(version: u8, enc: Encoding) -> (u32,u32) {
+    let length_bits = RMQR_LENGTH_BITS[version as usize - 1];
+    return match enc {
+        Encoding::Numeric => (1, length_bits[0]),
+        Encoding::Alphanumeric => (2, length_bits[1]),
+        Encoding::Byte | Encoding::ECI => (3, length_bits[2]),
+        Encoding::Kanji => (4, length_bits[3]),
+    };
+}
+
+/// Returns the number of bits segments take in a rectangular micro qr code, including the headers
+fn rmqr_num_bits(segments: &[Segment], version: u8) -> usize {
+    return segments.iter().map(|seg| {
+        let eci_header = if seg.enc == Encoding::ECI {(RMQR_MODE_BITS + 8) as usize} else {0};
+        eci_header + (RMQR_MODE_BITS + rmqr_header(version,seg.enc).1) as usize + seg.data_bits()
+    }).sum();
+}
+
+/// Given list of segments, returns the message of a rectangular micro qr code as data codewords
+fn get_rmqr_codewords(segments: &[Segment], version: u8, num_codewords: usize) -> Result<Vec<u8>,QrError> {
+    let mut message: Vec<u8> = Vec::with_capacity(num_codewords * 8);
+    // Number of characters in the segments before the current one
+    let mut position = 0;
+    for segment in segments.iter() {
+        let (indicator, length_bits) = rmqr_header(version,segment.enc);
+        let bits = segment.encode().map_err(|ch| QrError::InvalidCharacter {
+            ch,
+            position: position + segment.data.chars().position(|x| x == ch).unwrap_or(0),
+            mode: segment.enc,
+        })?;
+        position += segment.data.chars().count();
+        if segment.enc == Encoding::ECI {
+            // ECI header followed by a byte mode segment holding the UTF-8 bytes
+            bits::push_to_bit_list(&mut message,7,RMQR_MODE_BITS);
+            bits::push_to_bit_list(&mut message,bits::ECI_UTF8,8);
+        }
+        bits::push_to_bit_list(&mut message,indicator,RMQR_MODE_BITS);
+        bits::push_to_bit_list(&mut message,segment.num_chars() as u32,length_bits);
+        message.extend(bits);
+    }
+    if message.len() > num_codewords * 8 {
+        return Err(QrError::DataTooLong { needed_bits: message.len(), capacity: num_codewords * 8 });
+    }
+    // Terminator of up to three zeros, then zeros up to the end of the codeword
+    let terminator_len = std::cmp::min(RMQR_MODE_BITS as usize, num_codewords * 8 - message.len());
+    message.resize(message.len() + terminator_len, 0);
+    message.resize(message.len().div_ceil(8) * 8, 0);
+
+    let mut codewords: Vec<u8> = message.chunks(8).map(|x| bits::collect_bits(x) as u8).collect();
+    // Fill remaining space with alternating pad codewords
+    let data_len = codewords.len();
+    while codewords.len() < num_codewords {
+        codewords.push(if (codewords.len() - data_len).is_multiple_of(2) {236} else {17});
+    }
+    return Ok(codewords);
+}
+
+impl QR {
+    /// Create a rectangular micro qr code from str using encoding
+    pub fn new_rmqr(str: &str, enc: Encoding, ec_level: ECLevel) -> Result<QR,QrError> {
+        return QR::rmqr_from_segments(&[Segment::new(str,enc)],ec_level);
+    }
+
+    /// Create a rectangular micro qr code from a list of segments written one after another
+    pub fn rmqr_from_segments(segments: &[Segment], ec_level: ECLevel) -> Result<QR,QrError> {
+        let version = QR::get_min_rmqr_version(segments,ec_level)?;
+        return QR::create_rmqr(segments,version,ec_level);
+    }
+
+    /// Returns the rectangular micro qr version with the smallest area that can store a list of segments
+    pub fn get_min_rmqr_version(segments: &[Segment], ec_level: ECLevel) -> Result<u8,QrError> {
+        return QR::get_min_rmqr_version_in_range(segments,ec_level,1,32);
+    }
+
+    /// Returns the version between min_version and max_version with the smallest area that can store a list of segments
+    /// Versions 1 to 5 are all 7 modules tall, so a range can limit the height of the code
+    /// Returns DataTooLong with the capacity of the largest version in the range if none fit
+    pub fn get_min_rmqr_version_in_range(segments: &[Segment], ec_level: ECLevel, min_version: u8, max_version: u8) -> Result<u8,QrError> {
+        if !(1..=32).contains(&min_version) {
+            return Err(QrError::VersionOutOfRange(min_version));
+        }
+        if !(min_version..=32).contains(&max_version) {
+            return Err(QrError::VersionOutOfRange(max_version));
+        }
+        if rmqr_blocks_table_get(min_version,ec_level).is_none() {
+            return Err(QrError::UnsupportedEcLevel(ec_level));
+        }
+        let area = |version: u8| {
+            let (height, width) = QR::get_rmqr_version_size(version);
+            return height * width;
+        };
+        let fits = |version: u8| rmqr_num_bits(segments,version) <= rmqr_blocks_table_get(version,ec_level).unwrap().0 * 8;
+        if let Some(version) = (min_version..=max_version).filter(|version| fits(*version)).min_by_key(|version| area(*version)) {
+            return Ok(version);
+        }
+        let largest = (min_version..=max_version).max_by_key(|version| rmqr_blocks_table_get(*version,ec_level).unwrap().0).unwrap();
+        return Err(QrError::DataTooLong {
+            needed_bits: rmqr_num_bits(segments,largest),
+            capacity: rmqr_blocks_table_get(largest,ec_level).unwrap().0 * 8,
+        });
+    }
+
+    /// Create a rectangular micro qr code of a fixed version from a list of segments
+    /// Rectangular codes only have one mask, so mask_index is always 0
+    pub(crate) fn create_rmqr(segments: &[Segment], version: u8, ec_level: ECLevel) -> Result<QR,QrError> {
+        if !(1..=32).contains(&version) {
+            return Err(QrError::VersionOutOfRange(version));
+        }
+        let blocks = rmqr_blocks_table_get(version,ec_level).ok_or(QrError::UnsupportedEcLevel(ec_level))?;
+        let message = get_rmqr_codewords(segments,version,blocks.0)?;
+        let message_ec = error_correction::ec_encode_blocks(message,blocks);
+
+        let (height, width) = QR::get_rmqr_version_size(version);
+        let mut out = QR {
+            kind: SymbolKind::Rmqr,
+            bitmap: Grid::new(width,height),
+            pattern_mask: Grid::new(width,height),
+            version,
+            ec_level,
+            mask_index: 0,
+        };
+        out.apply_patterns();
+        out.write_message(message_ec)?;
+        out.apply_mask(0);
+        return Ok(out);
+    }
+
+    /// Get (height, width) of a rectangular micro qr code of size version
+    pub fn get_rmqr_version_size(version: u8) -> (usize,usize) {
+        return RMQR_SIZES[version as usize - 1];
+    }
+}
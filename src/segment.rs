@@ -0,0 +1,159 @@
+use crate::bits;
+use crate::metadata;
+use crate::qr::Encoding;
+use crate::qr::QR;
+use crate::error_correction::ECLevel;
+use crate::error::QrError;
+
+/// Part of a message that is written with a single encoding mode
+/// Each segment gets its own mode indicator and length field
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct Segment {
+    pub enc: Encoding,
+    pub data: String,
+}
+
+impl Segment {
+    pub fn new(data: &str, enc: Encoding) -> Segment {
+        Segment { enc, data: data.to_string() }
+    }
+
+    /// Returns the data of the segment encoded as bits, without the mode indicator and length
+    pub(crate) fn encode(&self) -> Result<Vec<u8>, char> {
+        return match self.enc {
+            Encoding::Numeric => bits::encode_numeric(&self.data),
+            Encoding::Alphanumeric => bits::encode_alphanumeric(&self.data),
+            Encoding::Byte => bits::encode_latin(&self.data),
+            Encoding::Kanji => bits::encode_kanji(&self.data),
+            Encoding::ECI => bits::encode_utf8(&self.data),
+        };
+    }
+
+    /// Returns the value written in the length field
+    pub fn num_chars(&self) -> usize {
+        return metadata::char_count(&self.data,self.enc);
+    }
+
+    /// Returns the number of bits the segment takes, including the headers
+    pub fn num_bits(&self, version: u8) -> Result<usize,QrError> {
+        return Ok(header_bits(self.enc,version)? + self.data_bits());
+    }
+
+    /// Returns the number of bits taken by the encoded data, without the headers
+    pub(crate) fn data_bits(&self) -> usize {
+        let num_chars = self.num_chars();
+        return match self.enc {
+            Encoding::Numeric => num_chars / 3 * 10
+                + (num_chars % 3 == 2) as usize * 7
+                + (num_chars % 3 == 1) as usize * 4,
+            Encoding::Alphanumeric => num_chars / 2 * 11
+                + (num_chars % 2 == 1) as usize * 6,
+            Encoding::Byte => num_chars * 8,
+            Encoding::Kanji => num_chars * 13,
+            Encoding::ECI => num_chars * 8,
+        };
+    }
+}
+
+/// Returns the number of bits taken by the mode indicator and the length field
+pub(crate) fn header_bits(enc: Encoding, version: u8) -> Result<usize,QrError> {
+    let eci_header = if let Encoding::ECI = enc {metadata::ECI_HEADER_BITS} else {0};
+    return Ok(eci_header + 4 + metadata::num_length_bits(version,enc)? as usize);
+}
+
+/// Modes that the optimizer chooses between
+/// The byte mode is either Byte or ECI depending on the message
+const NUM_MODES: usize = 4;
+
+/// Splits str into the segments that need the fewest bits
+/// Tries the length field sizes of each version range from smallest to largest
+/// and returns the first split that fits in its own range
+pub fn optimize(str: &str, ec_level: ECLevel) -> Result<Vec<Segment>,QrError> {
+    for max_version in [9, 26] {
+        let segments = optimize_for_version(str,max_version)?;
+        if matches!(QR::get_min_version_segments(&segments,ec_level), Ok((version, _)) if version <= max_version) {
+            return Ok(segments);
+        }
+    }
+    return optimize_for_version(str,40);
+}
+
+/// Splits str into the segments that need the fewest bits using the length field sizes of version
+/// Each character is assigned a mode with dynamic programming over the cost of switching modes
+/// Costs are measured in sixths of a bit so numeric and alphanumeric characters have integer costs
+pub fn optimize_for_version(str: &str, version: u8) -> Result<Vec<Segment>,QrError> {
+    let chars: Vec<char> = str.chars().collect();
+    if chars.is_empty() {
+        return Ok(vec![]);
+    }
+    // Byte segments hold Latin-1 unless some character can only be written as UTF-8
+    let byte_mode = if chars.iter().all(|ch| *ch <= 255 as char || bits::kanji_char_to_sjis(*ch).is_some()) {
+        Encoding::Byte
+    } else {
+        Encoding::ECI
+    };
+    let modes = [Encoding::Numeric, Encoding::Alphanumeric, byte_mode, Encoding::Kanji];
+    let head_costs: Vec<usize> = modes.iter()
+        .map(|enc| header_bits(*enc,version).map(|bits| bits * 6))
+        .collect::<Result<Vec<usize>,QrError>>()?;
+    let char_cost = |mode: usize, ch: char| -> Option<usize> {
+        match mode {
+            0 => ch.is_ascii_digit().then_some(20),
+            1 => bits::alphanumeric_char_to_idx(ch).map(|_| 33),
+            2 => match byte_mode {
+                Encoding::ECI => Some(ch.len_utf8() * 8 * 6),
+                _ => (ch <= 255 as char).then_some(8 * 6),
+            },
+            _ => bits::kanji_char_to_sjis(ch).map(|_| 13 * 6),
+        }
+    };
+
+    // char_modes[i][mode] is the mode of character i when the best encoding up to i ends in mode
+    let mut char_modes: Vec<[Option<usize>; NUM_MODES]> = Vec::with_capacity(chars.len());
+    let mut prev_costs: Vec<usize> = head_costs.clone();
+    for ch in chars.iter() {
+        let mut cur_modes: [Option<usize>; NUM_MODES] = [None; NUM_MODES];
+        let mut cur_costs: Vec<usize> = vec![usize::MAX; NUM_MODES];
+        // Extend the current segment with this character
+        for mode in 0..NUM_MODES {
+            if let Some(cost) = char_cost(mode,*ch) {
+                cur_costs[mode] = prev_costs[mode] + cost;
+                cur_modes[mode] = Some(mode);
+            }
+        }
+        // Or end the segment after this character and start a new one
+        for to_mode in 0..NUM_MODES {
+            for from_mode in 0..NUM_MODES {
+                if cur_modes[from_mode].is_none() {continue;}
+                let cost = cur_costs[from_mode].div_ceil(6) * 6 + head_costs[to_mode];
+                if cur_modes[to_mode].is_none() || cost < cur_costs[to_mode] {
+                    cur_costs[to_mode] = cost;
+                    cur_modes[to_mode] = Some(from_mode);
+                }
+            }
+        }
+        char_modes.push(cur_modes);
+        prev_costs = cur_costs;
+    }
+
+    // Find the cheapest final mode, then walk backwards to recover each character's mode
+    let mut cur_mode = (0..NUM_MODES)
+        .filter(|mode| char_modes[chars.len()-1][*mode] == Some(*mode))
+        .min_by_key(|mode| prev_costs[*mode].div_ceil(6))
+        .unwrap();
+    let mut assigned: Vec<usize> = vec![0; chars.len()];
+    for i in (0..chars.len()).rev() {
+        cur_mode = char_modes[i][cur_mode].unwrap();
+        assigned[i] = cur_mode;
+    }
+
+    // Group runs of characters with the same mode into segments
+    let mut out: Vec<Segment> = vec![];
+    for (i, ch) in chars.iter().enumerate() {
+        if i == 0 || assigned[i] != assigned[i-1] {
+            out.push(Segment { enc: modes[assigned[i]], data: String::new() });
+        }
+        out.last_mut().unwrap().data.push(*ch);
+    }
+    return Ok(out);
+}
@@ -0,0 +1,135 @@
+use crate::bits;
+use crate::decode::DecodedQr;
+use crate::error::QrError;
+use crate::error_correction::ECLevel;
+use crate::gs1::{self, Fnc1};
+use crate::metadata;
+use crate::qr::Encoding;
+use crate::segment::{self, Segment};
+
+/// Maximum number of symbols in a structured append sequence
+pub const MAX_SYMBOLS: usize = 16;
+
+/// Number of bits taken by the structured append header at the start of each symbol
+pub const HEADER_BITS: usize = 4 + 4 + 4 + 8;
+
+/// Mode indicator of the structured append header
+const MODE_INDICATOR: u32 = 3;
+
+/// Position of a symbol in a message split across several qr codes
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub struct StructuredAppend {
+    /// Position of this symbol, starting at 0
+    pub index: u8,
+    /// Number of symbols in the sequence, from 2 to 16
+    pub total: u8,
+    /// Parity of the whole message, the same in every symbol
+    pub parity: u8,
+}
+
+impl StructuredAppend {
+    /// Returns the header bits written before the segments of the symbol
+    pub(crate) fn header_bits(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(HEADER_BITS);
+        bits::push_to_bit_list(&mut out,MODE_INDICATOR,4);
+        bits::push_to_bit_list(&mut out,self.index as u32,4);
+        bits::push_to_bit_list(&mut out,self.total as u32 - 1,4);
+        bits::push_to_bit_list(&mut out,self.parity as u32,8);
+        return out;
+    }
+}
+
+/// Returns the xor of every byte of the message, as the segments encode it
+/// Kanji segments give their Shift JIS bytes, ECI segments their UTF-8 bytes and other segments one byte per character
+pub fn parity(segments: &[Segment]) -> u8 {
+    return segments.iter().fold(0, |acc, segment| acc ^ match segment.enc {
+        Encoding::Kanji => segment.data.chars().filter_map(bits::kanji_char_to_sjis).fold(0, |acc, sjis| acc ^ (sjis >> 8) as u8 ^ sjis as u8),
+        Encoding::ECI => segment.data.bytes().fold(0, |acc, byte| acc ^ byte),
+        _ => segment.data.chars().fold(0, |acc, ch| acc ^ ch as u8),
+    });
+}
+
+/// Splits text into the fewest chunks that each fit in one symbol of version after the header
+/// Chunks are written with enc, or split into the segments with the fewest bits if enc is None
+/// With FNC1 the header is longer and separators are written as in gs1::fnc1_segments
+/// Stops once there are more than max_chunks chunks, returns DataTooLong if a single character does not fit
+pub(crate) fn split(text: &str, enc: Option<Encoding>, fnc1: Option<Fnc1>, version: u8, ec_level: ECLevel, max_chunks: usize) -> Result<Vec<Vec<Segment>>,QrError> {
+    if let Some(enc) = enc {
+        Segment::new(text,enc).encode().map_err(|ch| QrError::InvalidCharacter {
+            ch,
+            position: text.chars().position(|x| x == ch).unwrap_or(0),
+            mode: enc,
+        })?;
+    }
+    let capacity = metadata::blocks_table_get(version,ec_level).0 * 8;
+    let header_bits = HEADER_BITS + fnc1.map_or(0, |fnc1| fnc1.header_bits().len());
+    let encode = |chunk: &[char]| -> Result<(Vec<Segment>,usize),QrError> {
+        let chunk: String = chunk.iter().collect();
+        let segments = match (enc, fnc1) {
+            (Some(enc), None) => vec![Segment::new(&chunk,enc)],
+            (Some(enc), Some(_)) => vec![gs1::fnc1_segment(&chunk,enc)],
+            (None, None) => segment::optimize_for_version(&chunk,version)?,
+            (None, Some(_)) => gs1::fnc1_segments_for_version(&chunk,version)?,
+        };
+        let num_bits = segments.iter().map(|seg| seg.num_bits(version)).sum::<Result<usize,QrError>>()?;
+        return Ok((segments, header_bits + num_bits));
+    };
+    let chars: Vec<char> = text.chars().collect();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < chars.len() && chunks.len() <= max_chunks {
+        // Binary search for the longest run of characters that still fits
+        // Numeric characters take the fewest bits at 10 bits for 3
+        let (mut low, mut high) = (0, std::cmp::min(chars.len() - start, capacity * 3 / 10));
+        let mut best = Vec::new();
+        while low < high {
+            let mid = (low + high).div_ceil(2);
+            let (segments, num_bits) = encode(&chars[start..start + mid])?;
+            if num_bits <= capacity {
+                low = mid;
+                best = segments;
+            }
+            else {
+                high = mid - 1;
+            }
+        }
+        if low == 0 {
+            let needed_bits = encode(&chars[start..start + 1])?.1;
+            return Err(QrError::DataTooLong { needed_bits, capacity });
+        }
+        chunks.push(best);
+        start += low;
+    }
+    if chunks.is_empty() {
+        chunks.push(Vec::new());
+    }
+    return Ok(chunks);
+}
+
+/// Joins the text of decoded symbols from one structured append sequence, in any order
+/// Returns MissingSymbol with the first index that was not given, and SequenceMismatch if the symbols
+/// are not part of the same sequence, an index is repeated, or the parity does not match the joined segments
+pub fn reassemble(symbols: &[DecodedQr]) -> Result<String,QrError> {
+    let Some(first) = symbols.first().and_then(|symbol| symbol.structured_append) else {
+        return Err(QrError::SequenceMismatch);
+    };
+    let mut parts: Vec<Option<&DecodedQr>> = vec![None; first.total as usize];
+    for symbol in symbols {
+        match symbol.structured_append {
+            Some(info) if info.total == first.total && info.parity == first.parity && info.index < info.total
+                && parts[info.index as usize].is_none() => {
+                parts[info.index as usize] = Some(symbol);
+            },
+            _ => return Err(QrError::SequenceMismatch),
+        }
+    }
+    if let Some(missing) = parts.iter().position(|part| part.is_none()) {
+        return Err(QrError::MissingSymbol(missing as u8));
+    }
+    let parts: Vec<&DecodedQr> = parts.into_iter().flatten().collect();
+    let segments: Vec<Segment> = parts.iter().flat_map(|symbol| symbol.segments.iter().cloned()).collect();
+    if parity(&segments) != first.parity {
+        return Err(QrError::SequenceMismatch);
+    }
+    return Ok(parts.iter().map(|symbol| symbol.text.as_str()).collect());
+}
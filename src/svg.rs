@@ -0,0 +1,95 @@
+use crate::grid::Grid;
+use crate::qr::QR;
+
+/// Options for rendering a qr code as SVG
+#[derive(Debug,Clone,PartialEq)]
+pub struct SvgOptions {
+    /// Width and height of a module in pixels
+    pub module_size: f64,
+    /// Width of the light border around the code in modules
+    pub quiet_zone: usize,
+    /// Color of the dark modules, any CSS color
+    pub foreground: String,
+    /// Color of the light modules and quiet zone, None for transparent
+    pub background: Option<String>,
+    /// Accessible name written to a <title> element
+    pub title: Option<String>,
+    /// Longer accessible description written to a <desc> element
+    pub description: Option<String>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> SvgOptions {
+        SvgOptions {
+            module_size: 8.0,
+            quiet_zone: 4,
+            foreground: String::from("#000000"),
+            background: Some(String::from("#ffffff")),
+            title: None,
+            description: None,
+        }
+    }
+}
+
+impl QR {
+    /// Render qr code as an SVG document
+    pub fn to_svg(&self, options: &SvgOptions) -> String {
+        return to_svg(&self.bitmap,options);
+    }
+}
+
+/// Render a grid of modules as an SVG document
+/// Dark modules are drawn as a single path, with horizontal runs merged into one rectangle
+pub fn to_svg(bitmap: &Grid, options: &SvgOptions) -> String {
+    let height = bitmap.height() + 2 * options.quiet_zone;
+    let width = bitmap.width() + 2 * options.quiet_zone;
+    let mut out = String::new();
+    out.push_str(&format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" version=\"1.1\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" shape-rendering=\"crispEdges\"",
+        width as f64 * options.module_size, height as f64 * options.module_size, width, height,
+    ));
+    if options.title.is_some() || options.description.is_some() {
+        out.push_str(" role=\"img\"");
+    }
+    out.push_str(">\n");
+    if let Some(title) = &options.title {
+        out.push_str(&format!("<title>{}</title>\n",escape_xml(title)));
+    }
+    if let Some(description) = &options.description {
+        out.push_str(&format!("<desc>{}</desc>\n",escape_xml(description)));
+    }
+    if let Some(background) = &options.background {
+        out.push_str(&format!("<rect width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",width,height,escape_xml(background)));
+    }
+    out.push_str(&format!("<path fill=\"{}\" d=\"{}\"/>\n",escape_xml(&options.foreground),module_path(bitmap,options.quiet_zone)));
+    out.push_str("</svg>\n");
+    return out;
+}
+
+/// Returns path data drawing every dark module, offset by the quiet zone
+/// Each horizontal run of dark modules becomes one rectangle: M x,y h len v1 h -len z
+fn module_path(bitmap: &Grid, quiet_zone: usize) -> String {
+    let mut path = String::new();
+    for y in 0..bitmap.height() {
+        for (start, len) in bitmap.dark_runs(y) {
+            path.push_str(&format!("M{},{}h{}v1h-{}z",start + quiet_zone,y + quiet_zone,len,len));
+        }
+    }
+    return path;
+}
+
+/// Escapes text so it can be placed in XML content or attributes
+fn escape_xml(str: &str) -> String {
+    let mut out = String::with_capacity(str.len());
+    for ch in str.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            _ => out.push(ch),
+        }
+    }
+    return out;
+}
@@ -12,7 +12,7 @@ fn test_numeric() {
     assert_eq!(encode_numeric("10").unwrap(),vec![0,0,0,1,0,1,0]);
     assert_eq!(encode_numeric("512").unwrap(),vec![1,0,0,0,0,0,0,0,0,0]);
     for str in NUMERIC_TESTS {
-        assert_eq!(decode_numeric(encode_numeric(&(*str).to_string()).unwrap()).unwrap(),str);
+        assert_eq!(decode_numeric(encode_numeric(str).unwrap()).unwrap(),str);
     }
 }
 
@@ -24,28 +24,25 @@ fn test_alphanumeric() {
     assert_eq!(encode_alphanumeric("21").unwrap(),vec![0,0,0,0,1,0,1,1,0,1,1]);
     assert_eq!(encode_alphanumeric("1").unwrap(),vec![0,0,0,0,0,1]);
     assert_eq!(encode_alphanumeric("001").unwrap(),vec![0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,1]);
-    for x in 0..45 {
-        assert_eq!(alphanumeric_char_to_idx(char::from_u32(ALPHANUMERIC_CHARS[x] as u32).unwrap()).unwrap(),x as u32);
+    for (x, ch) in ALPHANUMERIC_CHARS.iter().enumerate() {
+        assert_eq!(alphanumeric_char_to_idx(*ch as char).unwrap(),x as u32);
     }
-    for x in 0..45 {
-        for y in 0..45 {
+    for (x, ch_x) in ALPHANUMERIC_CHARS.iter().enumerate() {
+        for (y, ch_y) in ALPHANUMERIC_CHARS.iter().enumerate() {
             // Test all two character strings
-            let str: String = [
-                char::from_u32(ALPHANUMERIC_CHARS[x] as u32).unwrap(),
-                char::from_u32(ALPHANUMERIC_CHARS[y] as u32).unwrap()
-            ].iter().collect();
+            let str: String = [*ch_x as char, *ch_y as char].iter().collect();
             let mut bits: Vec<u8> = vec![];
             push_to_bit_list(&mut bits,(x*45 + y) as u32, 11);
             assert_eq!(encode_alphanumeric(str.as_str()).unwrap(),bits,"{} {}",x,y);
+            assert_eq!(decode_alphanumeric(bits).unwrap(),str);
         }
         
         // Test all one character strings
-        let str: String = [
-            char::from_u32(ALPHANUMERIC_CHARS[x] as u32).unwrap()
-        ].iter().collect();
+        let str: String = [*ch_x as char].iter().collect();
         let mut bits: Vec<u8> = vec![];
         push_to_bit_list(&mut bits,x as u32, 6);
         assert_eq!(encode_alphanumeric(str.as_str()).unwrap(),bits);
+        assert_eq!(decode_alphanumeric(bits).unwrap(),str);
         
     }
 }
@@ -74,6 +71,43 @@ fn test_latin() {
         assert!(encode_latin(Into::<String>::into(ch).as_str()).is_err());
     }
     for str in LATIN_TESTS {
-        assert_eq!(decode_latin(encode_latin(&(*str).to_string()).unwrap()).unwrap(),(*str).to_string());
+        assert_eq!(decode_latin(encode_latin(str).unwrap()).unwrap(),(*str).to_string());
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_utf8() {
+    const UTF8_TESTS: [&str; 6] = ["é","Zoë Ångström","日本語","🎉","naïve café 123","Øresund Brücke — 42 €"];
+    use crate::bits::*;
+    assert_eq!(encode_utf8("é").unwrap(),vec![1,1,0,0,0,0,1,1,1,0,1,0,1,0,0,1]);
+    assert_eq!(encode_utf8("a").unwrap(),encode_latin("a").unwrap());
+    for str in UTF8_TESTS {
+        assert_eq!(encode_utf8(str).unwrap().len(),str.len() * 8);
+        assert_eq!(decode_utf8(encode_utf8(str).unwrap()).unwrap(),str);
+    }
+    // Anything outside of Latin-1 falls back to ECI
+    assert!(matches!(get_encoding("Zoë"),crate::qr::Encoding::Byte));
+    assert!(matches!(get_encoding("🎉 Zoë"),crate::qr::Encoding::ECI));
+}
+
+#[test]
+fn test_kanji() {
+    const KANJI_TESTS: [&str; 5] = ["点茗","日本語","こんにちは","東京都千代田区","ＱＲコード"];
+    use crate::bits::*;
+    // Examples from the QR specification
+    assert_eq!(kanji_char_to_sjis('点'),Some(0x935F));
+    assert_eq!(kanji_char_to_sjis('茗'),Some(0xE4AA));
+    assert_eq!(encode_kanji("点").unwrap(),vec![0,1,1,0,1,1,0,0,1,1,1,1,1]);
+    assert_eq!(encode_kanji("茗").unwrap(),vec![1,1,0,1,0,1,0,1,0,1,0,1,0]);
+    // Single byte and unmapped characters are rejected
+    assert_eq!(kanji_char_to_sjis('A'),None);
+    assert_eq!(kanji_char_to_sjis('ｱ'),None);
+    assert_eq!(encode_kanji("点A"),Err('A'));
+    assert_eq!(encode_kanji("点🎉"),Err('🎉'));
+    for str in KANJI_TESTS {
+        assert_eq!(encode_kanji(str).unwrap().len(),str.chars().count() * 13);
+        assert_eq!(decode_kanji(encode_kanji(str).unwrap()).unwrap(),str);
+        assert!(matches!(get_encoding(str),crate::qr::Encoding::Kanji));
+    }
+    assert!(matches!(get_encoding("日本語 text"),crate::qr::Encoding::ECI));
+}
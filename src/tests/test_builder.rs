@@ -0,0 +1,81 @@
+// Tests the builder.rs module
+
+#[test]
+fn test_builder_defaults() {
+    use crate::builder::QrBuilder;
+    use crate::qr::{QR,Encoding};
+    use crate::error_correction::ECLevel;
+    let built = QrBuilder::new("HELLO WORLD").ec_level(ECLevel::Q).build().unwrap();
+    let qr = QR::new("HELLO WORLD",Encoding::Alphanumeric,ECLevel::Q).unwrap();
+    assert_eq!(built.bitmap,qr.bitmap);
+    assert_eq!(built.mask_index,qr.mask_index);
+    assert_eq!(QrBuilder::new("hello").build().unwrap().ec_level,ECLevel::M);
+}
+
+#[test]
+fn test_builder_version() {
+    use crate::builder::QrBuilder;
+    use crate::qr::QR;
+    use crate::error::QrError;
+    use crate::error_correction::ECLevel;
+    // Every code in a batch can be forced to the same size
+    for str in ["1", "https://example.com", "ASSET-000123"] {
+        let qr = QrBuilder::new(str).version(5).build().unwrap();
+        assert_eq!(qr.version,5);
+        assert_eq!(qr.bitmap.height(),QR::get_version_size(5));
+    }
+    assert_eq!(QrBuilder::new("1").version_range(3,10).build().unwrap().version,3);
+    assert_eq!(QrBuilder::new(&"a".repeat(100)).version_range(3,10).build().unwrap().version,6);
+    assert_eq!(QrBuilder::new(&"a".repeat(100)).ec_level(ECLevel::H).version(3).build().err(),
+        Some(QrError::DataTooLong { needed_bits: 4 + 8 + 800, capacity: 26 * 8 }));
+    assert_eq!(QrBuilder::new("1").version(41).build().err(),Some(QrError::VersionOutOfRange(41)));
+    assert_eq!(QrBuilder::new("1").version_range(5,2).build().err(),Some(QrError::VersionOutOfRange(2)));
+}
+
+#[test]
+fn test_builder_mask() {
+    use crate::builder::QrBuilder;
+    use crate::error::QrError;
+    for mask_index in 0..8 {
+        let qr = QrBuilder::new("MASKED").mask(mask_index).build().unwrap();
+        assert_eq!(qr.mask_index,mask_index);
+    }
+    // Different masks change the data modules but not the finder patterns
+    let mask0 = QrBuilder::new("MASKED").mask(0).build().unwrap();
+    let mask1 = QrBuilder::new("MASKED").mask(1).build().unwrap();
+    assert_ne!(mask0.bitmap,mask1.bitmap);
+    assert_eq!(mask0.bitmap.to_rows()[0][..7],mask1.bitmap.to_rows()[0][..7]);
+    assert_eq!(QrBuilder::new("MASKED").mask(8).build().err(),Some(QrError::MaskOutOfRange(8)));
+}
+
+#[test]
+fn test_builder_encoding() {
+    use crate::builder::QrBuilder;
+    use crate::qr::Encoding;
+    use crate::segment::Segment;
+    use crate::error::QrError;
+    use crate::error_correction::ECLevel;
+    // 41 digits fit in 1-L as numeric but not as bytes
+    let digits = "1".repeat(41);
+    assert_eq!(QrBuilder::new(&digits).ec_level(ECLevel::L).build().unwrap().version,1);
+    assert_eq!(QrBuilder::new(&digits).ec_level(ECLevel::L).encoding(Encoding::Byte).build().unwrap().version,3);
+    assert!(matches!(QrBuilder::new("abc").encoding(Encoding::Numeric).build(),Err(QrError::InvalidCharacter { .. })));
+    let segments = [Segment::new("ABC",Encoding::Alphanumeric),Segment::new("123",Encoding::Numeric)];
+    assert_eq!(QrBuilder::from_segments(&segments).version(2).build().unwrap().version,2);
+}
+
+#[test]
+fn test_builder_boost_ec() {
+    use crate::builder::QrBuilder;
+    use crate::error_correction::ECLevel;
+    // "HELLO WORLD" needs 74 bits, version 1-H holds 72
+    let qr = QrBuilder::new("HELLO WORLD").ec_level(ECLevel::L).boost_ec(true).build().unwrap();
+    assert_eq!((qr.version,qr.ec_level),(1,ECLevel::Q));
+    let qr = QrBuilder::new("HELLO").ec_level(ECLevel::L).boost_ec(true).build().unwrap();
+    assert_eq!((qr.version,qr.ec_level),(1,ECLevel::H));
+    // Boosting never raises the version
+    let qr = QrBuilder::new(&"a".repeat(100)).ec_level(ECLevel::L).version(10).boost_ec(true).build().unwrap();
+    assert_eq!((qr.version,qr.ec_level),(10,ECLevel::H));
+    let qr = QrBuilder::new("HELLO WORLD").ec_level(ECLevel::L).build().unwrap();
+    assert_eq!(qr.ec_level,ECLevel::L);
+}
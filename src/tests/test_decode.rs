@@ -0,0 +1,121 @@
+// Tests the decode.rs module
+
+#[test]
+fn test_decode_round_trip() {
+    use crate::builder::QrBuilder;
+    use crate::decode::decode;
+    use crate::error_correction::ECLevel;
+    use crate::segment;
+    const TESTS: [&str; 5] = ["HELLO WORLD", "0123456789012", "https://example.com/?q=Zoë", "日本語のテキスト 123 ABC", "Øresund 🎉"];
+    for ec_level in [ECLevel::L, ECLevel::M, ECLevel::Q, ECLevel::H] {
+        for (i, str) in TESTS.iter().enumerate() {
+            for version in [5, 7, 10, 27, 40] {
+                let segments = segment::optimize_for_version(str,version).unwrap();
+                let qr = QrBuilder::from_segments(&segments).ec_level(ec_level).version(version).mask(i as u8).build().unwrap();
+                let decoded = decode(&qr.bitmap).unwrap();
+                assert_eq!(decoded.text,*str);
+                assert_eq!(decoded.segments,segments);
+                assert_eq!((decoded.version, decoded.ec_level, decoded.mask_index),(version, ec_level, i as u8));
+                assert_eq!(decoded.corrected_errors,0);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_decode_masks() {
+    use crate::builder::QrBuilder;
+    for mask_index in 0..8 {
+        let qr = QrBuilder::new("MASK TEST 42").mask(mask_index).build().unwrap();
+        let decoded = qr.decode().unwrap();
+        assert_eq!(decoded.mask_index,mask_index);
+        assert_eq!(decoded.text,"MASK TEST 42");
+    }
+}
+
+#[test]
+fn test_decode_format_correction() {
+    use crate::qr::{QR,Encoding};
+    use crate::error::QrError;
+    use crate::error_correction::ECLevel;
+    let qr = QR::new("HELLO WORLD",Encoding::Alphanumeric,ECLevel::Q).unwrap();
+    let mut bitmap = qr.bitmap.clone();
+    // Three wrong bits in the first copy of the format information
+    for x in [0, 2, 4] {
+        bitmap.toggle(x,8);
+    }
+    let decoded = crate::decode::decode(&bitmap).unwrap();
+    assert_eq!((decoded.ec_level, decoded.mask_index),(ECLevel::Q, qr.mask_index));
+    // The second copy is intact
+    for x in [1, 3, 5, 7] {
+        bitmap.toggle(x,8);
+    }
+    assert_eq!(crate::decode::decode(&bitmap).unwrap().text,"HELLO WORLD");
+    // Both copies damaged
+    for y in [20, 19, 18, 17] {
+        bitmap.toggle(8,y);
+    }
+    assert_eq!(crate::decode::decode(&bitmap).err(),Some(QrError::FormatUnreadable));
+}
+
+#[test]
+fn test_decode_version_info() {
+    use crate::builder::QrBuilder;
+    use crate::error::QrError;
+    let qr = QrBuilder::new("version information").version(12).build().unwrap();
+    let width = qr.bitmap.height();
+    let mut bitmap = qr.bitmap.clone();
+    // Damage the top right copy beyond repair
+    for y in 0..3 {
+        for x in width-11..width-8 {
+            bitmap.toggle(x,y);
+        }
+    }
+    assert_eq!(crate::decode::decode(&bitmap).unwrap().version,12);
+    // Version information of a different size
+    let other = QrBuilder::new("version information").version(13).build().unwrap();
+    for i in 0..18 {
+        let [(y, x), (y2, x2)] = crate::patterns::version_positions(i,width + 4);
+        bitmap.set(x - 4,y,other.bitmap.get(x,y));
+        bitmap.set(x2,y2 - 4,other.bitmap.get(x2,y2));
+    }
+    assert_eq!(crate::decode::decode(&bitmap).err(),Some(QrError::VersionUnreadable));
+}
+
+#[test]
+fn test_decode_corrects_codewords() {
+    use crate::builder::QrBuilder;
+    use crate::error::QrError;
+    use crate::error_correction::ECLevel;
+    // Version 5-Q has 4 blocks with 18 error correction codewords each, which fixes 9 per block
+    let str = "Reed-Solomon codes can fix damaged codewords";
+    let qr = QrBuilder::new(str).ec_level(ECLevel::Q).version(5).build().unwrap();
+    let positions = qr.data_positions();
+    let flip_codewords = |count: usize| {
+        let mut bitmap = qr.bitmap.clone();
+        // Codewords are interleaved, so consecutive codewords are in different blocks
+        for codeword in 0..count {
+            for (y, x) in positions[codeword * 8..codeword * 8 + 8].iter() {
+                bitmap.toggle(*x,*y);
+            }
+        }
+        return bitmap;
+    };
+    for count in [1, 4, 20, 36] {
+        let decoded = crate::decode::decode(&flip_codewords(count)).unwrap();
+        assert_eq!(decoded.text,str);
+        assert_eq!(decoded.corrected_errors,count);
+    }
+    assert!(matches!(crate::decode::decode(&flip_codewords(40)).err(),Some(QrError::TooManyErrors { .. })));
+}
+
+#[test]
+fn test_decode_invalid() {
+    use crate::error::QrError;
+    use crate::decode::decode;
+    use crate::grid::Grid;
+    assert_eq!(decode(&Grid::new(20,20)).err(),Some(QrError::InvalidSize(20)));
+    assert_eq!(decode(&Grid::new(22,22)).err(),Some(QrError::InvalidSize(22)));
+    assert_eq!(decode(&Grid::new(21,25)).err(),Some(QrError::InvalidSize(25)));
+    assert_eq!(decode(&Grid::new(0,0)).err(),Some(QrError::InvalidSize(0)));
+}
@@ -22,13 +22,8 @@ fn test_generator_poly() {
 fn test_ec() {
     use crate::error_correction as ec;
     let str = String::from("https://www.qrcode.com/");
-    let bitstream = crate::bits::encode_latin(&str).unwrap();
-    let mut message = crate::metadata::get_codewords(&bitstream,str.chars().count(),crate::qr::Encoding::Byte,6,28);
-    // Swap trailing byte types because different tutorials conflict on this
-    for i in 0..message.len() {
-        if message[i] == 17 {message[i] = 236;}
-        else if message[i] == 236 {message[i] = 17;}
-    }
+    let segment = crate::segment::Segment::new(&str,crate::qr::Encoding::Byte);
+    let message = crate::metadata::get_codewords(&[segment],6,28,&[]).unwrap();
     // Example provided in https://dev.to/maxart2501/let-s-develop-a-qr-code-generator-part-iii-error-correction-1kbm
     assert_eq!(ec::ec_group(&message,16), vec![52, 61, 242, 187, 29, 7, 216, 249, 103, 87, 95, 69, 188, 134, 57, 20]);
 }
@@ -38,15 +33,125 @@ fn test_ec_grouping() {
     use crate::error_correction as ec;
     let str = String::from("['give you up','let you down','run around and desert you'].map(x=>'Never gonna '+x)");
 
-    let bitstream: Vec<u8> = crate::bits::encode_latin(&str).unwrap();
-    let mut message = crate::metadata::get_codewords(&bitstream,str.chars().count(),crate::qr::Encoding::Byte,6, 88);
-
-    // Swap trailing byte types because different tutorials conflict on this
-    for i in 0..message.len() {
-        if message[i] == 17 {message[i] = 236;}
-        else if message[i] == 236 {message[i] = 17;}
-    }
+    let segment = crate::segment::Segment::new(&str,crate::qr::Encoding::Byte);
+    let message = crate::metadata::get_codewords(&[segment],6,88,&[]).unwrap();
     // Example provided in https://dev.to/maxart2501/let-s-develop-a-qr-code-generator-part-ix-structuring-larger-versions-2n5d
     assert_eq!(ec::ec_encode(message,7,ec::ECLevel::Q),
         [69, 2, 118, 6, 117, 34, 53, 114, 226, 22, 210, 6, 178, 194, 114, 230, 230, 118, 118, 118, 194, 66, 214, 246, 118, 198, 119, 6, 23, 230, 151, 87, 39, 70, 2, 230, 102, 66, 86, 87, 135, 18, 82, 7, 226, 54, 131, 2, 7, 150, 6, 87, 211, 114, 150, 247, 23, 39, 226, 183, 247, 82, 38, 66, 116, 130, 82, 6, 247, 7, 230, 144, 7, 70, 86, 150, 87, 236, 87, 247, 230, 247, 102, 17, 66, 82, 87, 236, 63, 55, 231, 201, 50, 250, 102, 104, 200, 194, 61, 125, 26, 180, 168, 254, 126, 223, 192, 39, 134, 237, 34, 82, 65, 63, 187, 55, 69, 173, 106, 47, 177, 234, 241, 7, 117, 63, 145, 100, 48, 84, 90, 98, 96, 80, 78, 65, 107, 121, 18, 27, 111, 79, 88, 60, 5, 26, 172, 186, 138, 158, 22, 131, 26, 176, 42, 140, 155, 124, 136, 125, 103, 124, 40, 135, 187, 15, 127, 157, 35, 125, 76, 150, 227, 245, 86, 196, 251, 62, 86, 16, 253, 37, 71, 64, 189, 243, 248, 199, 7, 15, 1, 181, 202, 64, 199, 23]);
-}
\ No newline at end of file
+}
+
+/// Deterministic pseudo-random numbers for picking corrupted codewords
+#[cfg(test)]
+fn lcg(seed: &mut u64) -> usize {
+    *seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+    return (*seed >> 33) as usize;
+}
+
+/// Returns count distinct positions below len
+#[cfg(test)]
+fn random_positions(seed: &mut u64, count: usize, len: usize) -> Vec<usize> {
+    let mut out: Vec<usize> = Vec::with_capacity(count);
+    while out.len() < count {
+        let pos = lcg(seed) % len;
+        if !out.contains(&pos) {out.push(pos);}
+    }
+    return out;
+}
+
+#[test]
+fn test_rs_correct_errors() {
+    use crate::error_correction as ec;
+    let mut seed = 1;
+    for ec_count in [7, 10, 13, 17, 22, 26, 30] {
+        let data: Vec<u8> = (0..40).map(|_| lcg(&mut seed) as u8).collect();
+        let mut original = data.clone();
+        original.extend(ec::ec_group(&data,ec_count));
+        assert_eq!(ec::rs_correct(&mut original.clone(),ec_count,&[]),Some(0));
+        for num_errors in 1..=ec_count / 2 {
+            for _ in 0..10 {
+                let mut block = original.clone();
+                for pos in random_positions(&mut seed,num_errors,block.len()) {
+                    block[pos] ^= (lcg(&mut seed) % 255 + 1) as u8;
+                }
+                assert_eq!(ec::rs_correct(&mut block,ec_count,&[]),Some(num_errors),"{} errors with {} ec codewords",num_errors,ec_count);
+                assert_eq!(block,original);
+            }
+        }
+        // One error too many is either rejected or corrected to a different codeword
+        for _ in 0..10 {
+            let mut block = original.clone();
+            for pos in random_positions(&mut seed,ec_count / 2 + 1,block.len()) {
+                block[pos] ^= (lcg(&mut seed) % 255 + 1) as u8;
+            }
+            assert!(ec::rs_correct(&mut block,ec_count,&[]).is_none() || block != original);
+        }
+    }
+}
+
+#[test]
+fn test_rs_correct_erasures() {
+    use crate::error_correction as ec;
+    let mut seed = 2;
+    for ec_count in [7, 10, 18, 30] {
+        let data: Vec<u8> = (0..60).map(|_| lcg(&mut seed) as u8).collect();
+        let mut original = data.clone();
+        original.extend(ec::ec_group(&data,ec_count));
+        // Any mix where 2 * errors + erasures fits in the error correction codewords
+        for num_erasures in 0..=ec_count {
+            let num_errors = (ec_count - num_erasures) / 2;
+            let mut block = original.clone();
+            let positions = random_positions(&mut seed,num_erasures + num_errors,block.len());
+            for pos in positions.iter() {
+                block[*pos] ^= (lcg(&mut seed) % 255 + 1) as u8;
+            }
+            assert_eq!(ec::rs_correct(&mut block,ec_count,&positions[..num_erasures]),Some(num_erasures + num_errors),
+                "{} erasures and {} errors with {} ec codewords",num_erasures,num_errors,ec_count);
+            assert_eq!(block,original);
+        }
+        // Erased codewords that happen to be right are not counted
+        let mut block = original.clone();
+        block[3] ^= 1;
+        assert_eq!(ec::rs_correct(&mut block,ec_count,&[3, 4, 5]),Some(1));
+        assert_eq!(block,original);
+    }
+    let mut block = vec![1, 2, 3];
+    block.extend(ec::ec_group(&[1, 2, 3],4));
+    assert_eq!(ec::rs_correct(&mut block.clone(),4,&[7]),None);
+    assert_eq!(ec::rs_correct(&mut block.clone(),4,&[1, 1]),None);
+    assert_eq!(ec::rs_correct(&mut block.clone(),4,&[0, 1, 2, 3, 4]),None);
+}
+
+#[test]
+fn test_ec_level_tolerance() {
+    use crate::builder::QrBuilder;
+    use crate::error_correction::ECLevel;
+    use crate::metadata::blocks_table_get;
+    // Lowest share of codewords that each level restores over all versions, in percent rounded down
+    // These meet the nominal 7/15/25/30% recovery apart from Q, as version 1-Q only restores 23%
+    for (ec_level, min_percent) in [(ECLevel::L, 9), (ECLevel::M, 17), (ECLevel::Q, 23), (ECLevel::H, 30)] {
+        for version in 1..=40 {
+            let (num_data, ec_per_block, num_blocks) = blocks_table_get(version,ec_level);
+            let total = num_data + ec_per_block * num_blocks;
+            let tolerated = ec_per_block / 2 * num_blocks;
+            assert!(tolerated * 100 >= total * min_percent,"{:?} version {} only fixes {} of {} codewords",ec_level,version,tolerated,total);
+            if ![1, 2, 7, 10, 25, 40].contains(&version) {continue;}
+            // Damage the interleaved codewords so that every block gets as many errors as it can fix
+            let qr = QrBuilder::new("TOLERANCE").ec_level(ec_level).version(version).build().unwrap();
+            let positions = qr.data_positions();
+            let mut bitmap = qr.bitmap.clone();
+            let damage = |bitmap: &mut crate::grid::Grid, codeword: usize| {
+                for (y, x) in positions[codeword * 8..codeword * 8 + 8].iter() {
+                    bitmap.toggle(*x,*y);
+                }
+            };
+            for codeword in 0..tolerated {
+                damage(&mut bitmap,codeword);
+            }
+            let decoded = crate::decode::decode(&bitmap).unwrap();
+            assert_eq!((decoded.text.as_str(), decoded.corrected_errors),("TOLERANCE", tolerated));
+            // One more error in the first block
+            damage(&mut bitmap,tolerated);
+            assert_ne!(crate::decode::decode(&bitmap).ok().map(|x| x.text),Some(String::from("TOLERANCE")));
+        }
+    }
+}
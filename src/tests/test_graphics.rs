@@ -0,0 +1,36 @@
+// Tests the graphics.rs module
+
+#[cfg(test)]
+fn small_qr() -> crate::qr::QR {
+    return crate::builder::QrBuilder::new("HI").mask(0).build().unwrap();
+}
+
+#[test]
+fn test_sixel() {
+    use crate::graphics::sixel;
+    use crate::raster::RasterOptions;
+    use image::{Rgba, RgbaImage};
+    // 5 by 7 image, dark in the top left pixel and the whole bottom row
+    let image = RgbaImage::from_fn(5,7,|x, y| if (x, y) == (0, 0) || y == 6 {Rgba([0, 0, 0, 255])} else {Rgba([255, 255, 255, 255])});
+    assert_eq!(sixel(&image),"\x1bP0;1;0q\"1;1;5;7#0;2;0;0;0#1;2;100;100;100#0@$#1}!4~-#0!5@-\x1b\\");
+    let options = RasterOptions { quiet_zone: 1, foreground: [0, 51, 102, 255], ..Default::default() };
+    insta::assert_snapshot!(small_qr().to_sixel(&options));
+}
+
+#[test]
+fn test_kitty() {
+    use crate::graphics::kitty;
+    use crate::raster::RasterOptions;
+    assert_eq!(kitty(b"Man"),"\x1b_Ga=T,f=100,q=2,m=0;TWFu\x1b\\");
+    assert_eq!(kitty(b"Ma"),"\x1b_Ga=T,f=100,q=2,m=0;TWE=\x1b\\");
+    assert_eq!(kitty(b"M"),"\x1b_Ga=T,f=100,q=2,m=0;TQ==\x1b\\");
+    // 3073 bytes take 4100 characters, which is split after 4096
+    let data: Vec<u8> = (0..3073).map(|i| (i % 251) as u8).collect();
+    insta::assert_snapshot!(kitty(&data));
+
+    // The payload is the PNG of the code
+    let options = RasterOptions { scale: 2, ..Default::default() };
+    let mut png = Vec::new();
+    small_qr().write_image(&mut png,crate::raster::RasterFormat::Png,&options).unwrap();
+    assert_eq!(small_qr().to_kitty(&options).unwrap(),kitty(&png));
+}
@@ -0,0 +1,80 @@
+// Tests the grid.rs module
+
+#[cfg(test)]
+fn random_rows(seed: &mut u64, width: usize, height: usize) -> Vec<Vec<u8>> {
+    return (0..height).map(|_| (0..width).map(|_| {
+        *seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        return (*seed >> 33) as u8 & 1;
+    }).collect()).collect();
+}
+
+#[test]
+fn test_grid_get_set() {
+    use crate::grid::Grid;
+    let mut grid = Grid::new(70,3);
+    assert_eq!((grid.width(), grid.height()),(70, 3));
+    assert_eq!(grid.count_dark(),0);
+    grid.set(0,0,true);
+    grid.set(63,1,true);
+    grid.set(64,1,true);
+    grid.toggle(69,2);
+    assert!(grid.get(0,0) && grid.get(63,1) && grid.get(64,1) && grid.get(69,2));
+    assert!(!grid.get(1,0) && !grid.get(64,0));
+    assert_eq!(grid.row_words(1),[1 << 63, 1]);
+    assert_eq!(grid.row_words(2),[0, 1 << 5]);
+    grid.toggle(0,0);
+    grid.set(63,1,false);
+    assert_eq!(grid.count_dark(),2);
+    assert_eq!(grid.iter().filter(|(_,_,dark)| *dark).map(|(x,y,_)| (x,y)).collect::<Vec<_>>(),[(64,1), (69,2)]);
+}
+
+#[test]
+#[should_panic]
+fn test_grid_out_of_bounds() {
+    let grid = crate::grid::Grid::new(70,3);
+    grid.get(70,0);
+}
+
+#[test]
+fn test_grid_rows() {
+    use crate::grid::Grid;
+    let mut seed = 7;
+    for (width, height) in [(1, 1), (21, 21), (64, 2), (65, 9), (139, 17), (177, 177)] {
+        let rows = random_rows(&mut seed,width,height);
+        let grid = Grid::from_rows(&rows);
+        assert_eq!(grid.to_rows(),rows);
+        assert_eq!(grid.count_dark(),rows.iter().flatten().filter(|el| **el == 1).count());
+        // Bits past the width stay clear
+        for y in 0..height {
+            assert_eq!(grid.row_words(y).last().unwrap() & !grid.last_word_mask(),0);
+        }
+    }
+    assert_eq!(Grid::from_rows(&[]),Grid::new(0,0));
+}
+
+#[test]
+fn test_grid_transpose() {
+    use crate::grid::Grid;
+    let mut seed = 11;
+    for (width, height) in [(1, 5), (21, 21), (43, 7), (70, 130), (139, 17), (177, 177)] {
+        let rows = random_rows(&mut seed,width,height);
+        let columns: Vec<Vec<u8>> = (0..width).map(|x| rows.iter().map(|row| row[x]).collect()).collect();
+        let grid = Grid::from_rows(&rows);
+        assert_eq!(grid.transpose().to_rows(),columns);
+        assert_eq!(grid.transpose().transpose(),grid);
+    }
+}
+
+#[test]
+fn test_grid_dark_runs() {
+    use crate::grid::Grid;
+    let grid = Grid::from_rows(&[vec![1,1,0,1,0,0,1,1,1], vec![0;9]]);
+    assert_eq!(grid.dark_runs(0).collect::<Vec<_>>(),[(0, 2), (3, 1), (6, 3)]);
+    assert_eq!(grid.dark_runs(1).count(),0);
+    // A run across the boundary of two words
+    let mut grid = Grid::new(130,1);
+    for x in 60..129 {
+        grid.set(x,0,true);
+    }
+    assert_eq!(grid.dark_runs(0).collect::<Vec<_>>(),[(60, 69)]);
+}
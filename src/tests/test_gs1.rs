@@ -0,0 +1,95 @@
+// Tests the gs1.rs module
+
+#[test]
+fn test_gs1_check_digit() {
+    use crate::gs1::check_digit;
+    assert_eq!(check_digit("0950600013435"),Some('2'));
+    assert_eq!(check_digit("400638133393"),Some('1'));
+    assert_eq!(check_digit("37610425002123456"),Some('9'));
+    assert_eq!(check_digit("12A"),None);
+}
+
+#[test]
+fn test_gs1_parse() {
+    use crate::gs1::{parse,element_string,parse_element_string,Element,GS};
+    use crate::error::QrError;
+    let elements = parse("(01)09506000134352(17)201225(10)ABC123(21)12345").unwrap();
+    assert_eq!(elements[2],Element { ai: "10".to_string(), value: "ABC123".to_string() });
+    // Only the batch number needs a separator, the GTIN and date have predefined lengths
+    let str = element_string(&elements);
+    assert_eq!(str,format!("01095060001343521720122510ABC123{}2112345",GS));
+    assert_eq!(parse_element_string(&str).unwrap(),elements);
+    assert_eq!(parse("(3103)000750(400)PO-17%").unwrap()[0].ai,"3103");
+    // AI 235 has a variable length even though it starts like the predefined length identifiers
+    let elements = parse("(235)TPX9(21)12345").unwrap();
+    assert_eq!(element_string(&elements),format!("235TPX9{}2112345",GS));
+
+    assert_eq!(parse("(01)09506000134353").err(),Some(QrError::CheckDigitMismatch { ai: "01".to_string(), expected: '2' }));
+    assert_eq!(parse("(01)0950600013435").err(),Some(QrError::InvalidElement("01".to_string())));
+    assert_eq!(parse("(17)2012AB").err(),Some(QrError::InvalidElement("17".to_string())));
+    assert_eq!(parse("(10)ABC~").err(),Some(QrError::InvalidElement("10".to_string())));
+    assert_eq!(parse("(14)123456").err(),Some(QrError::UnknownApplicationIdentifier("14".to_string())));
+    assert_eq!(parse("01)09506000134352").err(),Some(QrError::MalformedElementString));
+    assert_eq!(parse("").err(),Some(QrError::MalformedElementString));
+}
+
+#[test]
+fn test_gs1_round_trip() {
+    use crate::builder::QrBuilder;
+    use crate::gs1::{parse_element_string,Fnc1,GS};
+    use crate::qr::Encoding;
+    let qr = QrBuilder::gs1("(01)09506000134352(17)201225(10)ABC123(21)12345").unwrap().build().unwrap();
+    let decoded = qr.decode().unwrap();
+    assert_eq!(decoded.fnc1,Some(Fnc1::First));
+    // The separator is written as % in an alphanumeric segment
+    assert!(decoded.segments.iter().any(|seg| seg.enc == Encoding::Alphanumeric && seg.data.contains('%')));
+    assert!(decoded.text.contains(GS));
+    assert_eq!(parse_element_string(&decoded.text).unwrap().len(),4);
+
+    // A literal % stays a % and the separator is 0x1D in byte segments
+    let str = format!("10lot%7{}21abc",GS);
+    for enc in [None, Some(Encoding::Byte), Some(Encoding::Alphanumeric)] {
+        let mut builder = QrBuilder::new(&str.to_uppercase()).fnc1(Fnc1::First);
+        if let Some(enc) = enc {
+            builder = builder.encoding(enc);
+        }
+        assert_eq!(builder.build().unwrap().decode().unwrap().text,str.to_uppercase(),"{:?}",enc);
+    }
+    let decoded = QrBuilder::new(&str).fnc1(Fnc1::First).build().unwrap().decode().unwrap();
+    assert_eq!(decoded.text,str);
+    // Every symbol of a structured append sequence starts with the FNC1 mode indicator
+    let long = str.to_uppercase().repeat(8);
+    let symbols = QrBuilder::new(&long).fnc1(Fnc1::First).version(2).build_structured_append().unwrap();
+    let decoded: Vec<_> = symbols.iter().map(|qr| qr.decode().unwrap()).collect();
+    assert!(decoded.len() > 1 && decoded.iter().all(|symbol| symbol.fnc1 == Some(Fnc1::First)));
+    assert_eq!(crate::structured::reassemble(&decoded).unwrap(),long);
+}
+
+#[test]
+fn test_gs1_mode_indicators() {
+    use crate::builder::QrBuilder;
+    use crate::gs1::Fnc1;
+    use crate::qr::SymbolKind;
+    use crate::error::QrError;
+    use crate::error_correction::{self,ECLevel};
+    // Returns the first data codewords of a qr code
+    let first_codewords = |builder: QrBuilder| {
+        let mut qr = builder.ec_level(ECLevel::L).mask(0).build().unwrap();
+        // Masking again undoes the mask
+        qr.mask(0);
+        let (data, _) = error_correction::ec_decode(&qr.read_message(),qr.version,qr.ec_level).unwrap();
+        return data[..2].to_vec();
+    };
+    // 0101 followed by the numeric mode indicator 0001
+    assert_eq!(first_codewords(QrBuilder::new("0112345678901231").fnc1(Fnc1::First))[0],0b0101_0001);
+    // 1001, the application indicator 65 + 100 for the letter A, then the byte mode indicator
+    let codewords = first_codewords(QrBuilder::new("abc").fnc1(Fnc1::Second(b'A' + 100)));
+    assert_eq!(codewords,[0b1001_1010, 0b0101_0100]);
+    let decoded = QrBuilder::new("abc").fnc1(Fnc1::Second(7)).build().unwrap().decode().unwrap();
+    assert_eq!((decoded.fnc1, decoded.text.as_str()),(Some(Fnc1::Second(7)), "abc"));
+    assert_eq!(QrBuilder::new("01").fnc1(Fnc1::First).kind(SymbolKind::Micro).build().err(),Some(QrError::UnsupportedSymbolKind(SymbolKind::Micro)));
+    // The mode indicator counts towards the capacity, 41 digits only fit version 1-L without it
+    let digits = "1".repeat(41);
+    assert_eq!(QrBuilder::new(&digits).ec_level(ECLevel::L).build().unwrap().version,1);
+    assert_eq!(QrBuilder::new(&digits).ec_level(ECLevel::L).fnc1(Fnc1::First).build().unwrap().version,2);
+}
@@ -3,50 +3,218 @@
 #[test]
 fn test_line_penalty() {
     use crate::mask;
-    assert_eq!(mask::line_penalty(&vec![vec![1,1,1,1]]),0);
-    assert_eq!(mask::line_penalty(&vec![vec![1,1,1,1,1]]),3);
-    assert_eq!(mask::line_penalty(&vec![vec![1],vec![1],vec![1],vec![1],vec![1]]),3);
-    assert_eq!(mask::line_penalty(&vec![vec![1],vec![1],vec![0],vec![1],vec![1]]),0);
-    assert_eq!(mask::line_penalty(&vec![vec![1,1,1,1,1,1]]),4);
-    assert_eq!(mask::line_penalty(&vec![vec![1,1,0,1,1,1]]),0);
-    assert_eq!(mask::line_penalty(&vec![vec![0,0,0,0,0,1]]),3);
+    use crate::grid::Grid;
+    assert_eq!(mask::line_penalty(&Grid::from_rows(&[vec![1,1,1,1]])),0);
+    assert_eq!(mask::line_penalty(&Grid::from_rows(&[vec![1,1,1,1,1]])),3);
+    assert_eq!(mask::line_penalty(&Grid::from_rows(&[vec![1],vec![1],vec![1],vec![1],vec![1]])),3);
+    assert_eq!(mask::line_penalty(&Grid::from_rows(&[vec![1],vec![1],vec![0],vec![1],vec![1]])),0);
+    assert_eq!(mask::line_penalty(&Grid::from_rows(&[vec![1,1,1,1,1,1]])),4);
+    assert_eq!(mask::line_penalty(&Grid::from_rows(&[vec![1,1,0,1,1,1]])),0);
+    assert_eq!(mask::line_penalty(&Grid::from_rows(&[vec![0,0,0,0,0,1]])),3);
 }
 
 #[test]
 fn test_square_penalty() {
     use crate::mask;
-    assert_eq!(mask::square_penalty(&vec![vec![1,1],vec![1,1]]),3);
-    assert_eq!(mask::square_penalty(&vec![vec![1,1,1],vec![1,1,1]]),6);
-    assert_eq!(mask::square_penalty(&vec![vec![1,1,1],vec![1,1,1],vec![1,1,1]]),12);
-    assert_eq!(mask::square_penalty(&vec![vec![0,0,0],vec![0,0,0],vec![0,0,1]]),9);
-    assert_eq!(mask::square_penalty(&vec![vec![0,0],vec![0,0]]),3);
+    use crate::grid::Grid;
+    assert_eq!(mask::square_penalty(&Grid::from_rows(&[vec![1,1],vec![1,1]])),3);
+    assert_eq!(mask::square_penalty(&Grid::from_rows(&[vec![1,1,1],vec![1,1,1]])),6);
+    assert_eq!(mask::square_penalty(&Grid::from_rows(&[vec![1,1,1],vec![1,1,1],vec![1,1,1]])),12);
+    assert_eq!(mask::square_penalty(&Grid::from_rows(&[vec![0,0,0],vec![0,0,0],vec![0,0,1]])),9);
+    assert_eq!(mask::square_penalty(&Grid::from_rows(&[vec![0,0],vec![0,0]])),3);
 }
 
 #[test]
 fn test_finder_penalty() {
     use crate::mask;
-    assert_eq!(mask::finder_penalty(&vec![vec![1,0,1,1,1,0,1,0,0,0,0]]),40);
-    assert_eq!(mask::finder_penalty(&vec![vec![1,0,1,1,1,0,1,0,0,0,0],vec![1,0,1,1,1,0,1,0,0,0,0]]),80);
-    assert_eq!(mask::finder_penalty(&vec![vec![0,0,0,0,1,0,1,1,1,0,1],vec![1,0,1,1,1,0,1,0,0,0,0]]),80);
-    assert_eq!(mask::finder_penalty(&vec![vec![1],vec![0],vec![1],vec![1],vec![1],vec![0],vec![1],vec![0],vec![0],vec![0],vec![0]]),40);
-    assert_eq!(mask::finder_penalty(&vec![vec![0],vec![0],vec![0],vec![0],vec![1],vec![0],vec![1],vec![1],vec![1],vec![0],vec![1]]),40);
-    assert_eq!(mask::finder_penalty(&vec![vec![1,0,1,1,1,0,1,0,0,1,0]]),0);
+    use crate::grid::Grid;
+    assert_eq!(mask::finder_penalty(&Grid::from_rows(&[vec![1,0,1,1,1,0,1,0,0,0,0]])),40);
+    assert_eq!(mask::finder_penalty(&Grid::from_rows(&[vec![1,0,1,1,1,0,1,0,0,0,0],vec![1,0,1,1,1,0,1,0,0,0,0]])),80);
+    assert_eq!(mask::finder_penalty(&Grid::from_rows(&[vec![0,0,0,0,1,0,1,1,1,0,1],vec![1,0,1,1,1,0,1,0,0,0,0]])),80);
+    assert_eq!(mask::finder_penalty(&Grid::from_rows(&[vec![1],vec![0],vec![1],vec![1],vec![1],vec![0],vec![1],vec![0],vec![0],vec![0],vec![0]])),40);
+    assert_eq!(mask::finder_penalty(&Grid::from_rows(&[vec![0],vec![0],vec![0],vec![0],vec![1],vec![0],vec![1],vec![1],vec![1],vec![0],vec![1]])),40);
+    assert_eq!(mask::finder_penalty(&Grid::from_rows(&[vec![1,0,1,1,1,0,1,0,0,1,0]])),0);
 }
 
 #[test]
 fn test_same_color_penalty() {
     use crate::mask;
-    assert_eq!(mask::same_color_penalty(&vec![vec![1]]),20);
-    assert_eq!(mask::same_color_penalty(&vec![vec![1,1,1,1]]),20);
-    assert_eq!(mask::same_color_penalty(&vec![vec![0,0,0,0]]),20);
-    assert_eq!(mask::same_color_penalty(&vec![vec![0,0,0,1]]),10);
-    assert_eq!(mask::same_color_penalty(&vec![vec![1,1,0,0]]),0);
+    use crate::grid::Grid;
+    assert_eq!(mask::same_color_penalty(&Grid::from_rows(&[vec![1]])),20);
+    assert_eq!(mask::same_color_penalty(&Grid::from_rows(&[vec![1,1,1,1]])),20);
+    assert_eq!(mask::same_color_penalty(&Grid::from_rows(&[vec![0,0,0,0]])),20);
+    assert_eq!(mask::same_color_penalty(&Grid::from_rows(&[vec![0,0,0,1]])),10);
+    assert_eq!(mask::same_color_penalty(&Grid::from_rows(&[vec![1,1,0,0]])),0);
 }
 
 #[test]
 fn test_sum_penalty() {
     use crate::mask;
-    assert_eq!(mask::sum_penalty(&vec![vec![0,1,0,1,0,1],vec![1,0,1,0,1,0],vec![0,1,0,1,0,1]]),0);
-    assert_eq!(mask::sum_penalty(&vec![vec![0,0,0,0,0,0],vec![0,0,0,0,0,0],vec![0,0,0,0,0,0]]),30 + 20 + 12);
+    use crate::grid::Grid;
+    assert_eq!(mask::sum_penalty(&Grid::from_rows(&[vec![0,1,0,1,0,1],vec![1,0,1,0,1,0],vec![0,1,0,1,0,1]])),0);
+    assert_eq!(mask::sum_penalty(&Grid::from_rows(&[vec![0,0,0,0,0,0],vec![0,0,0,0,0,0],vec![0,0,0,0,0,0]])),30 + 20 + 12);
 
 }
+
+#[test]
+fn test_penalty_wide_grids() {
+    use crate::mask;
+    use crate::grid::Grid;
+    // Scores computed one module at a time, checking runs and patterns that cross words
+    let line = |rows: &[Vec<u8>]| {
+        let mut penalty = 0;
+        for row in rows {
+            let (mut counting, mut count) = (row[0], 0);
+            for cell in row {
+                if *cell != counting {
+                    if count >= 5 {penalty += count - 2;}
+                    counting = *cell;
+                    count = 0;
+                }
+                else {count += 1;}
+            }
+            if count >= 5 {penalty += count - 2;}
+        }
+        return penalty;
+    };
+    let finder = |rows: &[Vec<u8>]| {
+        let patterns = [[1,0,1,1,1,0,1,0,0,0,0], [0,0,0,0,1,0,1,1,1,0,1]];
+        return 40 * rows.iter().map(|row| row.windows(11).filter(|window| patterns.iter().any(|pattern| window == pattern)).count() as i32).sum::<i32>();
+    };
+    let mut seed: u64 = 3;
+    for (width, height) in [(43, 7), (64, 64), (77, 13), (139, 17), (177, 177)] {
+        // Long runs make line and square penalties likely
+        let rows: Vec<Vec<u8>> = (0..height).map(|_| {
+            let mut row = Vec::with_capacity(width);
+            while row.len() < width {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                let len = std::cmp::min((seed >> 33) as usize % 8 + 1,width - row.len());
+                row.extend(std::iter::repeat_n((seed >> 40) as u8 & 1,len));
+            }
+            return row;
+        }).collect();
+        let columns: Vec<Vec<u8>> = (0..width).map(|x| rows.iter().map(|row| row[x]).collect()).collect();
+        let squares = (0..height-1).map(|y| (0..width-1).filter(|x| {
+            let el = rows[y][*x];
+            return rows[y][x+1] == el && rows[y+1][*x] == el && rows[y+1][x+1] == el;
+        }).count() as i32 * 3).sum::<i32>();
+        let grid = Grid::from_rows(&rows);
+        assert_eq!(mask::line_penalty(&grid),line(&rows) + line(&columns));
+        assert_eq!(mask::square_penalty(&grid),squares);
+        assert_eq!(mask::finder_penalty(&grid),finder(&rows) + finder(&columns));
+    }
+}
+
+#[test]
+fn test_apply_masking_matches_full_scoring() {
+    use crate::mask;
+    use crate::builder::QrBuilder;
+    use crate::qr::SymbolKind;
+    // Each mask is applied to the whole code and scored from scratch, as before the scores were kept per line
+    for (str, kind, version) in [("HELLO WORLD", SymbolKind::Qr, 1), ("masks are scored independently", SymbolKind::Qr, 7), ("1234567890", SymbolKind::Qr, 25), ("M4", SymbolKind::Micro, 4)] {
+        let qr = QrBuilder::new(str).kind(kind).version(version).build().unwrap();
+        let num_masks = if kind == SymbolKind::Micro {4} else {8};
+        // Masking with the chosen mask again removes it
+        let mut unmasked = qr.clone();
+        unmasked.mask(qr.mask_function(qr.mask_index));
+        let mut best: Option<(i32, crate::qr::QR)> = None;
+        for mask_index in 0..num_masks {
+            let mut masked = unmasked.clone();
+            masked.apply_mask(mask_index);
+            let penalty = match kind {
+                SymbolKind::Micro => -mask::micro_score(&masked.bitmap),
+                _ => mask::sum_penalty(&masked.bitmap),
+            };
+            let (bitmap, report) = unmasked.evaluate_mask(mask_index,crate::mask::MaskScorer::Legacy);
+            assert_eq!((bitmap, report.total),(masked.bitmap.clone(), penalty));
+            if best.as_ref().is_none_or(|(best_penalty, _)| penalty < *best_penalty) {
+                best = Some((penalty, masked));
+            }
+        }
+        let best = best.unwrap().1;
+        assert_eq!((qr.mask_index, &qr.bitmap),(best.mask_index, &best.bitmap),"{}",str);
+    }
+}
+
+#[test]
+fn test_iso_penalty() {
+    use crate::mask;
+    use crate::grid::Grid;
+    // N1 scores 3 for a run of 5 and 1 more for each module after it
+    assert_eq!(mask::iso_penalty(&Grid::from_rows(&[vec![1,1,1,1,0,0,0,0,1,0]])),0);
+    assert_eq!(mask::iso_penalty(&Grid::from_rows(&[vec![1,1,1,1,1,0,0,0,0,0]])),3 + 3);
+    assert_eq!(mask::iso_penalty(&Grid::from_rows(&[vec![1,1,1,1,1,1,1,0,0,0,0,0,0,0]])),5 + 5);
+    // N2 scores 3 for each 2x2 block of one color
+    assert_eq!(mask::iso_penalty(&Grid::from_rows(&[vec![1,1,0,0],vec![1,1,0,0]])),3 + 3);
+    // N3 counts the area around the symbol as light, and counts a pattern with enough light on both sides twice
+    assert_eq!(mask::iso_penalty(&Grid::from_rows(&[vec![1,0,1,1,1,0,1,0,0,0,0]])),80);
+    // The legacy scorer only looks inside the symbol and counts it once
+    assert_eq!(mask::finder_penalty(&Grid::from_rows(&[vec![1,0,1,1,1,0,1,0,0,0,0]])),40);
+    // N4 scores 10 for each full 5% step away from half dark
+    assert_eq!(mask::iso_penalty(&Grid::from_rows(&[vec![1,0,1,1,1,0,1,0,1,1,0]])),40 + 20);
+    // Patterns of any module size
+    assert_eq!(mask::iso_penalty(&Grid::from_rows(&[vec![1,1,0,0,1,1,1,1,1,1,0,0,1,1]])),4 + 80 + 40);
+}
+
+#[test]
+fn test_iso_mask_corpus() {
+    use crate::builder::QrBuilder;
+    use crate::qr::Encoding;
+    use crate::mask::MaskScorer;
+    use crate::error_correction::ECLevel;
+    let corpus = include_str!("data/iso_masks.txt");
+    let mut masks = Vec::new();
+    for symbol in corpus.split("\n\n").filter(|symbol| !symbol.starts_with('#') && !symbol.trim().is_empty()) {
+        let mut lines = symbol.lines();
+        let fields: Vec<&str> = lines.next().unwrap().split('\t').collect();
+        let ec_level = match fields[1] {"L" => ECLevel::L, "M" => ECLevel::M, "Q" => ECLevel::Q, _ => ECLevel::H};
+        let (version, mask_index): (u8, u8) = (fields[2].parse().unwrap(), fields[3].parse().unwrap());
+        let builder = QrBuilder::new(fields[0]).encoding(Encoding::Byte).ec_level(ec_level).version(version);
+        let qr = builder.clone().mask_scorer(MaskScorer::Iso).build().unwrap();
+        assert_eq!(qr.mask_index,mask_index,"{}",fields[0]);
+        let rows: Vec<String> = qr.bitmap.to_rows().iter().map(|row| {
+            let mut padded = row.clone();
+            padded.resize(row.len().div_ceil(4) * 4,0);
+            return padded.chunks(4).map(|bits| format!("{:x}",bits.iter().fold(0,|acc, bit| acc << 1 | bit))).collect();
+        }).collect();
+        assert_eq!(rows,lines.collect::<Vec<&str>>(),"{}",fields[0]);
+        assert_ne!(builder.build().unwrap().mask_index,mask_index);
+        masks.push(mask_index);
+    }
+    masks.dedup();
+    assert_eq!(masks,[0, 1, 2, 3, 4, 5, 6, 7]);
+}
+
+#[test]
+fn test_mask_report() {
+    use crate::mask::MaskScorer;
+    use crate::builder::QrBuilder;
+    use crate::qr::SymbolKind;
+    for scorer in [MaskScorer::Legacy, MaskScorer::Iso] {
+        let (qr, report) = QrBuilder::new("mask report").mask_scorer(scorer).build_with_mask_report().unwrap();
+        assert_eq!(report.scorer,scorer);
+        assert_eq!(report.masks.len(),8);
+        assert_eq!(report.chosen,qr.mask_index);
+        // The chosen mask has the lowest total, which is the sum of the penalties
+        let lowest = report.masks.iter().min_by_key(|penalty| penalty.total).unwrap();
+        assert_eq!(lowest.mask_index,report.chosen);
+        for (i, penalty) in report.masks.iter().enumerate() {
+            assert_eq!(penalty.mask_index as usize,i);
+            assert_eq!(penalty.line_penalty + penalty.square_penalty + penalty.finder_penalty + penalty.same_color_penalty,penalty.total);
+        }
+        let scores = [crate::mask::sum_penalty, crate::mask::iso_penalty][(scorer == MaskScorer::Iso) as usize];
+        assert_eq!(lowest.total,scores(&qr.bitmap));
+        let dark = qr.bitmap.count_dark() as f64 / (21 * 21) as f64;
+        assert_eq!((report.dark_ratio, lowest.dark_ratio),(dark, dark));
+    }
+    // A given mask is reported as chosen even when it is not the lowest
+    let (_, report) = QrBuilder::new("mask report").mask(5).build_with_mask_report().unwrap();
+    assert_eq!(report.chosen,5);
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["scorer"],"legacy");
+    assert_eq!(json["masks"][5]["finder_penalty"],report.masks[5].finder_penalty);
+    // Micro qr codes are compared by their score alone
+    let (qr, report) = QrBuilder::new("M4").kind(SymbolKind::Micro).build_with_mask_report().unwrap();
+    assert_eq!(report.masks.len(),4);
+    assert_eq!(report.masks[qr.mask_index as usize].total,-crate::mask::micro_score(&qr.bitmap));
+}
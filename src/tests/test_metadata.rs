@@ -23,4 +23,81 @@ fn test_blocks_table() {
     }
     assert_eq!(codewords_sum,122300);
     
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_blocks_table_total_codewords() {
+    use crate::error_correction::ECLevel;
+    use crate::metadata::blocks_table_get;
+    use crate::qr::QR;
+    // Data and EC codewords of every block fill the symbol
+    for version in 1..=40 {
+        for ec_level in [ECLevel::L,ECLevel::M,ECLevel::Q,ECLevel::H] {
+            let (data, ec, blocks) = blocks_table_get(version,ec_level);
+            assert_eq!(data + ec * blocks,QR::get_available_modules(version as usize) / 8,"{} {:?}",version,ec_level);
+        }
+    }
+    // Table 9 lists 20 + 61 blocks for 40-H
+    assert_eq!(blocks_table_get(40,ECLevel::H),(1276,30,81));
+}
+
+#[test]
+fn test_eci_codewords() {
+    use crate::qr::{QR,Encoding};
+    use crate::error_correction::ECLevel;
+    use crate::metadata::{get_codewords,char_count};
+    let str = "é";
+    assert_eq!(char_count(str,Encoding::ECI),2);
+    let segment = crate::segment::Segment::new(str,Encoding::ECI);
+    // 0111 00011010 | 0100 00000010 | 11000011 10101001 | 0000 terminator, then padding
+    assert_eq!(get_codewords(&[segment],1,19,&[]).unwrap()[..8],[0b01110001,0b10100100,0b00000010,0b11000011,0b10101001,0,236,17]);
+    // 12 header bits + 4 mode bits + 8 length bits + 8 bits per byte
+    assert_eq!(QR::get_min_version("🎉",Encoding::ECI,ECLevel::H).unwrap(),(1,9));
+    assert_eq!(QR::get_min_version("🎉🎉",Encoding::ECI,ECLevel::H).unwrap(),(2,16));
+}
+
+#[test]
+fn test_capacity() {
+    use crate::error_correction::ECLevel;
+    use crate::metadata::{capacity,capacity_table,min_version_for,CAPACITY_TABLE_MODES};
+    use crate::qr::{QR,Encoding};
+    use crate::segment::Segment;
+    // Table 7 of ISO/IEC 18004
+    assert_eq!(capacity_table()[0][0],[41, 25, 17, 10]);
+    assert_eq!(capacity_table()[0][3],[17, 10, 7, 4]);
+    assert_eq!(capacity_table()[39][0],[7089, 4296, 2953, 1817]);
+    assert_eq!(capacity_table()[39][3],[3057, 1852, 1273, 784]);
+    assert_eq!(capacity(40,ECLevel::L,Encoding::ECI),2952);
+    let sample = |enc: Encoding, len: usize| -> String {
+        return match enc {
+            Encoding::Numeric => "7".repeat(len),
+            Encoding::Alphanumeric => "Q".repeat(len),
+            Encoding::Kanji => "茗".repeat(len),
+            _ => "b".repeat(len),
+        };
+    };
+    let mut exact_fits = 0;
+    for version in 1..=40 {
+        for (ec_index, ec_level) in [ECLevel::L, ECLevel::M, ECLevel::Q, ECLevel::H].into_iter().enumerate() {
+            for (enc_index, enc) in CAPACITY_TABLE_MODES.into_iter().enumerate() {
+                let chars = capacity(version,ec_level,enc);
+                assert_eq!(capacity_table()[version as usize - 1][ec_index][enc_index],chars);
+                // The capacity fits in the version, and one more character does not
+                let full = Segment::new(&sample(enc,chars),enc);
+                assert!(min_version_for(std::slice::from_ref(&full),ec_level).is_some_and(|min| min <= version),"{} {:?} {:?}",version,ec_level,enc);
+                let over = Segment::new(&sample(enc,chars + 1),enc);
+                assert_eq!(QR::get_min_version_in_range(&[over],ec_level,version,version).ok(),None,"{} {:?} {:?}",version,ec_level,enc);
+                exact_fits += (full.num_bits(version).unwrap() == crate::metadata::blocks_table_get(version,ec_level).0 * 8) as usize;
+            }
+        }
+    }
+    // Messages that fill every data bit leave out the terminator
+    assert!(exact_fits > 0);
+    let exact = [Segment::new(&"7".repeat(41),Encoding::Numeric)];
+    assert_eq!(min_version_for(&exact,ECLevel::L),Some(1));
+    assert_eq!(QR::create(&exact,1,ECLevel::L,None,Default::default()).unwrap().version,1);
+    // ECI payloads are counted in UTF-8 bytes, three for each of these characters
+    let eci = [Segment::new(&"€".repeat(capacity(5,ECLevel::M,Encoding::ECI) / 3),Encoding::ECI)];
+    assert_eq!(min_version_for(&eci,ECLevel::M),Some(5));
+    assert_eq!(min_version_for(&[Segment::new(&"1".repeat(7090),Encoding::Numeric)],ECLevel::L),None);
+}
@@ -0,0 +1,169 @@
+// Tests the micro.rs module
+
+#[test]
+fn test_micro_m1() {
+    use crate::builder::QrBuilder;
+    use crate::qr::SymbolKind;
+    use crate::error_correction::ECLevel;
+    let qr = QrBuilder::new("01234").kind(SymbolKind::Micro).ec_level(ECLevel::L).mask(2).build().unwrap();
+    assert_eq!((qr.version, qr.ec_level),(1, ECLevel::L));
+    // The default error correction level M needs at least M2
+    assert_eq!(QrBuilder::new("01234").kind(SymbolKind::Micro).build().unwrap().version,2);
+    assert_eq!(qr.bitmap.to_rows(),vec![
+        vec![1,1,1,1,1,1,1,0,1,0,1],
+        vec![1,0,0,0,0,0,1,0,1,0,1],
+        vec![1,0,1,1,1,0,1,0,1,1,1],
+        vec![1,0,1,1,1,0,1,0,0,0,0],
+        vec![1,0,1,1,1,0,1,0,1,1,0],
+        vec![1,0,0,0,0,0,1,0,0,0,0],
+        vec![1,1,1,1,1,1,1,0,1,0,1],
+        vec![0,0,0,0,0,0,0,0,0,0,0],
+        vec![1,1,0,0,1,1,1,0,0,1,1],
+        vec![0,1,0,0,0,1,1,1,1,0,0],
+        vec![1,1,0,0,0,0,0,1,0,1,1],
+    ]);
+}
+
+#[test]
+fn test_micro_m3_half_codeword() {
+    use crate::builder::QrBuilder;
+    use crate::qr::{Encoding,SymbolKind};
+    use crate::error_correction::ECLevel;
+    let qr = QrBuilder::new("hello").kind(SymbolKind::Micro).encoding(Encoding::Byte).ec_level(ECLevel::M).mask(3).build().unwrap();
+    assert_eq!(qr.version,3);
+    assert_eq!(qr.bitmap.to_rows(),vec![
+        vec![1,1,1,1,1,1,1,0,1,0,1,0,1,0,1],
+        vec![1,0,0,0,0,0,1,0,1,0,1,1,0,1,1],
+        vec![1,0,1,1,1,0,1,0,1,1,1,0,1,0,1],
+        vec![1,0,1,1,1,0,1,0,1,1,0,1,1,0,0],
+        vec![1,0,1,1,1,0,1,0,0,0,0,1,0,0,1],
+        vec![1,0,0,0,0,0,1,0,0,0,0,0,0,0,1],
+        vec![1,1,1,1,1,1,1,0,0,0,0,1,1,0,0],
+        vec![0,0,0,0,0,0,0,0,0,0,0,1,1,1,0],
+        vec![1,0,0,0,1,0,0,1,1,1,0,0,0,0,0],
+        vec![0,1,0,1,0,1,1,0,1,1,0,0,1,1,1],
+        vec![1,1,1,0,1,0,1,0,1,0,0,0,1,1,0],
+        vec![0,0,1,1,0,0,0,0,0,1,0,0,0,0,1],
+        vec![1,1,1,0,1,0,1,0,1,1,0,0,1,1,1],
+        vec![0,0,0,0,0,0,1,1,0,1,0,1,0,1,0],
+        vec![1,0,1,0,1,1,0,0,1,1,0,1,1,0,1],
+    ]);
+}
+
+#[test]
+fn test_micro_m4() {
+    use crate::qr::{QR,Encoding};
+    use crate::micro;
+    use crate::error_correction::ECLevel;
+    let segments = [crate::segment::Segment::new("Micro QR!",Encoding::Byte)];
+    let qr = QR::create_micro(&segments,4,ECLevel::L,Some(1)).unwrap();
+    assert_eq!(qr.bitmap.to_rows(),vec![
+        vec![1,1,1,1,1,1,1,0,1,0,1,0,1,0,1,0,1],
+        vec![1,0,0,0,0,0,1,0,0,1,1,0,1,0,1,1,1],
+        vec![1,0,1,1,1,0,1,0,1,1,0,1,0,1,1,1,1],
+        vec![1,0,1,1,1,0,1,0,0,0,0,1,0,0,0,1,0],
+        vec![1,0,1,1,1,0,1,0,0,0,0,0,1,1,0,1,0],
+        vec![1,0,0,0,0,0,1,0,0,0,0,0,1,0,1,1,0],
+        vec![1,1,1,1,1,1,1,0,0,0,1,1,0,0,1,1,0],
+        vec![0,0,0,0,0,0,0,0,0,1,1,1,0,1,1,1,0],
+        vec![1,0,0,1,0,0,1,0,0,1,0,0,1,0,0,1,0],
+        vec![0,1,1,1,1,1,0,0,0,1,1,1,1,1,1,1,0],
+        vec![1,0,1,0,0,1,0,1,1,1,0,1,0,0,1,0,0],
+        vec![0,0,1,1,1,0,1,0,0,0,0,1,1,0,0,1,1],
+        vec![1,0,0,1,1,0,0,0,1,0,0,0,1,1,1,1,0],
+        vec![0,1,1,1,1,1,0,1,0,0,0,0,0,0,1,1,0],
+        vec![1,0,0,0,0,0,0,1,0,0,1,1,0,1,0,1,0],
+        vec![0,0,1,0,0,0,0,1,0,1,1,0,0,0,0,1,1],
+        vec![1,1,0,1,0,1,0,0,0,1,0,1,1,0,1,1,0],
+    ]);
+    // Without a fixed mask the best of the four micro masks is used
+    let best = QR::create_micro(&segments,4,ECLevel::L,None).unwrap();
+    assert_eq!(QR::new_micro("Micro QR!",Encoding::Byte,ECLevel::L).unwrap().version,3);
+    for mask_index in 0..4 {
+        let other = QR::create_micro(&segments,4,ECLevel::L,Some(mask_index)).unwrap();
+        assert!(crate::mask::micro_score(&other.bitmap) <= crate::mask::micro_score(&best.bitmap));
+    }
+    assert!(micro::micro_blocks_table_get(4,ECLevel::H).is_none());
+}
+
+#[test]
+fn test_micro_available_modules() {
+    use crate::qr::{QR,SymbolKind};
+    use crate::grid::Grid;
+    use crate::error_correction::ECLevel;
+    use crate::micro::micro_blocks_table_get;
+    for version in 1..=4 {
+        let size = QR::get_micro_version_size(version);
+        let mut qr = QR {
+            kind: SymbolKind::Micro,
+            bitmap: Grid::new(size,size),
+            pattern_mask: Grid::new(size,size),
+            version,
+            ec_level: ECLevel::L,
+            mask_index: 0,
+        };
+        qr.apply_patterns();
+        // Micro qr codes have no remainder bits
+        let (data_bits, ec_codewords) = micro_blocks_table_get(version,ECLevel::L).unwrap();
+        assert_eq!(qr.data_positions().len(),data_bits + ec_codewords * 8,"Version M{}",version);
+    }
+}
+
+#[test]
+fn test_micro_capacity() {
+    use crate::qr::{QR,Encoding};
+    use crate::segment::Segment;
+    use crate::error::QrError;
+    use crate::error_correction::ECLevel;
+    // Largest message of each mode for M1-L, M2-L, M3-L, M4-L and M4-Q
+    let tests = [
+        (Encoding::Numeric, ECLevel::L, [5, 10, 23, 35]),
+        (Encoding::Alphanumeric, ECLevel::L, [0, 6, 14, 21]),
+        (Encoding::Byte, ECLevel::L, [0, 0, 9, 15]),
+        (Encoding::Kanji, ECLevel::L, [0, 0, 6, 9]),
+        (Encoding::Numeric, ECLevel::Q, [0, 0, 0, 21]),
+    ];
+    for (enc, ec_level, lengths) in tests {
+        let ch = if enc == Encoding::Kanji {"点"} else {"1"};
+        for (i, len) in lengths.iter().enumerate().filter(|(_, len)| **len != 0) {
+            let version = i as u8 + 1;
+            let fits = [Segment::new(&ch.repeat(*len),enc)];
+            assert_eq!(QR::get_min_micro_version(&fits,ec_level),Ok(version),"{:?} {}",enc,len);
+            let too_long = [Segment::new(&ch.repeat(len + 1),enc)];
+            assert_ne!(QR::get_min_micro_version(&too_long,ec_level),Ok(version),"{:?} {}",enc,len + 1);
+        }
+    }
+    assert!(matches!(QR::new_micro(&"1".repeat(36),Encoding::Numeric,ECLevel::L).err(),Some(QrError::DataTooLong { .. })));
+}
+
+#[test]
+fn test_micro_errors() {
+    use crate::builder::QrBuilder;
+    use crate::qr::{QR,Encoding,SymbolKind};
+    use crate::error::QrError;
+    use crate::error_correction::ECLevel;
+    assert_eq!(QR::new_micro("1",Encoding::Numeric,ECLevel::H).err(),Some(QrError::UnsupportedEcLevel(ECLevel::H)));
+    assert_eq!(QR::new_micro("ë",Encoding::ECI,ECLevel::L).err(),Some(QrError::UnsupportedMode(Encoding::ECI)));
+    assert_eq!(QR::new_micro("AB",Encoding::Alphanumeric,ECLevel::L).unwrap().version,2);
+    let micro = QrBuilder::new("ABC").kind(SymbolKind::Micro);
+    assert_eq!(micro.clone().ec_level(ECLevel::L).version(1).build().err(),Some(QrError::UnsupportedMode(Encoding::Alphanumeric)));
+    assert_eq!(micro.clone().ec_level(ECLevel::M).version(1).build().err(),Some(QrError::UnsupportedEcLevel(ECLevel::M)));
+    assert_eq!(micro.clone().version(5).build().err(),Some(QrError::VersionOutOfRange(5)));
+    assert_eq!(micro.clone().mask(4).build().err(),Some(QrError::MaskOutOfRange(4)));
+    assert_eq!(micro.clone().boost_ec(true).build().unwrap().ec_level,ECLevel::M);
+    assert_eq!(micro.version_range(3,40).build().unwrap().version,3);
+}
+
+#[test]
+fn test_micro_rendering() {
+    use crate::builder::QrBuilder;
+    use crate::qr::SymbolKind;
+    use crate::svg::SvgOptions;
+    use crate::raster::RasterOptions;
+    let qr = QrBuilder::new("12345").kind(SymbolKind::Micro).ec_level(crate::error_correction::ECLevel::L).build().unwrap();
+    assert_eq!(qr.bitmap.height(),11);
+    let svg = qr.to_svg(&SvgOptions { quiet_zone: 2, module_size: 1.0, ..Default::default() });
+    assert!(svg.contains("viewBox=\"0 0 15 15\""),"{}",svg);
+    let image = qr.to_image_buffer(&RasterOptions { scale: 2, quiet_zone: 2, ..Default::default() });
+    assert_eq!(image.dimensions(),(30, 30));
+}
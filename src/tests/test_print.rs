@@ -0,0 +1,141 @@
+// Tests the print.rs module
+
+/// Rebuilds the module grid from rectangles in points, given the module size and where the code starts
+#[cfg(test)]
+fn grid_from_rects(rects: &[[f64; 4]], size: usize, module_size: f64, origin: f64) -> Vec<Vec<u8>> {
+    let mut grid = vec![vec![0; size]; size];
+    for [x, y, width, height] in rects {
+        assert!((height - module_size).abs() < 0.01);
+        let row = size - 1 - ((y - origin) / module_size).round() as usize;
+        let start = ((x - origin) / module_size).round() as usize;
+        for el in grid[row][start..start + (width / module_size).round() as usize].iter_mut() {
+            assert_eq!(*el,0,"Rectangles overlap");
+            *el = 1;
+        }
+    }
+    return grid;
+}
+
+#[test]
+fn test_print_pdf() {
+    use crate::print::*;
+    use crate::builder::QrBuilder;
+    let qr = QrBuilder::new("https://example.com/print").build().unwrap();
+    let options = PrintOptions {
+        module_size: Length::Millimeters(0.5),
+        bleed: Length::Points(9.0),
+        background: Some(PrintColor::Cmyk(0.0,0.0,0.0,0.0)),
+        title: Some(String::from("Label (front)")),
+        creation_date: Some(1700000000),
+        ..Default::default()
+    };
+    let pdf = qr.to_pdf(&options);
+    // Every cross reference entry points at its object
+    let text = String::from_utf8_lossy(&pdf);
+    let xref: usize = text.rsplit("startxref\n").next().unwrap().lines().next().unwrap().parse().unwrap();
+    assert!(pdf[xref..].starts_with(b"xref\n0 7\n"));
+    for (i, entry) in String::from_utf8_lossy(&pdf[xref..]).lines().skip(3).take(6).enumerate() {
+        let offset: usize = entry[..10].parse().unwrap();
+        assert!(pdf[offset..].starts_with(format!("{} 0 obj",i + 1).as_bytes()));
+    }
+
+    let document = lopdf::Document::load_mem(&pdf).unwrap();
+    let page = *document.get_pages().get(&1).unwrap();
+    let page_dict = document.get_object(page).unwrap().as_dict().unwrap();
+    let get_box = |name: &[u8]| -> Vec<f64> {
+        return page_dict.get(name).unwrap().as_array().unwrap().iter().map(|el| el.as_float().unwrap() as f64).collect();
+    };
+    // The trim box is the code and quiet zone in modules of 0.5mm, and the media box adds the bleed
+    let size = 0.5 * 72.0 / 25.4;
+    let width = (qr.bitmap.height() + 8) as f64 * size;
+    let trim = get_box(b"TrimBox");
+    assert!((trim[0] - 9.0).abs() < 0.01 && (trim[2] - 9.0 - width).abs() < 0.01,"{:?}",trim);
+    let media = get_box(b"MediaBox");
+    assert!((media[3] - 18.0 - width).abs() < 0.01,"{:?}",media);
+    assert_eq!(get_box(b"CropBox"),media);
+    assert_eq!(get_box(b"BleedBox"),media);
+    let info = document.trailer.get(b"Info").unwrap().as_reference().unwrap();
+    let info = document.get_object(info).unwrap().as_dict().unwrap();
+    let info_str = |key: &[u8]| -> &[u8] { return info.get(key).unwrap().as_str().unwrap(); };
+    assert_eq!(info_str(b"Title"),b"Label (front)");
+    assert_eq!(info_str(b"CreationDate"),b"D:20231114221320Z");
+    assert_eq!(info_str(b"ModDate"),b"D:20231114221320Z");
+    // PDF/X-1a keys, the output intent and the file identifier
+    assert_eq!(info_str(b"GTS_PDFXVersion"),b"PDF/X-1:2001");
+    assert_eq!(info_str(b"GTS_PDFXConformance"),b"PDF/X-1a:2001");
+    let intents = document.catalog().unwrap().get(b"OutputIntents").unwrap().as_array().unwrap();
+    let intent = document.get_object(intents[0].as_reference().unwrap()).unwrap().as_dict().unwrap();
+    assert_eq!(intent.get(b"S").unwrap().as_name().unwrap(),b"GTS_PDFX");
+    assert_eq!(intent.get(b"OutputConditionIdentifier").unwrap().as_str().unwrap(),b"FOGRA39");
+    assert_eq!(intent.get(b"RegistryName").unwrap().as_str().unwrap(),b"http://www.color.org");
+    let id = document.trailer.get(b"ID").unwrap().as_array().unwrap();
+    assert_eq!((id.len(), id[0].as_str().unwrap().len()),(2, 16));
+
+    let content = lopdf::content::Content::decode(&document.get_page_content(page).unwrap()).unwrap();
+    let operators: Vec<&str> = content.operations.iter().map(|op| op.operator.as_str()).filter(|op| *op != "re").collect();
+    assert_eq!(operators,["q", "k", "f", "k", "f", "Q"]);
+    let rects: Vec<[f64; 4]> = content.operations.iter().filter(|op| op.operator == "re").skip(1)
+        .map(|op| {
+            let values: Vec<f64> = op.operands.iter().map(|el| el.as_float().unwrap() as f64).collect();
+            [values[0], values[1], values[2], values[3]]
+        }).collect();
+    // Horizontal runs are merged, so there are fewer rectangles than dark modules
+    let num_dark = qr.bitmap.count_dark();
+    assert!(rects.len() < num_dark / 2);
+    assert_eq!(grid_from_rects(&rects,qr.bitmap.height(),size,9.0 + 4.0 * size),qr.bitmap.to_rows());
+}
+
+#[test]
+fn test_print_eps() {
+    use crate::print::*;
+    use crate::builder::QrBuilder;
+    let qr = QrBuilder::new("HELLO EPS").build().unwrap();
+    let options = PrintOptions {
+        module_size: Length::Points(2.0),
+        quiet_zone: 2,
+        foreground: PrintColor::cmyk_from_rgb(0,51,102),
+        title: Some(String::from("Two\nlines")),
+        ..Default::default()
+    };
+    let eps = qr.to_eps(&options);
+    let lines: Vec<&str> = eps.lines().collect();
+    assert_eq!(lines[0],"%!PS-Adobe-3.0 EPSF-3.0");
+    assert_eq!(lines[1],"%%BoundingBox: 0 0 50 50");
+    assert!(lines.contains(&"%%Title: Two lines"));
+    assert!(lines.contains(&"[ {ThisPage} << /TrimBox [0 0 50 50] /BleedBox [0 0 50 50] >> /PUT pdfmark"));
+    assert!(lines.contains(&"1 0.5 0 0.6 setcmykcolor"));
+    assert_eq!(lines[lines.len() - 3..],["showpage", "%%Trailer", "%%EOF"]);
+    // No background was given, so every rectangle is a run of dark modules
+    let rects: Vec<[f64; 4]> = lines.iter().filter_map(|line| line.strip_suffix(" R"))
+        .map(|line| {
+            let values: Vec<f64> = line.split(' ').map(|el| el.parse().unwrap()).collect();
+            [values[0], values[1], values[2], values[3]]
+        }).collect();
+    assert_eq!(grid_from_rects(&rects,21,2.0,4.0),qr.bitmap.to_rows());
+}
+
+#[test]
+fn test_print_colors() {
+    use crate::print::*;
+    assert_eq!(PrintColor::cmyk_from_rgb(0,0,0),PrintColor::Cmyk(0.0,0.0,0.0,1.0));
+    assert_eq!(PrintColor::cmyk_from_rgb(255,255,255),PrintColor::Cmyk(0.0,0.0,0.0,0.0));
+    assert_eq!(PrintColor::cmyk_from_rgb(255,0,0),PrintColor::Cmyk(0.0,1.0,1.0,0.0));
+    assert!((Length::Millimeters(25.4).points() - 72.0).abs() < 1e-9);
+    let bitmap = crate::grid::Grid::from_rows(&[vec![1]]);
+    let options = PrintOptions { quiet_zone: 0, module_size: Length::Points(1.0), title: Some(String::from("é")), ..Default::default() };
+    let pdf = String::from_utf8_lossy(&crate::print::to_pdf(&bitmap,&options)).to_string();
+    assert!(pdf.contains("/Title <FEFF00E9>"));
+    assert!(pdf.contains("0 0 0 1 k\n0 0 1 1 re\nf\n"));
+    assert!(pdf.contains("/GTS_PDFXVersion (PDF/X-1:2001)"));
+    // PDF/X needs a title, so untitled documents get one
+    let untitled = PrintOptions { title: None, creation_date: Some(951782400), ..options.clone() };
+    let pdf = String::from_utf8_lossy(&crate::print::to_pdf(&bitmap,&untitled)).to_string();
+    assert!(pdf.contains("/Title (QR code)") && pdf.contains("/CreationDate (D:20000229000000Z)"));
+    let options = PrintOptions { foreground: PrintColor::Gray(0.25), background: Some(PrintColor::Rgb(1.0,1.0,0.5)), ..options };
+    // RGB is not allowed in PDF/X-1a, so the document is plain PDF
+    let pdf = String::from_utf8_lossy(&crate::print::to_pdf(&bitmap,&options)).to_string();
+    assert!(!pdf.contains("/GTS_PDFXVersion") && !pdf.contains("/OutputIntents"));
+    assert!(pdf.contains("1 1 0.5 rg\n"));
+    let eps = crate::print::to_eps(&bitmap,&options);
+    assert!(eps.contains("1 1 0.5 setrgbcolor\n0 0 1 1 R\n0.25 setgray\n0 0 1 1 R\n"));
+}
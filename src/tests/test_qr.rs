@@ -1,84 +1,137 @@
+/// Returns an empty qr code of version with only the fixed patterns
+#[cfg(test)]
+fn blank_qr(version: u8) -> crate::qr::QR {
+    use crate::qr::QR;
+    use crate::grid::Grid;
+    use crate::error_correction::ECLevel;
+    let version_size = QR::get_version_size(version);
+    let mut qr = QR {
+        kind: crate::qr::SymbolKind::Qr,
+        bitmap: Grid::new(version_size,version_size),
+        pattern_mask: Grid::new(version_size,version_size),
+        version,
+        ec_level: ECLevel::L,
+        mask_index: 0,
+    };
+    qr.apply_patterns();
+    return qr;
+}
+
 #[test]
 fn test_get_available_modules() {
     use crate::qr::QR;
-    use crate::patterns::PatternMaskType;
-    use crate::error_correction::ECLevel;
     for version in 1..=40 {
-        let version_size = QR::get_version_size(version.into());
-        let mut qr = QR {
-            bitmap: vec![vec![0;version_size];version_size],
-            pattern_mask: vec![vec![PatternMaskType::None;version_size];version_size],
-            version,
-            ec_level: ECLevel::L,
-            mask_index: 0,
-        };
-        qr.apply_patterns();
-        let mut empty_cells = 0;
-        for vec in qr.pattern_mask.iter() {
-            for el in vec.iter() {
-                if *el == PatternMaskType::None {
-                    empty_cells += 1;
-                }
-            }
-        }
+        let qr = blank_qr(version);
+        let empty_cells = qr.pattern_mask.iter().filter(|(_, _, reserved)| !reserved).count();
         assert_eq!(empty_cells, QR::get_available_modules(version.into()), "Empty cells doesn't match on version {}",version);
 
     }
 }
 
+#[test]
+fn test_pattern_masks() {
+    use crate::qr::QR;
+    let reference = include_str!("data/pattern_masks.txt");
+    let mut versions = Vec::new();
+    for symbol in reference.split("\n\n").filter(|symbol| !symbol.starts_with('#') && !symbol.trim().is_empty()) {
+        let mut lines = symbol.lines();
+        let version: u8 = lines.next().unwrap().parse().unwrap();
+        let mut rows: Vec<Vec<u8>> = Vec::new();
+        for line in lines {
+            let (runs, repeat) = match line.split_once(" x") {
+                Some((runs, repeat)) => (runs, repeat.parse().unwrap()),
+                None => (line, 1),
+            };
+            let row: Vec<u8> = runs.split(' ').enumerate()
+                .flat_map(|(i, len)| std::iter::repeat_n((i % 2) as u8,len.parse().unwrap()))
+                .collect();
+            rows.extend(std::iter::repeat_n(row,repeat));
+        }
+        let qr = blank_qr(version);
+        assert_eq!(qr.pattern_mask.to_rows(),rows,"Version {}",version);
+        let data_modules = rows.iter().flatten().filter(|reserved| **reserved == 0).count();
+        assert_eq!(QR::get_available_modules(version.into()),data_modules,"Version {}",version);
+        versions.push(version);
+    }
+    assert_eq!(versions,(1..=40).collect::<Vec<u8>>());
+}
+
+#[test]
+fn test_alignment_positions() {
+    use crate::patterns::alignment_positions;
+    assert!(alignment_positions(1).is_empty());
+    assert_eq!(alignment_positions(32),[6, 34, 60, 86, 112, 138]);
+    // The last pattern is always 7 modules from the edge, and the gaps after the first are equal and even
+    for version in 2..=40 {
+        let positions = alignment_positions(version);
+        assert_eq!(positions.len(),version as usize / 7 + 2);
+        assert_eq!(positions[0],6);
+        assert_eq!(*positions.last().unwrap(),version as usize * 4 + 10);
+        let gaps: Vec<usize> = positions.windows(2).skip(1).map(|pair| pair[1] - pair[0]).collect();
+        assert!(gaps.iter().all(|gap| *gap == gaps[0] && gap % 2 == 0),"Version {}",version);
+    }
+    // Patterns centered on the timing patterns replace them
+    let rows = blank_qr(32).bitmap.to_rows();
+    for center in [34, 60, 86, 112] {
+        assert_eq!(rows[6][center - 2..=center + 2],[1,0,1,0,1]);
+        assert_eq!(rows[center - 2..=center + 2].iter().map(|row| row[6]).collect::<Vec<u8>>(),[1,0,1,0,1]);
+        assert_eq!(rows[center + 1][center - 2..=center + 2],[1,0,0,0,1]);
+    }
+}
+
 #[test]
 fn test_qr1() {
     use crate::qr::QR;
     use crate::qr::Encoding;
     use crate::error_correction::ECLevel;
-    let qr = QR::new(&String::from("https://github.com/benjamin-cates/qr_generator/123456789123456789123456789123456789"),Encoding::Byte,ECLevel::Q);
+    let qr = QR::new(&String::from("https://github.com/benjamin-cates/qr_generator/123456789123456789123456789123456789"),Encoding::Byte,ECLevel::Q).unwrap();
     
-    assert_eq!(qr.bitmap,vec![
-        vec![1,1,1,1,1,1,1,0,0,1,0,1,0,1,0,1,1,1,1,0,0,0,0,1,0,1,1,0,1,0,1,0,0,1,0,0,1,0,1,1,1,1,1,1,1],
+    assert_eq!(qr.bitmap.to_rows(),vec![
+        vec![1,1,1,1,1,1,1,0,0,1,0,1,0,1,0,1,1,1,0,0,0,0,0,1,0,1,1,0,1,0,1,0,0,1,0,0,1,0,1,1,1,1,1,1,1],
         vec![1,0,0,0,0,0,1,0,1,0,0,1,1,0,1,0,0,0,0,0,0,0,1,0,0,0,0,1,0,0,0,0,1,1,0,1,0,0,1,0,0,0,0,0,1],
-        vec![1,0,1,1,1,0,1,0,0,1,0,1,1,1,0,0,1,1,0,0,1,1,0,0,0,0,1,0,0,0,0,0,1,1,0,1,0,0,1,0,1,1,1,0,1],
-        vec![1,0,1,1,1,0,1,0,1,0,0,0,0,1,0,1,0,1,1,0,0,1,1,0,1,0,0,0,1,0,0,0,1,0,0,1,1,0,1,0,1,1,1,0,1],
-        vec![1,0,1,1,1,0,1,0,1,0,0,0,0,1,0,0,0,0,1,1,1,1,1,1,1,1,0,1,0,0,1,1,0,0,1,1,1,0,1,0,1,1,1,0,1],
-        vec![1,0,0,0,0,0,1,0,0,0,0,1,0,1,0,1,1,0,1,0,1,0,0,0,1,0,0,0,0,1,0,1,0,1,0,0,0,0,1,0,0,0,0,0,1],
+        vec![1,0,1,1,1,0,1,0,0,1,0,1,1,1,0,0,1,1,0,0,0,1,0,0,0,1,0,0,0,0,0,0,1,1,0,1,0,0,1,0,1,1,1,0,1],
+        vec![1,0,1,1,1,0,1,0,1,0,0,0,0,1,0,1,0,1,1,0,0,1,1,0,1,1,1,0,1,0,0,0,1,0,0,1,1,0,1,0,1,1,1,0,1],
+        vec![1,0,1,1,1,0,1,0,1,0,0,0,0,1,0,0,0,0,1,1,1,1,1,1,1,0,1,1,0,0,1,1,0,0,1,1,1,0,1,0,1,1,1,0,1],
+        vec![1,0,0,0,0,0,1,0,0,0,0,1,0,1,1,1,1,0,1,0,1,0,0,0,1,1,0,0,0,1,0,1,0,1,0,0,0,0,1,0,0,0,0,0,1],
         vec![1,1,1,1,1,1,1,0,1,0,1,0,1,0,1,0,1,0,1,0,1,0,1,0,1,0,1,0,1,0,1,0,1,0,1,0,1,0,1,1,1,1,1,1,1],
-        vec![0,0,0,0,0,0,0,0,1,0,1,0,1,1,0,1,0,1,0,0,1,0,0,0,1,0,1,0,1,1,0,0,0,1,0,0,1,0,0,0,0,0,0,0,0],
-        vec![0,1,0,1,1,1,1,0,1,1,1,1,1,0,1,0,0,0,0,1,1,1,1,1,1,0,0,0,1,1,0,1,0,0,0,0,0,1,1,0,1,1,0,1,0],
-        vec![0,1,1,0,0,1,0,0,1,1,0,1,0,0,0,1,1,1,1,1,1,1,0,1,1,1,1,0,0,1,1,1,1,0,1,0,1,1,1,0,1,0,1,0,0],
-        vec![1,1,0,0,0,1,1,0,1,0,0,1,1,0,1,0,0,0,0,0,1,0,1,1,1,1,0,0,1,1,0,0,1,0,1,0,0,1,0,0,0,0,1,1,1],
-        vec![1,0,0,0,0,1,0,0,0,1,1,0,1,0,1,1,0,0,0,1,1,1,0,1,1,0,1,0,0,0,1,1,1,1,1,1,1,1,1,1,0,0,1,1,0],
+        vec![0,0,0,0,0,0,0,0,1,0,1,0,1,0,0,1,0,1,0,0,1,0,0,0,1,0,1,0,1,1,0,0,0,1,0,0,1,0,0,0,0,0,0,0,0],
+        vec![0,1,0,1,1,1,1,0,1,1,1,1,0,0,0,0,0,0,0,1,1,1,1,1,1,0,0,0,1,1,0,1,0,0,0,0,0,1,1,0,1,1,0,1,0],
+        vec![1,1,0,1,0,1,0,0,1,1,0,1,1,1,1,1,1,1,1,1,1,1,0,1,0,1,1,0,0,1,1,1,1,0,1,0,1,1,1,0,1,0,1,0,0],
+        vec![1,1,0,1,0,1,1,0,1,0,0,1,1,0,1,0,0,0,0,0,1,0,1,1,0,1,0,0,1,1,0,0,1,0,1,0,0,1,0,0,0,0,1,1,1],
+        vec![1,0,1,1,0,1,0,1,0,1,1,0,1,0,1,1,0,0,0,1,1,1,0,0,1,0,1,0,0,0,1,1,1,1,1,1,1,1,1,1,0,0,1,1,0],
         vec![1,1,0,0,1,0,1,0,1,0,1,1,0,1,1,0,0,1,0,0,0,0,0,0,0,0,1,0,1,0,1,1,1,1,1,1,0,0,0,1,1,0,0,0,0],
         vec![1,0,0,1,0,1,0,0,1,1,0,0,0,1,0,1,1,0,1,0,0,0,1,1,0,1,1,1,0,0,1,1,1,0,0,1,1,0,1,1,0,0,1,1,0],
         vec![1,1,0,1,1,1,1,0,1,0,1,1,0,0,1,1,1,1,1,1,1,1,0,0,1,0,1,1,0,1,0,0,0,0,0,1,0,1,1,1,1,0,0,1,0],
-        vec![0,1,0,1,1,0,0,1,1,1,0,0,0,1,0,1,1,1,1,1,0,1,0,1,0,1,1,1,0,0,1,1,1,0,0,1,0,1,1,1,0,0,1,0,0],
-        vec![0,0,1,0,1,1,1,1,0,1,1,0,1,1,1,1,0,0,1,0,1,0,0,1,0,0,0,0,0,1,0,1,1,1,1,0,0,0,1,1,0,0,0,0,1],
-        vec![1,0,0,0,0,1,0,0,1,1,0,1,0,1,1,1,1,0,0,1,0,0,0,0,0,0,0,0,1,1,1,0,1,0,1,1,1,1,0,0,1,1,0,0,1],
-        vec![0,0,1,0,0,1,1,0,0,0,0,1,1,0,1,0,0,0,0,0,1,0,0,1,0,0,0,1,0,0,0,1,1,1,0,0,0,1,0,0,1,1,1,0,1],
-        vec![0,1,1,0,1,1,0,0,0,1,1,0,1,1,0,0,0,0,1,0,1,0,1,1,0,1,1,1,0,0,0,1,0,0,0,0,1,1,1,1,1,0,1,0,0],
-        vec![1,0,0,1,1,1,1,1,1,0,1,0,1,1,1,1,1,1,1,1,1,1,1,1,1,0,0,1,1,0,1,0,0,1,0,1,1,1,1,1,1,1,0,1,0],
-        vec![1,0,0,0,1,0,0,0,1,1,0,1,0,1,1,1,1,0,1,0,1,0,0,0,1,0,1,1,1,0,1,0,0,1,1,1,1,0,0,0,1,0,0,1,0],
-        vec![0,1,1,1,1,0,1,0,1,0,0,0,1,0,1,1,0,1,0,0,1,0,1,0,1,1,1,1,0,0,0,1,0,1,1,1,1,0,1,0,1,1,1,0,1],
-        vec![1,1,0,0,1,0,0,0,1,0,1,0,0,1,0,1,1,1,1,1,1,0,0,0,1,0,0,1,0,1,0,0,1,1,0,1,1,0,0,0,1,0,1,1,1],
+        vec![0,1,0,1,1,0,0,1,1,0,0,0,0,1,0,1,1,1,1,1,0,1,0,1,0,1,1,1,0,0,1,1,1,0,0,1,0,1,1,1,0,0,1,0,0],
+        vec![0,0,1,0,1,1,1,1,0,0,1,0,1,1,1,0,0,0,1,0,1,0,0,1,0,0,0,0,0,1,0,1,1,1,1,0,0,0,1,1,0,0,0,0,1],
+        vec![1,0,0,0,0,1,0,0,1,1,1,1,0,1,1,0,1,0,0,1,0,0,0,0,0,0,0,0,1,1,1,0,1,0,1,1,1,1,0,0,1,1,0,0,1],
+        vec![0,0,1,0,0,1,1,0,0,0,0,1,1,0,1,1,1,0,0,0,1,0,0,1,0,0,0,1,0,0,0,1,1,1,0,0,0,1,0,0,1,1,1,0,1],
+        vec![0,1,1,0,1,1,0,0,0,1,1,0,1,1,0,0,1,0,1,0,1,0,1,1,0,0,0,0,0,0,0,1,0,0,0,0,1,1,1,1,1,0,1,0,0],
+        vec![1,0,0,1,1,1,1,1,1,0,1,0,1,1,1,1,1,1,1,1,1,1,1,1,1,1,1,0,0,0,1,0,0,1,0,1,1,1,1,1,1,1,0,1,0],
+        vec![1,0,0,0,1,0,0,0,1,1,0,1,0,1,1,1,1,0,1,0,1,0,0,0,1,1,0,0,0,0,1,0,0,1,1,1,1,0,0,0,1,0,0,1,0],
+        vec![0,1,1,1,1,0,1,0,1,0,0,0,1,0,1,1,0,0,1,0,1,0,1,0,1,0,1,0,1,0,0,1,0,1,1,1,1,0,1,0,1,1,1,0,1],
+        vec![1,1,0,0,1,0,0,0,1,0,1,0,0,1,0,1,1,0,1,1,1,0,0,0,1,0,0,1,0,1,0,0,1,1,0,1,1,0,0,0,1,0,1,1,1],
         vec![1,0,1,1,1,1,1,1,1,1,0,1,1,0,1,0,0,1,1,0,1,1,1,1,1,1,1,1,1,0,1,0,1,0,0,0,1,1,1,1,1,0,0,0,1],
-        vec![1,1,1,0,1,0,0,1,1,0,1,1,0,1,1,1,1,0,1,1,1,0,0,0,1,0,0,0,0,1,1,0,0,1,0,1,1,1,0,1,0,1,0,0,0],
+        vec![1,1,1,0,1,0,0,1,1,0,1,1,0,1,1,1,1,0,0,1,1,0,0,0,1,0,0,0,0,1,1,0,0,1,0,1,1,1,0,1,0,1,0,0,0],
         vec![0,1,1,1,0,1,1,0,1,1,0,1,0,1,0,1,0,1,1,0,1,1,1,0,0,0,1,0,1,1,0,0,1,0,0,0,0,1,0,0,0,0,0,1,0],
-        vec![1,1,0,0,0,0,0,1,0,1,1,1,0,0,0,1,1,1,1,1,1,1,0,1,0,1,1,1,0,0,1,1,0,0,1,1,1,0,0,1,0,1,1,1,1],
-        vec![1,1,0,0,1,1,1,1,0,1,1,1,0,1,1,1,0,1,1,1,1,0,0,0,0,1,0,1,0,0,1,0,1,1,1,1,1,1,1,0,1,1,0,0,1],
-        vec![0,1,0,1,0,0,0,0,1,1,0,1,0,0,1,0,1,0,0,0,1,1,1,1,1,0,1,1,0,0,1,0,1,0,1,0,0,1,0,1,1,1,1,0,1],
-        vec![1,0,1,1,0,0,1,1,1,1,1,1,0,1,0,0,1,1,1,1,1,1,1,1,1,0,1,0,1,1,0,0,1,0,0,1,0,1,1,0,1,0,0,0,1],
-        vec![1,0,1,0,0,1,0,1,0,0,0,0,0,0,1,0,0,1,0,1,0,0,1,1,1,0,0,0,1,1,1,0,0,0,1,1,1,0,0,0,0,1,1,1,0],
-        vec![1,0,0,0,1,1,1,0,0,0,0,1,0,1,0,1,0,0,0,1,1,1,0,0,0,1,0,0,1,1,0,1,0,1,1,0,0,1,0,1,0,0,0,1,0],
-        vec![1,1,0,0,0,0,0,1,0,0,0,1,0,0,1,0,1,0,1,1,0,0,0,1,0,0,1,1,1,1,1,1,1,0,1,1,0,0,0,1,1,1,1,0,0],
-        vec![0,0,0,0,1,0,1,0,1,1,1,1,0,0,1,0,0,0,0,0,1,1,0,1,1,0,1,0,0,1,0,0,0,0,1,0,0,0,1,0,1,1,1,0,1],
+        vec![1,1,0,0,0,0,0,1,0,1,1,1,0,0,0,1,1,1,1,1,1,0,1,1,0,1,1,1,0,0,1,1,0,0,1,1,1,0,0,1,0,1,1,1,1],
+        vec![1,1,0,0,1,1,1,1,0,1,1,1,0,1,1,1,0,1,1,1,0,0,0,0,0,1,0,1,0,0,1,0,1,1,1,1,1,1,1,0,1,1,0,0,1],
+        vec![0,1,0,1,0,0,0,0,1,1,0,1,0,0,1,0,1,0,0,1,1,0,1,1,1,0,1,1,0,0,1,0,1,0,1,0,0,1,0,1,1,1,1,0,1],
+        vec![1,0,1,1,0,0,1,1,1,1,1,1,0,1,0,0,1,1,1,0,0,1,1,1,1,0,1,0,1,1,0,0,1,0,0,1,0,1,1,0,1,0,0,0,1],
+        vec![1,0,1,0,0,1,0,1,0,0,0,0,0,0,1,0,0,1,0,0,0,0,1,1,1,0,0,0,1,1,1,0,0,0,1,1,1,0,0,0,0,1,1,1,0],
+        vec![1,0,1,1,0,1,1,0,0,0,0,1,1,0,0,1,0,0,0,1,1,1,0,0,0,1,0,0,1,1,0,1,0,1,1,0,0,1,0,1,0,0,0,1,0],
+        vec![0,1,0,0,0,0,0,1,0,0,0,1,1,0,0,0,1,0,1,1,0,0,0,1,0,0,1,1,1,1,1,1,1,0,1,1,0,0,0,1,1,1,1,0,0],
+        vec![0,0,0,0,1,0,1,0,1,1,1,1,1,0,1,0,0,0,0,0,1,1,0,1,1,0,1,0,0,1,0,0,0,0,1,0,0,0,1,0,1,1,1,0,1],
         vec![0,1,1,1,1,0,0,1,1,0,1,0,0,1,1,1,0,0,1,1,0,1,1,0,0,1,1,0,0,0,1,1,1,1,1,0,0,1,1,0,1,0,1,0,1],
         vec![1,0,0,1,1,0,1,1,0,0,0,1,1,1,0,0,1,0,0,1,1,1,1,1,1,0,1,0,1,1,0,1,1,0,1,1,1,1,1,1,1,1,0,0,0],
         vec![0,0,0,0,0,0,0,0,1,1,1,1,1,1,0,1,0,0,1,1,1,0,0,0,1,1,0,1,0,0,1,0,0,0,0,1,1,0,0,0,1,0,1,1,0],
-        vec![1,1,1,1,1,1,1,0,0,1,0,0,1,0,0,1,1,1,0,1,1,0,1,0,1,0,1,0,0,1,0,1,0,0,0,0,1,0,1,0,1,1,0,0,0],
-        vec![1,0,0,0,0,0,1,0,1,1,1,0,0,0,0,1,0,1,1,0,1,0,0,0,1,0,0,0,0,0,1,1,0,1,0,1,1,0,0,0,1,0,1,1,1],
-        vec![1,0,1,1,1,0,1,0,1,1,0,1,1,1,0,1,1,1,1,1,1,1,1,1,1,1,0,0,0,0,0,1,1,0,1,1,1,1,1,1,1,0,0,1,1],
-        vec![1,0,1,1,1,0,1,0,1,0,1,0,1,0,0,0,0,0,1,0,1,0,1,1,1,0,0,0,1,1,1,1,0,0,1,1,0,0,0,1,0,1,0,1,0],
-        vec![1,0,1,1,1,0,1,0,0,0,1,1,0,1,1,1,1,1,0,0,0,0,1,1,1,0,1,0,1,0,0,1,1,1,0,1,1,1,1,0,1,0,1,0,1],
-        vec![1,0,0,0,0,0,1,0,1,1,1,1,0,0,0,1,1,1,1,0,1,1,1,1,1,0,0,0,0,0,0,1,0,1,0,1,1,0,1,0,0,0,1,1,1],
-        vec![1,1,1,1,1,1,1,0,0,0,0,0,1,1,0,1,0,0,1,0,1,1,1,0,1,1,0,1,1,1,1,0,0,0,1,1,0,0,1,0,1,1,0,0,0]]);
+        vec![1,1,1,1,1,1,1,0,0,1,1,0,1,0,0,1,1,1,0,1,1,0,1,0,1,0,1,0,0,1,0,1,0,0,0,0,1,0,1,0,1,1,0,0,0],
+        vec![1,0,0,0,0,0,1,0,1,0,0,0,0,0,0,1,0,1,1,0,1,0,0,0,1,0,0,0,0,0,1,1,0,1,0,1,1,0,0,0,1,0,1,1,1],
+        vec![1,0,1,1,1,0,1,0,1,1,1,1,1,1,0,1,0,1,1,1,1,1,1,1,1,1,0,0,0,0,0,1,1,0,1,1,1,1,1,1,1,0,0,1,1],
+        vec![1,0,1,1,1,0,1,0,1,0,0,0,1,0,0,0,1,0,1,0,1,0,1,1,1,0,0,0,1,1,1,1,0,0,1,1,0,0,0,1,0,1,0,1,0],
+        vec![1,0,1,1,1,0,1,0,0,0,1,1,0,1,1,1,0,1,0,0,0,0,1,1,1,0,1,0,1,0,0,1,1,1,0,1,1,1,1,0,1,0,1,0,1],
+        vec![1,0,0,0,0,0,1,0,1,1,1,1,0,0,0,0,0,1,1,0,1,1,1,0,1,1,0,0,0,0,0,1,0,1,0,1,1,0,1,0,0,0,1,1,1],
+        vec![1,1,1,1,1,1,1,0,0,0,0,0,1,1,0,1,0,0,1,0,1,1,1,1,0,0,0,1,1,1,1,0,0,0,1,1,0,0,1,0,1,1,0,0,0]]);
 }
 
 #[test]
@@ -86,8 +139,8 @@ fn test_qr2() {
     use crate::qr::QR;
     use crate::qr::Encoding;
     use crate::error_correction::ECLevel;
-    let qr = QR::new(&String::from("HELLO WORLD"),Encoding::Alphanumeric,ECLevel::Q);
-    assert_eq!(qr.bitmap,vec![
+    let qr = QR::new(&String::from("HELLO WORLD"),Encoding::Alphanumeric,ECLevel::Q).unwrap();
+    assert_eq!(qr.bitmap.to_rows(),vec![
         vec![1,1,1,1,1,1,1,0,0,0,0,1,0,0,1,1,1,1,1,1,1],
         vec![1,0,0,0,0,0,1,0,1,1,0,0,1,0,1,0,0,0,0,0,1],
         vec![1,0,1,1,1,0,1,0,0,1,0,1,1,0,1,0,1,1,1,0,1],
@@ -117,8 +170,8 @@ fn test_qr3() {
     use crate::qr::QR;
     use crate::qr::Encoding;
     use crate::error_correction::ECLevel;
-    let qr = QR::new(&String::from("65432112"),Encoding::Numeric,ECLevel::Q);
-    assert_eq!(qr.bitmap,vec![
+    let qr = QR::new(&String::from("65432112"),Encoding::Numeric,ECLevel::Q).unwrap();
+    assert_eq!(qr.bitmap.to_rows(),vec![
         vec![1,1,1,1,1,1,1,0,0,0,0,1,0,0,1,1,1,1,1,1,1],
         vec![1,0,0,0,0,0,1,0,1,1,1,0,0,0,1,0,0,0,0,0,1],
         vec![1,0,1,1,1,0,1,0,0,1,0,1,1,0,1,0,1,1,1,0,1],
@@ -141,3 +194,55 @@ fn test_qr3() {
         vec![1,0,0,0,0,0,1,0,1,1,0,0,0,1,1,0,1,0,1,0,1],
         vec![1,1,1,1,1,1,1,0,0,1,1,1,0,0,0,0,0,0,1,1,0]]);
 }
+
+#[test]
+fn test_qr_eci() {
+    use crate::qr::QR;
+    use crate::qr::Encoding;
+    use crate::error_correction::ECLevel;
+    let str = "Zoë Ångström, Øresund 🎉";
+    let qr = QR::new(str,crate::bits::get_encoding(str),ECLevel::M).unwrap();
+    // 30 bytes of UTF-8 plus the ECI and byte mode headers take 33 data codewords
+    assert_eq!(qr.version,3);
+    assert_eq!(qr.bitmap.height(),QR::get_version_size(3));
+    assert!(matches!(crate::bits::get_encoding(str),Encoding::ECI));
+}
+
+#[test]
+fn test_qr_kanji() {
+    use crate::qr::QR;
+    use crate::qr::Encoding;
+    use crate::error_correction::ECLevel;
+    let str = "東京都千代田区丸の内一丁目";
+    // 13 characters take 169 bits in kanji mode instead of 312 bits as UTF-8
+    assert_eq!(QR::get_min_version(str,Encoding::Kanji,ECLevel::M).unwrap(),(2,28));
+    assert_eq!(QR::get_min_version(str,Encoding::ECI,ECLevel::M).unwrap().0,3);
+    let qr = QR::new(str,crate::bits::get_encoding(str),ECLevel::M).unwrap();
+    assert_eq!(qr.version,2);
+}
+
+#[test]
+fn test_errors() {
+    use crate::qr::QR;
+    use crate::qr::Encoding;
+    use crate::segment::Segment;
+    use crate::error::QrError;
+    use crate::error_correction::ECLevel;
+    assert_eq!(QR::new("12a4",Encoding::Numeric,ECLevel::L).err(),
+        Some(QrError::InvalidCharacter { ch: 'a', position: 2, mode: Encoding::Numeric }));
+    assert_eq!(QR::new("hello",Encoding::Alphanumeric,ECLevel::L).err(),
+        Some(QrError::InvalidCharacter { ch: 'h', position: 0, mode: Encoding::Alphanumeric }));
+    // Position counts the characters of the previous segments
+    let segments = [Segment::new("ABC",Encoding::Alphanumeric),Segment::new("ñ点",Encoding::Kanji)];
+    assert_eq!(QR::from_segments(&segments,ECLevel::L).err(),
+        Some(QrError::InvalidCharacter { ch: 'ñ', position: 3, mode: Encoding::Kanji }));
+    // Too many digits for version 40-L
+    assert_eq!(QR::new(&"1".repeat(7088),Encoding::Numeric,ECLevel::L).unwrap().version,40);
+    assert_eq!(QR::new(&"1".repeat(7090),Encoding::Numeric,ECLevel::L).err(),
+        Some(QrError::DataTooLong { needed_bits: 4 + 14 + 23634, capacity: 2956 * 8 }));
+    assert!(matches!(QR::new_optimized(&"a".repeat(3000),ECLevel::H),Err(QrError::DataTooLong { .. })));
+    assert_eq!(crate::metadata::num_length_bits(0,Encoding::Byte),Err(QrError::VersionOutOfRange(0)));
+    assert_eq!(crate::segment::optimize_for_version("123",41),Err(QrError::VersionOutOfRange(41)));
+    assert_eq!(QrError::InvalidCharacter { ch: 'a', position: 2, mode: Encoding::Numeric }.to_string(),
+        "Invalid character 'a' at position 2 in encoding Numeric");
+}
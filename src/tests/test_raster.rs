@@ -0,0 +1,53 @@
+// Tests the raster.rs module
+
+#[test]
+fn test_raster_buffer() {
+    use crate::raster::*;
+    let bitmap = crate::grid::Grid::from_rows(&[vec![1,0],vec![0,1]]);
+    let options = RasterOptions {
+        scale: 3,
+        quiet_zone: 1,
+        foreground: [0, 51, 102, 255],
+        background: [255, 255, 255, 0],
+    };
+    let image = to_image_buffer(&bitmap,&options);
+    assert_eq!(image.dimensions(),(12,12));
+    // Quiet zone
+    assert_eq!(image.get_pixel(0,0).0,[255, 255, 255, 0]);
+    assert_eq!(image.get_pixel(11,5).0,[255, 255, 255, 0]);
+    // Each module is a 3x3 square
+    for (x, y, dark) in [(3,3,true),(5,5,true),(6,3,false),(8,5,false),(3,6,false),(6,6,true),(8,8,true),(9,9,false)] {
+        assert_eq!(image.get_pixel(x,y).0,if dark {options.foreground} else {options.background},"({},{})",x,y);
+    }
+    let image = to_image_buffer(&bitmap,&RasterOptions::default());
+    assert_eq!(image.dimensions(),(10,10));
+    assert_eq!(image.get_pixel(4,4).0,[0, 0, 0, 255]);
+    assert_eq!(image.get_pixel(5,4).0,[255, 255, 255, 255]);
+}
+
+#[test]
+fn test_raster_formats() {
+    use crate::raster::*;
+    use crate::qr::{QR,Encoding};
+    use crate::error_correction::ECLevel;
+    let qr = QR::new("HELLO WORLD",Encoding::Alphanumeric,ECLevel::Q).unwrap();
+    let options = RasterOptions { scale: 4, ..Default::default() };
+    for (format, image_format) in [
+        (RasterFormat::Png, image::ImageFormat::Png),
+        (RasterFormat::Jpeg(90), image::ImageFormat::Jpeg),
+        (RasterFormat::Bmp, image::ImageFormat::Bmp),
+        (RasterFormat::Gif, image::ImageFormat::Gif),
+        (RasterFormat::WebP, image::ImageFormat::WebP),
+    ] {
+        let mut out: Vec<u8> = Vec::new();
+        qr.write_image(&mut out,format,&options).unwrap();
+        assert_eq!(image::guess_format(&out).unwrap(),image_format);
+        let decoded = image::load_from_memory_with_format(&out,image_format).unwrap().to_luma8();
+        assert_eq!(decoded.dimensions(),(21 * 4 + 32,21 * 4 + 32));
+        // Sample the center of every module, allowing for lossy JPEG
+        for (x, y, dark) in qr.bitmap.iter() {
+            let pixel = decoded.get_pixel(16 + x as u32 * 4 + 2,16 + y as u32 * 4 + 2).0[0];
+            assert_eq!(pixel < 128,dark,"{:?} at ({},{})",format,x,y);
+        }
+    }
+}
@@ -0,0 +1,122 @@
+// Tests the reader.rs module
+
+/// Renders text as an RGBA image of a qr code
+#[cfg(test)]
+fn render(text: &str, scale: u32) -> image::RgbaImage {
+    use crate::raster::RasterOptions;
+    let qr = crate::builder::QrBuilder::new(text).build().unwrap();
+    return qr.to_image_buffer(&RasterOptions { scale, ..Default::default() });
+}
+
+#[cfg(test)]
+fn assert_close(point: crate::reader::Point, x: f64, y: f64) {
+    assert!((point.x - x).abs() < 2.0 && (point.y - y).abs() < 2.0,"{:?} is not close to ({}, {})",point,x,y);
+}
+
+#[test]
+fn test_read_single() {
+    use crate::reader::*;
+    let text = "https://example.com/inventory/4242";
+    let image = render(text,4);
+    let found = read_image(&image::DynamicImage::ImageRgba8(image));
+    assert_eq!(found.len(),1);
+    assert_eq!(found[0].decoded.text,text);
+    let size = 16.0 + (found[0].decoded.version as f64 * 4.0 + 17.0) * 4.0;
+    assert_close(found[0].bounds[0],16.0,16.0);
+    assert_close(found[0].bounds[1],size,16.0);
+    assert_close(found[0].bounds[2],size,size);
+    assert_close(found[0].bounds[3],16.0,size);
+}
+
+/// Draws source onto a white canvas so that its corners land on quad
+#[cfg(test)]
+fn warp(source: &image::RgbaImage, width: u32, height: u32, quad: [(f64, f64); 4]) -> image::RgbaImage {
+    use crate::reader::{Point, PerspectiveTransform};
+    let (w, h) = (source.width() as f64, source.height() as f64);
+    let transform = PerspectiveTransform::quad_to_quad(
+        quad.map(|(x, y)| Point { x, y }),
+        [Point { x: 0.0, y: 0.0 }, Point { x: w, y: 0.0 }, Point { x: w, y: h }, Point { x: 0.0, y: h }],
+    );
+    return image::RgbaImage::from_fn(width, height, |x, y| {
+        let point = transform.apply(Point { x: x as f64 + 0.5, y: y as f64 + 0.5 });
+        if point.x < 0.0 || point.y < 0.0 || point.x >= w || point.y >= h {
+            return image::Rgba([255, 255, 255, 255]);
+        }
+        return *source.get_pixel(point.x as u32,point.y as u32);
+    });
+}
+
+#[test]
+fn test_read_multiple() {
+    use crate::reader::*;
+    let texts = ["FIRST LABEL 0001", "second label with more text to need a bigger version", "ラベル３"];
+    let mut canvas = image::RgbaImage::from_pixel(700, 400, image::Rgba([255, 255, 255, 255]));
+    image::imageops::overlay(&mut canvas,&render(texts[0],3),10,20);
+    image::imageops::overlay(&mut canvas,&image::imageops::rotate90(&render(texts[1],5)),200,30);
+    image::imageops::overlay(&mut canvas,&image::imageops::rotate180(&render(texts[2],4)),520,200);
+    let found = read_image(&image::DynamicImage::ImageRgba8(canvas));
+    let mut decoded: Vec<&str> = found.iter().map(|x| x.decoded.text.as_str()).collect();
+    decoded.sort();
+    let mut expected = texts.to_vec();
+    expected.sort();
+    assert_eq!(decoded,expected);
+    // Bounds follow the orientation of the code
+    let rotated = found.iter().find(|x| x.decoded.text == texts[1]).unwrap();
+    let size = (rotated.decoded.version as f64 * 4.0 + 17.0) * 5.0;
+    assert_close(rotated.bounds[0],200.0 + 20.0 + size,30.0 + 20.0);
+    assert_close(rotated.bounds[3],200.0 + 20.0,30.0 + 20.0);
+    let upside_down = found.iter().find(|x| x.decoded.text == texts[2]).unwrap();
+    let size = (upside_down.decoded.version as f64 * 4.0 + 17.0) * 4.0;
+    assert_close(upside_down.bounds[0],520.0 + 16.0 + size,200.0 + 16.0 + size);
+}
+
+#[test]
+fn test_read_perspective() {
+    use crate::reader::*;
+    // Rotated by about 20 degrees
+    let rotated = [(120.0, 40.0), (420.0, 150.0), (310.0, 450.0), (10.0, 340.0)];
+    // Version 1 has no alignment pattern, so only mild perspective can be corrected
+    let mild = [(60.0, 50.0), (420.0, 40.0), (440.0, 440.0), (50.0, 420.0)];
+    // Seen from an angle, version 7 and up also has version information and several alignment patterns
+    let strong = [(60.0, 60.0), (400.0, 30.0), (440.0, 460.0), (40.0, 400.0)];
+    for (text, scale, quads) in [("perspective", 6, [rotated, mild]), (&*"tilted and skewed ".repeat(8), 3, [rotated, strong])] {
+        let source = render(text,scale);
+        for quad in quads {
+            let found = read_image(&image::DynamicImage::ImageRgba8(warp(&source,480,480,quad)));
+            assert_eq!(found.len(),1,"{} in {:?}",text,quad);
+            assert_eq!(found[0].decoded.text,text);
+        }
+    }
+}
+
+#[test]
+fn test_read_uneven_lighting() {
+    use crate::reader::*;
+    let text = "Shadow across the label";
+    let qr = crate::builder::QrBuilder::new(text).build().unwrap();
+    let mut image = qr.to_image_buffer(&crate::raster::RasterOptions { scale: 6, ..Default::default() });
+    // Light falls off from left to right, so the right side is darker than a global threshold would allow
+    let width = image.width() as f64;
+    for (x, _, pixel) in image.enumerate_pixels_mut() {
+        let light = 1.0 - 0.7 * x as f64 / width;
+        for channel in pixel.0.iter_mut().take(3) {
+            *channel = (*channel as f64 * light + 20.0 * (1.0 - light)) as u8;
+        }
+    }
+    let mut jpeg: Vec<u8> = Vec::new();
+    image::DynamicImage::ImageRgba8(image).to_rgb8()
+        .write_to(&mut std::io::Cursor::new(&mut jpeg),image::ImageFormat::Jpeg).unwrap();
+    let found = read_image_bytes(&jpeg).unwrap();
+    assert_eq!(found.len(),1);
+    assert_eq!(found[0].decoded.text,text);
+}
+
+#[test]
+fn test_read_nothing() {
+    use crate::reader::*;
+    let blank = image::GrayImage::from_pixel(200, 100, image::Luma([255]));
+    assert!(read_image(&image::DynamicImage::ImageLuma8(blank)).is_empty());
+    let noise = image::GrayImage::from_fn(200, 100, |x, y| image::Luma([((x * 7919 + y * 104729) % 251) as u8]));
+    assert!(read_image(&image::DynamicImage::ImageLuma8(noise)).is_empty());
+    assert!(read_image_bytes(b"not an image").is_err());
+}
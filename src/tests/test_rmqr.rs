@@ -0,0 +1,187 @@
+// Tests the rmqr.rs module
+
+#[cfg(test)]
+/// Returns an empty rectangular code of version with only the fixed patterns
+fn blank_rmqr(version: u8) -> crate::qr::QR {
+    use crate::qr::{QR,SymbolKind};
+    use crate::grid::Grid;
+    let (height, width) = QR::get_rmqr_version_size(version);
+    let mut qr = QR {
+        kind: SymbolKind::Rmqr,
+        bitmap: Grid::new(width,height),
+        pattern_mask: Grid::new(width,height),
+        version,
+        ec_level: crate::error_correction::ECLevel::M,
+        mask_index: 0,
+    };
+    qr.apply_patterns();
+    return qr;
+}
+
+#[test]
+fn test_rmqr_r7x43() {
+    use crate::builder::QrBuilder;
+    use crate::qr::SymbolKind;
+    let qr = QrBuilder::new("HELLO").kind(SymbolKind::Rmqr).version(1).build().unwrap();
+    // Output of this crate rather than a reference symbol, test_rmqr_data_positions checks the module order against the standard
+    assert_eq!(qr.bitmap.to_rows(),vec![
+        vec![1,1,1,1,1,1,1,0,1,0,1,0,1,0,1,0,1,0,1,0,1,1,1,0,1,0,1,0,1,0,1,0,1,0,1,0,1,0,1,0,1,1,1],
+        vec![1,0,0,0,0,0,1,0,0,1,0,1,0,0,0,0,0,0,1,1,1,0,1,1,0,1,1,0,0,0,0,0,1,1,0,1,1,0,0,0,1,0,1],
+        vec![1,0,1,1,1,0,1,0,1,0,1,1,0,1,0,1,1,0,1,0,1,1,1,1,0,0,0,1,1,1,1,0,0,1,0,1,1,1,1,1,1,1,1],
+        vec![1,0,1,1,1,0,1,0,0,1,1,0,0,1,0,1,1,0,0,1,0,0,1,1,1,1,1,1,0,0,1,1,0,1,1,0,0,0,1,0,0,0,1],
+        vec![1,0,1,1,1,0,1,0,0,0,1,1,1,0,0,0,1,1,0,0,1,1,1,0,1,1,0,1,0,0,1,0,0,1,1,1,0,0,1,0,1,0,1],
+        vec![1,0,0,0,0,0,1,0,1,1,1,0,0,0,1,1,0,0,0,0,1,0,1,0,1,0,0,0,1,1,1,1,1,1,0,1,1,0,1,0,0,0,1],
+        vec![1,1,1,1,1,1,1,0,1,0,1,0,1,0,1,0,1,0,1,0,1,1,1,0,1,0,1,0,1,0,1,0,1,0,1,0,1,0,1,1,1,1,1],
+    ]);
+}
+
+#[test]
+fn test_rmqr_data_positions() {
+    // Column pairs start at width - 2 going up, as the right edge is a timing pattern
+    // In R7x43 the pairs at 41, 39 and 37 are all sub-finder, format information and corner patterns,
+    // so data starts in the pair at 35 going down, where only column 34 is free
+    let positions = blank_rmqr(1).data_positions();
+    assert_eq!(positions[..16],[(1,34),(2,34),(3,34),(4,34),(5,34),(5,33),(5,32),(4,33),(4,32),(3,33),(3,32),(2,33),(2,32),(1,33),(1,32),(1,31)]);
+    // In R17x139 the first pair is 137 and 136 above the format information, not 137 alone
+    let positions = blank_rmqr(32).data_positions();
+    assert!(positions.iter().all(|(_, x)| *x < 138));
+    assert_eq!(positions[..6],[(11,137),(10,137),(10,136),(9,137),(9,136),(8,137)]);
+}
+
+#[test]
+fn test_rmqr_codeword_count() {
+    use crate::rmqr::rmqr_blocks_table_get;
+    use crate::error_correction::ECLevel;
+    // Every module that is not part of a pattern holds a codeword, apart from up to 7 remainder bits
+    for version in 1..=32 {
+        let modules = blank_rmqr(version).data_positions().len();
+        for ec_level in [ECLevel::M, ECLevel::H] {
+            let (data, ec_per_block, blocks) = rmqr_blocks_table_get(version,ec_level).unwrap();
+            assert_eq!(modules / 8,data + ec_per_block * blocks,"Version {} {:?}",version,ec_level);
+        }
+    }
+}
+
+#[test]
+fn test_rmqr_format_information() {
+    use crate::patterns;
+    use crate::qr::QR;
+    use crate::error_correction::ECLevel;
+    let finder_mask = 0b011111101010110010;
+    let sub_finder_mask = 0b100000101001111011;
+    for version in [1, 11, 22, 32] {
+        for ec_level in [ECLevel::M, ECLevel::H] {
+            let mut qr = blank_rmqr(version);
+            qr.ec_level = ec_level;
+            qr.format_pattern();
+            let (height, width) = QR::get_rmqr_version_size(version);
+            let mut copies = [0, 0];
+            for i in 0..18 {
+                for (copy, (y, x)) in copies.iter_mut().zip(patterns::rmqr_format_positions(i,height,width)) {
+                    *copy |= (qr.bitmap.get(x,y) as usize) << i;
+                }
+            }
+            // Both copies start with the error correction bit and the version indicator
+            let data = (ec_level == ECLevel::H) as usize * 32 + version as usize - 1;
+            assert_eq!((copies[0] ^ finder_mask) >> 12,data);
+            assert_eq!((copies[1] ^ sub_finder_mask) >> 12,data);
+            assert_eq!(copies[0] ^ finder_mask,copies[1] ^ sub_finder_mask);
+        }
+    }
+}
+
+#[test]
+fn test_rmqr_patterns() {
+    let qr = blank_rmqr(20);
+    let (height, width) = (13, 77);
+    assert_eq!((qr.bitmap.height(), qr.bitmap.width()),(height, width));
+    let bitmap = qr.bitmap.to_rows();
+    // Finder pattern and separator
+    assert_eq!(bitmap[3][..8],[1,0,1,1,1,0,1,0]);
+    assert_eq!(bitmap[7][..8],[0;8]);
+    // Sub-finder pattern
+    assert_eq!(bitmap[height-3][width-5..],[1,0,1,0,1]);
+    assert_eq!(bitmap[height-4][width-5..],[1,0,0,0,1]);
+    // Corner patterns
+    assert_eq!(bitmap[height-1][..4],[1,1,1,0]);
+    assert_eq!(bitmap[height-2][..2],[1,0]);
+    assert_eq!([bitmap[0][width-2], bitmap[0][width-1], bitmap[1][width-2], bitmap[1][width-1]],[1,1,0,1]);
+    // Alignment patterns at columns 25 and 51, joined by vertical timing patterns
+    for x in [25, 51] {
+        assert_eq!(bitmap[1][x-1..=x+1],[1,0,1]);
+        assert_eq!(bitmap[height-2][x-1..=x+1],[1,0,1]);
+        let column: Vec<u8> = bitmap.iter().map(|row| row[x]).collect();
+        assert_eq!(column,[1,0,1,0,1,0,1,0,1,0,1,0,1]);
+        assert!((0..height).all(|y| qr.pattern_mask.get(x,y)));
+    }
+    // Timing patterns along the edges
+    assert_eq!(bitmap[0][8..14],[1,0,1,0,1,0]);
+    assert_eq!(bitmap[height-1][30..36],[1,0,1,0,1,0]);
+    assert_eq!(bitmap[8..11].iter().map(|row| row[0]).collect::<Vec<u8>>(),[1,0,1]);
+}
+
+#[test]
+fn test_rmqr_codewords_round_trip() {
+    use crate::builder::QrBuilder;
+    use crate::qr::SymbolKind;
+    use crate::error_correction::{self,ECLevel};
+    use crate::rmqr::rmqr_blocks_table_get;
+    let str = "Tray 0042 / cable A7";
+    for version in [5, 10, 22, 27, 32] {
+        for ec_level in [ECLevel::M, ECLevel::H] {
+            let mut qr = QrBuilder::new(str).kind(SymbolKind::Rmqr).ec_level(ec_level).version(version).build().unwrap();
+            // rMQR always uses mask 4, and masking again removes it
+            qr.mask(4);
+            let blocks = rmqr_blocks_table_get(version,ec_level).unwrap();
+            let (data, corrected) = error_correction::ec_decode_blocks(&qr.read_message(),blocks).unwrap();
+            assert_eq!(corrected,0);
+            // Byte mode indicator 011 followed by the length
+            let length_bits = [6, 6, 7, 7, 8][[5, 10, 22, 27, 32].iter().position(|x| *x == version).unwrap()];
+            let header = (data[0] as usize) << 8 | data[1] as usize;
+            assert_eq!(header >> (13 - length_bits),3 << length_bits | str.len());
+        }
+    }
+}
+
+#[test]
+fn test_rmqr_version_selection() {
+    use crate::builder::QrBuilder;
+    use crate::qr::{QR,Encoding,SymbolKind};
+    use crate::error::QrError;
+    use crate::error_correction::ECLevel;
+    use crate::rmqr::rmqr_version;
+    assert_eq!(rmqr_version(7,43),Some(1));
+    assert_eq!(rmqr_version(11,27),Some(11));
+    assert_eq!(rmqr_version(17,139),Some(32));
+    assert_eq!(rmqr_version(9,27),None);
+    // Smallest area that fits, R11x27 is smaller than R7x59
+    assert_eq!(QR::new_rmqr("0123456789012",Encoding::Numeric,ECLevel::M).unwrap().version,11);
+    assert_eq!(QR::new_rmqr("0123456789012",Encoding::Numeric,ECLevel::H).unwrap().version,17);
+    // Versions 1 to 5 are 7 modules tall
+    let rmqr = QrBuilder::new("https://example.com/tray/4711").kind(SymbolKind::Rmqr);
+    let qr = rmqr.clone().version_range(1,5).build().unwrap();
+    assert_eq!(qr.bitmap.height(),7);
+    assert_eq!(qr.version,5);
+    assert_eq!(rmqr.clone().ec_level(ECLevel::L).build().err(),Some(QrError::UnsupportedEcLevel(ECLevel::L)));
+    assert_eq!(rmqr.clone().mask(1).build().err(),Some(QrError::MaskOutOfRange(1)));
+    assert_eq!(rmqr.clone().version(33).build().err(),Some(QrError::VersionOutOfRange(33)));
+    assert_eq!(rmqr.clone().boost_ec(true).version(9).build().unwrap().ec_level,ECLevel::M);
+    assert_eq!(rmqr.clone().boost_ec(true).version(10).build().unwrap().ec_level,ECLevel::H);
+    assert_eq!(rmqr.clone().version(1).build().err(),Some(QrError::DataTooLong { needed_bits: 3 + 3 + 29 * 8, capacity: 48 }));
+    assert_eq!(QR::new_rmqr(&"1".repeat(362),Encoding::Numeric,ECLevel::M).err(),
+        Some(QrError::DataTooLong { needed_bits: 3 + 9 + 1207, capacity: 152 * 8 }));
+    assert_eq!(QR::new_rmqr(&"1".repeat(361),Encoding::Numeric,ECLevel::M).unwrap().version,32);
+}
+
+#[test]
+fn test_rmqr_rendering() {
+    use crate::builder::QrBuilder;
+    use crate::qr::SymbolKind;
+    use crate::svg::SvgOptions;
+    use crate::raster::RasterOptions;
+    let qr = QrBuilder::new("R9x59").kind(SymbolKind::Rmqr).version(7).build().unwrap();
+    let svg = qr.to_svg(&SvgOptions { quiet_zone: 2, module_size: 1.0, ..Default::default() });
+    assert!(svg.contains("viewBox=\"0 0 63 13\""),"{}",svg);
+    let image = qr.to_image_buffer(&RasterOptions { scale: 3, quiet_zone: 2, ..Default::default() });
+    assert_eq!(image.dimensions(),(63 * 3, 13 * 3));
+}
@@ -0,0 +1,64 @@
+// Tests the segment.rs module
+
+#[test]
+fn test_num_bits() {
+    use crate::segment::Segment;
+    use crate::qr::Encoding;
+    // Mode indicator + length field + data
+    assert_eq!(Segment::new("0123456789",Encoding::Numeric).num_bits(1).unwrap(),4 + 10 + 34);
+    assert_eq!(Segment::new("HELLO WORLD",Encoding::Alphanumeric).num_bits(1).unwrap(),4 + 9 + 61);
+    assert_eq!(Segment::new("HELLO WORLD",Encoding::Alphanumeric).num_bits(10).unwrap(),4 + 11 + 61);
+    assert_eq!(Segment::new("café",Encoding::Byte).num_bits(27).unwrap(),4 + 16 + 32);
+    assert_eq!(Segment::new("点茗",Encoding::Kanji).num_bits(1).unwrap(),4 + 8 + 26);
+    assert_eq!(Segment::new("é",Encoding::ECI).num_bits(1).unwrap(),12 + 4 + 8 + 16);
+    for enc in [Encoding::Numeric,Encoding::Alphanumeric,Encoding::Byte] {
+        let segment = Segment::new("123",enc);
+        assert_eq!(segment.num_bits(1).unwrap(),4 + crate::metadata::num_length_bits(1,enc).unwrap() as usize + segment.encode().unwrap().len());
+    }
+}
+
+#[test]
+fn test_optimize() {
+    use crate::segment::*;
+    use crate::qr::Encoding::*;
+    let segments = optimize_for_version("ORDER 0001234567890 for alice@example.com",1).unwrap();
+    assert_eq!(segments,vec![
+        Segment::new("ORDER ",Alphanumeric),
+        Segment::new("0001234567890",Numeric),
+        Segment::new(" for alice@example.com",Byte),
+    ]);
+    assert_eq!(segments.iter().map(|seg| seg.num_bits(1).unwrap()).sum::<usize>(),292);
+    assert_eq!(Segment::new("ORDER 0001234567890 for alice@example.com",Byte).num_bits(1).unwrap(),340);
+
+    assert_eq!(optimize_for_version("",1).unwrap(),vec![]);
+    assert_eq!(optimize_for_version("0123456789",1).unwrap(),vec![Segment::new("0123456789",Numeric)]);
+    assert_eq!(optimize_for_version("HELLO WORLD",40).unwrap(),vec![Segment::new("HELLO WORLD",Alphanumeric)]);
+    // Short digit runs are not worth a new header
+    assert_eq!(optimize_for_version("A1B2C3",1).unwrap(),vec![Segment::new("A1B2C3",Alphanumeric)]);
+    assert_eq!(optimize_for_version("https://example.com/track?id=1234567890123456",1).unwrap(),vec![
+        Segment::new("https://example.com/track?id=",Byte),
+        Segment::new("1234567890123456",Numeric),
+    ]);
+    assert_eq!(optimize_for_version("東京都ABC123456789",1).unwrap(),vec![
+        Segment::new("東京都",Kanji),
+        Segment::new("ABC",Alphanumeric),
+        Segment::new("123456789",Numeric),
+    ]);
+    // Latin-1 stays in byte mode when the rest fits in kanji mode
+    assert_eq!(optimize_for_version("日本 café",1).unwrap(),vec![Segment::new("日本",Kanji),Segment::new(" café",Byte)]);
+    // Otherwise the byte segments switch to UTF-8
+    assert_eq!(optimize_for_version("🎉1234567890123",1).unwrap(),vec![Segment::new("🎉",ECI),Segment::new("1234567890123",Numeric)]);
+}
+
+#[test]
+fn test_optimized_qr() {
+    use crate::qr::{QR,Encoding};
+    use crate::error_correction::ECLevel;
+    let str = "ORDER 0001234567890 for alice@example.com, ref 98765432109876543210";
+    assert_eq!(QR::new(str,Encoding::Byte,ECLevel::Q).unwrap().version,6);
+    assert_eq!(QR::new_optimized(str,ECLevel::Q).unwrap().version,5);
+    // Digits past the version 1-9 capacity are split with the version 10-26 length fields
+    let long_digits = "9".repeat(1500);
+    let segments = crate::segment::optimize(&long_digits,ECLevel::L).unwrap();
+    assert_eq!(QR::get_min_version_segments(&segments,ECLevel::L).unwrap().0,17);
+}
@@ -0,0 +1,105 @@
+// Tests the structured.rs module
+
+#[test]
+fn test_structured_header() {
+    use crate::structured::{StructuredAppend,parity};
+    use crate::segment::Segment;
+    use crate::qr::Encoding;
+    // Example from the specification, the third of four symbols
+    let header = StructuredAppend { index: 2, total: 4, parity: 0x39 }.header_bits();
+    assert_eq!(header,[0,0,1,1, 0,0,1,0, 0,0,1,1, 0,0,1,1,1,0,0,1]);
+    assert_eq!(parity(&[Segment::new("AB",Encoding::Alphanumeric)]),b'A' ^ b'B');
+    assert_eq!(parity(&[Segment::new("é",Encoding::Byte)]),0xE9);
+    // ECI segments use the UTF-8 bytes and kanji segments the Shift JIS bytes
+    assert_eq!(parity(&[Segment::new("€",Encoding::ECI)]),0xE2 ^ 0x82 ^ 0xAC);
+    assert_eq!(parity(&[Segment::new("点茗",Encoding::Kanji)]),0x93 ^ 0x5F ^ 0xE4 ^ 0xAA);
+    assert_eq!(parity(&[Segment::new("12",Encoding::Numeric),Segment::new("点",Encoding::Kanji)]),b'1' ^ b'2' ^ 0x93 ^ 0x5F);
+}
+
+#[test]
+fn test_structured_round_trip() {
+    use crate::builder::QrBuilder;
+    use crate::structured::reassemble;
+    use crate::error_correction::ECLevel;
+    let str = "Structured append splits one message across several symbols. 0123456789 ".repeat(6);
+    let symbols = QrBuilder::new(&str).ec_level(ECLevel::Q).version_range(1,5).build_structured_append().unwrap();
+    assert!(symbols.len() > 1);
+    assert!(symbols.iter().all(|qr| qr.version == symbols[0].version && qr.ec_level == ECLevel::Q));
+    let mut decoded: Vec<_> = symbols.iter().map(|qr| qr.decode().unwrap()).collect();
+    for (i, symbol) in decoded.iter().enumerate() {
+        let info = symbol.structured_append.unwrap();
+        assert_eq!((info.index as usize, info.total as usize),(i, symbols.len()));
+    }
+    decoded.reverse();
+    decoded.swap(0,1);
+    assert_eq!(reassemble(&decoded).unwrap(),str);
+}
+
+#[test]
+fn test_structured_kanji_round_trip() {
+    use crate::builder::QrBuilder;
+    use crate::structured::reassemble;
+    use crate::qr::Encoding;
+    // 点 is 0x935F and 茗 is 0xE4AA in Shift JIS, and pairs of the same character cancel out
+    let str = format!("{}点","点茗".repeat(40));
+    let symbols = QrBuilder::new(&str).encoding(Encoding::Kanji).version(3).build_structured_append().unwrap();
+    assert!(symbols.len() > 1);
+    let decoded: Vec<_> = symbols.iter().map(|qr| qr.decode().unwrap()).collect();
+    assert!(decoded.iter().all(|symbol| symbol.structured_append.unwrap().parity == 0x93 ^ 0x5F));
+    assert_eq!(reassemble(&decoded).unwrap(),str);
+}
+
+#[test]
+fn test_structured_version_choice() {
+    use crate::builder::QrBuilder;
+    use crate::structured::reassemble;
+    // Needs two version 40 symbols, which also fit the message at a smaller version
+    let str = "x".repeat(3000);
+    let symbols = QrBuilder::new(&str).build_structured_append().unwrap();
+    assert_eq!(symbols.len(),2);
+    let version = symbols[0].version;
+    assert!(version < 40 && symbols.iter().all(|qr| qr.version == version));
+    let smaller = QrBuilder::new(&str).version_range(1,version - 1).build_structured_append().unwrap();
+    assert!(smaller.len() > 2);
+    // Data that fits in one symbol gets no structured append header
+    let str = "SHORT MESSAGE";
+    let symbols = QrBuilder::new(str).build_structured_append().unwrap();
+    assert_eq!(symbols.len(),1);
+    assert_eq!(symbols[0].bitmap,QrBuilder::new(str).build().unwrap().bitmap);
+    let decoded = symbols[0].decode().unwrap();
+    assert_eq!((decoded.structured_append, decoded.text.as_str()),(None, str));
+    // A multi-kilobyte message across the maximum of 16 symbols
+    let str: String = (0..20000).map(|i| char::from(b'0' + (i * 7 % 10) as u8)).collect();
+    let symbols = QrBuilder::new(&str).version_range(1,20).build_structured_append().unwrap();
+    assert!(symbols.len() <= 16);
+    let decoded: Vec<_> = symbols.iter().map(|qr| qr.decode().unwrap()).collect();
+    assert_eq!(reassemble(&decoded).unwrap(),str);
+}
+
+#[test]
+fn test_structured_errors() {
+    use crate::builder::QrBuilder;
+    use crate::qr::{Encoding,SymbolKind};
+    use crate::structured::reassemble;
+    use crate::error::QrError;
+    let str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ".repeat(4);
+    let symbols = QrBuilder::new(&str).version(2).build_structured_append().unwrap();
+    let decoded: Vec<_> = symbols.iter().map(|qr| qr.decode().unwrap()).collect();
+    assert!(decoded.len() >= 3);
+    assert_eq!(reassemble(&[decoded[0].clone(), decoded[2].clone()]),Err(QrError::MissingSymbol(1)));
+    assert_eq!(reassemble(&[decoded[0].clone(), decoded[0].clone()]),Err(QrError::SequenceMismatch));
+    // Symbol from a different message
+    let other = QrBuilder::new(&str.to_lowercase()).version(2).build_structured_append().unwrap();
+    let mut mixed = decoded.clone();
+    mixed[1] = other[1].decode().unwrap();
+    assert_eq!(reassemble(&mixed),Err(QrError::SequenceMismatch));
+    // A plain qr code is not part of a sequence
+    let plain = QrBuilder::new("HELLO").build().unwrap().decode().unwrap();
+    assert_eq!(plain.structured_append,None);
+    assert_eq!(reassemble(&[plain]),Err(QrError::SequenceMismatch));
+
+    assert_eq!(QrBuilder::new(&"x".repeat(40000)).build_structured_append().err(),Some(QrError::TooManySymbols { needed: 18 }));
+    assert_eq!(QrBuilder::new("A").kind(SymbolKind::Micro).build_structured_append().err(),Some(QrError::UnsupportedSymbolKind(SymbolKind::Micro)));
+    assert_eq!(QrBuilder::new("Ab").encoding(Encoding::Alphanumeric).build_structured_append().err(),
+        Some(QrError::InvalidCharacter { ch: 'b', position: 1, mode: Encoding::Alphanumeric }));
+}
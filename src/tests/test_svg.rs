@@ -0,0 +1,56 @@
+// Tests the svg.rs module
+
+#[test]
+fn test_svg_path() {
+    use crate::svg::*;
+    use crate::grid::Grid;
+    let options = SvgOptions { quiet_zone: 0, module_size: 1.0, ..Default::default() };
+    let svg = to_svg(&Grid::from_rows(&[vec![1,1,0,1],vec![0,1,1,1]]),&options);
+    // Horizontal runs are merged into one rectangle
+    assert!(svg.contains("d=\"M0,0h2v1h-2zM3,0h1v1h-1zM1,1h3v1h-3z\""),"{}",svg);
+    assert!(svg.contains("width=\"4\" height=\"2\" viewBox=\"0 0 4 2\""),"{}",svg);
+    let svg = to_svg(&Grid::from_rows(&[vec![1,1,0,1],vec![0,1,1,1]]),&SvgOptions { module_size: 2.5, ..Default::default() });
+    assert!(svg.contains("d=\"M4,4h2v1h-2zM7,4h1v1h-1zM5,5h3v1h-3z\""),"{}",svg);
+    assert!(svg.contains("width=\"30\" height=\"25\" viewBox=\"0 0 12 10\""),"{}",svg);
+}
+
+#[test]
+fn test_svg_options() {
+    use crate::svg::*;
+    use crate::grid::Grid;
+    let bitmap = Grid::from_rows(&[vec![1,0],vec![0,1]]);
+    let svg = to_svg(&bitmap,&SvgOptions::default());
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+    assert!(svg.ends_with("</svg>\n"));
+    assert!(svg.contains("<rect width=\"10\" height=\"10\" fill=\"#ffffff\"/>"));
+    assert!(svg.contains("<path fill=\"#000000\""));
+    assert!(!svg.contains("<title>"));
+    // Transparent background has no rectangle
+    let options = SvgOptions {
+        foreground: String::from("rgb(0, 51, 102)"),
+        background: None,
+        title: Some(String::from("Tom & Jerry's <site>")),
+        description: Some(String::from("Link to \"example.com\"")),
+        ..Default::default()
+    };
+    let svg = to_svg(&bitmap,&options);
+    assert!(!svg.contains("<rect"));
+    assert!(svg.contains("<path fill=\"rgb(0, 51, 102)\""));
+    assert!(svg.contains("role=\"img\""));
+    assert!(svg.contains("<title>Tom &amp; Jerry&apos;s &lt;site&gt;</title>"));
+    assert!(svg.contains("<desc>Link to &quot;example.com&quot;</desc>"));
+}
+
+#[test]
+fn test_qr_svg() {
+    use crate::qr::{QR,Encoding};
+    use crate::error_correction::ECLevel;
+    let qr = QR::new("HELLO WORLD",Encoding::Alphanumeric,ECLevel::Q).unwrap();
+    let svg = qr.to_svg(&Default::default());
+    assert!(svg.contains("width=\"232\" height=\"232\" viewBox=\"0 0 29 29\""));
+    // Top row of the code starts with the 7 module wide finder pattern
+    assert!(svg.contains("d=\"M4,4h7v1h-7z"));
+    let num_dark = qr.bitmap.count_dark();
+    let drawn: usize = svg.split("h-").skip(1).map(|run| run.split('z').next().unwrap().parse::<usize>().unwrap()).sum();
+    assert_eq!(num_dark,drawn);
+}
@@ -0,0 +1,64 @@
+// Tests the text.rs module
+
+#[test]
+fn test_text_output() {
+    use crate::text::{to_text,TextOptions};
+    use crate::grid::Grid;
+    let bitmap = Grid::from_rows(&[vec![1,0],vec![0,1]]);
+    assert_eq!(to_text(&bitmap,&TextOptions { quiet_zone: 0, ..Default::default() }),"##  \n  ##\n");
+    let options = TextOptions { quiet_zone: 1, dark: String::from("1"), light: String::from("0") };
+    assert_eq!(to_text(&bitmap,&options),"0000\n0100\n0010\n0000\n");
+    let qr = crate::qr::QR::new("HELLO",crate::qr::Encoding::Alphanumeric,crate::error_correction::ECLevel::M).unwrap();
+    let text = qr.to_text(&TextOptions::default());
+    assert_eq!(text.lines().count(),21 + 8);
+    assert!(text.lines().all(|line| line.len() == (21 + 8) * 2));
+}
+
+#[test]
+fn test_terminal_half_blocks() {
+    use crate::text::{to_terminal,TerminalOptions};
+    use crate::grid::Grid;
+    let bitmap = Grid::from_rows(&[vec![1,0],vec![1,1],vec![0,1]]);
+    let options = TerminalOptions { quiet_zone: 0, ..Default::default() };
+    assert_eq!(to_terminal(&bitmap,&options),"█▄\n ▀\n");
+    // Inverted output draws the light modules and the quiet zone, but nothing past the last row
+    let options = TerminalOptions { quiet_zone: 1, invert: true, ..Default::default() };
+    assert_eq!(to_terminal(&bitmap,&options),"█▀██\n█▄ █\n▀▀▀▀\n");
+
+    // Reading the blocks back gives the code with its quiet zone
+    let qr = crate::builder::QrBuilder::new("https://example.com").build().unwrap();
+    let text = qr.to_terminal(&TerminalOptions::default());
+    let mut rows = Vec::new();
+    for line in text.lines() {
+        let (top, bottom): (Vec<u8>,Vec<u8>) = line.chars().map(|ch| match ch {
+            '█' => (1, 1),
+            '▀' => (1, 0),
+            '▄' => (0, 1),
+            _ => (0, 0),
+        }).unzip();
+        rows.push(top);
+        rows.push(bottom);
+    }
+    let size = qr.bitmap.height();
+    assert_eq!(rows.len(),size + 8 + 1);
+    assert!(rows[..4].iter().all(|row| row.iter().all(|el| *el == 0)));
+    let bitmap: Vec<Vec<u8>> = rows[4..4 + size].iter().map(|row| row[4..4 + size].to_vec()).collect();
+    assert_eq!(crate::decode::decode(&Grid::from_rows(&bitmap)).unwrap().text,"https://example.com");
+}
+
+#[test]
+fn test_terminal_ansi() {
+    use crate::text::{to_terminal,TerminalOptions,TerminalStyle};
+    use crate::grid::Grid;
+    let bitmap = Grid::from_rows(&[vec![1,1,0]]);
+    let options = TerminalOptions { style: TerminalStyle::Ansi, quiet_zone: 1, invert: false };
+    let text = to_terminal(&bitmap,&options);
+    let dark = "\x1b[40m";
+    let light = "\x1b[107m";
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(),3);
+    assert_eq!(lines[0],format!("{}{}\x1b[0m",light,"  ".repeat(5)));
+    assert_eq!(lines[1],format!("{light}  {dark}    {light}    \x1b[0m"));
+    let inverted = to_terminal(&bitmap,&TerminalOptions { invert: true, ..options });
+    assert_eq!(inverted.lines().nth(1).unwrap(),format!("{dark}  {light}    {dark}    \x1b[0m"));
+}
@@ -0,0 +1,139 @@
+use crate::grid::Grid;
+use crate::qr::QR;
+
+/// Options for rendering a qr code as plain text
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct TextOptions {
+    /// Width of the light border around the code in modules
+    pub quiet_zone: usize,
+    /// Text written for each dark module
+    pub dark: String,
+    /// Text written for each light module and the quiet zone
+    pub light: String,
+}
+
+impl Default for TextOptions {
+    fn default() -> TextOptions {
+        TextOptions {
+            quiet_zone: 4,
+            dark: String::from("##"),
+            light: String::from("  "),
+        }
+    }
+}
+
+impl QR {
+    /// Render qr code as lines of text
+    pub fn to_text(&self, options: &TextOptions) -> String {
+        return to_text(&self.bitmap,options);
+    }
+}
+
+/// Render a grid of modules as lines of text, one line per row ending in a newline
+/// The default options use two characters per module so the code keeps its proportions in a terminal
+pub fn to_text(bitmap: &Grid, options: &TextOptions) -> String {
+    let width = bitmap.width() + 2 * options.quiet_zone;
+    let blank_row = options.light.repeat(width) + "\n";
+    let mut out = blank_row.repeat(options.quiet_zone);
+    for row in bitmap.rows() {
+        out.push_str(&options.light.repeat(options.quiet_zone));
+        for dark in row {
+            out.push_str(if dark {&options.dark} else {&options.light});
+        }
+        out.push_str(&options.light.repeat(options.quiet_zone));
+        out.push('\n');
+    }
+    out.push_str(&blank_row.repeat(options.quiet_zone));
+    return out;
+}
+
+/// Ways of drawing a qr code in a terminal
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum TerminalStyle {
+    /// Unicode half blocks, with two rows of modules per line
+    HalfBlocks,
+    /// Spaces with ANSI background colors, two per module
+    Ansi,
+}
+
+/// Options for rendering a qr code in a terminal
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct TerminalOptions {
+    pub style: TerminalStyle,
+    /// Width of the light border around the code in modules
+    pub quiet_zone: usize,
+    /// Swap dark and light modules
+    /// Half blocks are drawn in the text color, so terminals with light text on a dark background need this
+    pub invert: bool,
+}
+
+impl Default for TerminalOptions {
+    fn default() -> TerminalOptions {
+        TerminalOptions {
+            style: TerminalStyle::HalfBlocks,
+            quiet_zone: 4,
+            invert: false,
+        }
+    }
+}
+
+/// ANSI escape sequences for a black and a bright white background, and for resetting the colors
+const ANSI_DARK: &str = "\x1b[40m";
+const ANSI_LIGHT: &str = "\x1b[107m";
+const ANSI_RESET: &str = "\x1b[0m";
+
+impl QR {
+    /// Render qr code as text that can be printed to a terminal
+    pub fn to_terminal(&self, options: &TerminalOptions) -> String {
+        return to_terminal(&self.bitmap,options);
+    }
+}
+
+/// Render a grid of modules as lines of text that can be printed to a terminal
+pub fn to_terminal(bitmap: &Grid, options: &TerminalOptions) -> String {
+    let quiet_zone = options.quiet_zone;
+    let height = bitmap.height() + 2 * quiet_zone;
+    let width = bitmap.width() + 2 * quiet_zone;
+    // Returns whether the module at (x, y) of the code with its quiet zone is drawn, None outside of it
+    let ink = |x: usize, y: usize| -> Option<bool> {
+        if y >= height {
+            return None;
+        }
+        let dark = (quiet_zone..quiet_zone + bitmap.height()).contains(&y)
+            && (quiet_zone..quiet_zone + bitmap.width()).contains(&x)
+            && bitmap.get(x - quiet_zone,y - quiet_zone);
+        return Some(dark != options.invert);
+    };
+    let mut out = String::new();
+    match options.style {
+        TerminalStyle::HalfBlocks => {
+            for y in (0..height).step_by(2) {
+                for x in 0..width {
+                    out.push(match (ink(x,y), ink(x,y + 1)) {
+                        (Some(true), Some(true)) => '\u{2588}',
+                        (Some(true), _) => '\u{2580}',
+                        (_, Some(true)) => '\u{2584}',
+                        _ => ' ',
+                    });
+                }
+                out.push('\n');
+            }
+        },
+        TerminalStyle::Ansi => {
+            for y in 0..height {
+                let mut current = None;
+                for x in 0..width {
+                    let dark = ink(x,y);
+                    if dark != current {
+                        out.push_str(if dark == Some(true) {ANSI_DARK} else {ANSI_LIGHT});
+                        current = dark;
+                    }
+                    out.push_str("  ");
+                }
+                out.push_str(ANSI_RESET);
+                out.push('\n');
+            }
+        },
+    }
+    return out;
+}
@@ -0,0 +1,23 @@
+#![allow(clippy::needless_return)]
+// Helpers shared by the tests of the binary
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// Runs the binary with args and stdin, returning its output
+pub fn run(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_qr_generator"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    return child.wait_with_output().unwrap();
+}
+
+/// Returns a path in the temporary directory that is unique to this test run
+pub fn temp_path(name: &str) -> std::path::PathBuf {
+    return std::env::temp_dir().join(format!("qr_generator_{}_{}",std::process::id(),name));
+}
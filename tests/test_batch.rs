@@ -0,0 +1,99 @@
+#![allow(clippy::needless_return)]
+// Tests the batch subcommand in batch.rs
+
+mod common;
+use common::{run, temp_path};
+
+#[test]
+fn test_batch_directory() {
+    let manifest = temp_path("manifest.csv");
+    let long = "A".repeat(100);
+    std::fs::write(&manifest,format!("payload,filename,ec,version,foreground,background\n\
+        HELLO,a.png,H,,,\n\
+        12345,tags/b.svg,,5,#ff0000,#00000000\n\
+        {},c.png,,1,,\n\
+        x,../d.png,,,,\n\
+        x,report.csv,,,,\n",long)).unwrap();
+    let out = temp_path("out");
+    let output = run(&["batch", manifest.to_str().unwrap(), "-o", out.to_str().unwrap(), "--scale", "2"], "");
+    // Rows that fail are reported without stopping the others
+    assert_eq!(output.status.code(),Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("3 of 5 codes"));
+    let image = image::open(out.join("a.png")).unwrap();
+    assert_eq!(image.width(),(21 + 8) * 2);
+    let svg = std::fs::read_to_string(out.join("tags/b.svg")).unwrap();
+    assert!(svg.contains("#ff0000") && svg.contains("viewBox=\"0 0 45 45\""));
+    assert!(!out.join("c.png").exists());
+
+    let report: Vec<csv::StringRecord> = csv::Reader::from_path(out.join("report.csv")).unwrap()
+        .records().map(|row| row.unwrap()).collect();
+    assert_eq!(report.len(),5);
+    assert_eq!((&report[0][0], &report[0][1], &report[0][2], &report[0][4]),("a.png", "1", "H", "Alphanumeric"));
+    assert_eq!((&report[0][5], &report[0][6]),("41", "72"));
+    assert_eq!((&report[1][1], &report[1][2], &report[1][4]),("5", "M", "Numeric"));
+    assert!(report[2][8].starts_with("Data too long"));
+    assert!(report[3][8].contains("relative path"));
+    // A row cannot overwrite the report
+    assert!(report[4][8].contains("used by the report"));
+    std::fs::remove_dir_all(out).unwrap();
+    std::fs::remove_file(manifest).unwrap();
+}
+
+#[test]
+fn test_batch_archives() {
+    use std::io::Read;
+    let manifest = "{\"payload\": \"hi\", \"filename\": \"x.txt\"}\n\n{\"payload\": \"yo\", \"filename\": \"y\", \"ec\": \"L\"}\n";
+    let zip_path = temp_path("codes.zip");
+    let output = run(&["batch", "-", "--manifest-format", "jsonl", "-o", zip_path.to_str().unwrap(), "--report", "report.json"], manifest);
+    assert!(output.status.success(),"{}",String::from_utf8_lossy(&output.stderr));
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(&zip_path).unwrap()).unwrap();
+    let names: Vec<&str> = archive.file_names().collect();
+    assert_eq!(names.len(),3);
+    let mut report = String::new();
+    archive.by_name("report.json").unwrap().read_to_string(&mut report).unwrap();
+    let report: serde_json::Value = serde_json::from_str(&report).unwrap();
+    assert_eq!(report[1]["filename"],"y");
+    assert_eq!(report[1]["ec_level"],"L");
+    assert_eq!(report[0]["error"],serde_json::Value::Null);
+    // Rows without a known extension use --format
+    let mut png = Vec::new();
+    archive.by_name("y").unwrap().read_to_end(&mut png).unwrap();
+    assert!(png.starts_with(b"\x89PNG"));
+
+    let tar_path = temp_path("codes.tar");
+    assert!(run(&["batch", "-", "--manifest-format", "jsonl", "-o", tar_path.to_str().unwrap(), "-f", "svg"], manifest).status.success());
+    let mut archive = tar::Archive::new(std::fs::File::open(&tar_path).unwrap());
+    let names: Vec<String> = archive.entries().unwrap()
+        .map(|entry| entry.unwrap().path().unwrap().to_str().unwrap().to_string()).collect();
+    assert_eq!(names,["x.txt", "y", "report.csv"]);
+    std::fs::remove_file(zip_path).unwrap();
+    std::fs::remove_file(tar_path).unwrap();
+
+    let output = run(&["batch", "-", "--manifest-format", "jsonl", "-o", temp_path("unused").to_str().unwrap()], "{\"payload\": 5}\n");
+    assert_eq!(output.status.code(),Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Line 1"));
+}
+
+#[test]
+fn test_batch_preview() {
+    use qr_generator::builder::QrBuilder;
+    use qr_generator::raster::RasterOptions;
+    use qr_generator::text::TerminalOptions;
+    let manifest = "payload,filename,ec,version,foreground,background\nHELLO,a.png,Q,,,\nx,/etc/b.png,,,,\n";
+    let output = run(&["preview", "-", "--protocol", "kitty", "--scale", "2"], manifest);
+    // The failing row is listed after the first code is drawn
+    assert_eq!(output.status.code(),Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let qr = QrBuilder::new("HELLO").ec_level(qr_generator::error_correction::ECLevel::Q).build().unwrap();
+    let kitty = qr.to_kitty(&RasterOptions { scale: 2, ..Default::default() }).unwrap();
+    let summary = format!("a.png: version 1-Q, mask {}, Alphanumeric, ",qr.mask_index);
+    assert!(stdout.starts_with(&summary));
+    assert!(stdout.contains(&format!("\n{}\n\n/etc/b.png: Filename",kitty)));
+
+    let output = run(&["preview", "-", "--protocol", "utf8", "--invert"], manifest);
+    let utf8 = qr.to_terminal(&TerminalOptions { invert: true, ..Default::default() });
+    assert!(String::from_utf8(output.stdout).unwrap().contains(&utf8));
+    // Sixel output of a single code goes to stdout by default
+    let output = run(&["HELLO", "--ec", "Q", "-f", "sixel", "--scale", "1"], "");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(),qr.to_sixel(&RasterOptions::default()) + "\n");
+}
@@ -0,0 +1,112 @@
+#![allow(clippy::needless_return)]
+// Tests the command line interface in main.rs
+
+mod common;
+use common::{run, temp_path};
+
+#[test]
+fn test_cli_text_output() {
+    use qr_generator::builder::QrBuilder;
+    use qr_generator::error_correction::ECLevel;
+    use qr_generator::text::TextOptions;
+    let output = run(&["HELLO WORLD", "--ec", "q", "--mask", "3", "--format", "txt", "--quiet-zone", "1"], "");
+    assert!(output.status.success());
+    let qr = QrBuilder::new("HELLO WORLD").ec_level(ECLevel::Q).mask(3).build().unwrap();
+    let expected = qr.to_text(&TextOptions { quiet_zone: 1, ..Default::default() });
+    assert_eq!(String::from_utf8(output.stdout).unwrap(),expected);
+    // The same payload from stdin, without its trailing newline
+    let output = run(&["--ec", "Q", "--mask", "3", "-f", "txt", "--quiet-zone", "1", "-o", "-"], "HELLO WORLD\n");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(),expected);
+}
+
+#[test]
+fn test_cli_files() {
+    let input = temp_path("input.txt");
+    std::fs::write(&input,"https://example.com/file").unwrap();
+    let png = temp_path("out.png");
+    let output = run(&["-i", input.to_str().unwrap(), "-o", png.to_str().unwrap(), "--scale", "3", "--quiet-zone", "2", "--version", "5"], "");
+    assert!(output.status.success(),"{}",String::from_utf8_lossy(&output.stderr));
+    let image = image::open(&png).unwrap();
+    assert_eq!((image.width(), image.height()),((37 + 4) * 3, (37 + 4) * 3));
+    // Format from the extension of the output
+    let svg = temp_path("out.svg");
+    assert!(run(&["HI", "-o", svg.to_str().unwrap()], "").status.success());
+    assert!(std::fs::read_to_string(&svg).unwrap().starts_with("<svg"));
+    for path in [input, png, svg] {
+        std::fs::remove_file(path).unwrap();
+    }
+    let output = run(&["-i", temp_path("missing.txt").to_str().unwrap(), "-f", "txt"], "");
+    assert_eq!(output.status.code(),Some(1));
+}
+
+#[test]
+fn test_cli_exit_codes() {
+    let output = run(&["ABC", "--encoding", "numeric", "-f", "txt"], "");
+    assert_eq!(output.status.code(),Some(4));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Invalid character 'A'"));
+    let output = run(&["-f", "txt", "--version", "1"], &"1".repeat(42));
+    assert_eq!(output.status.code(),Some(3));
+    assert_eq!(run(&["A", "--mask", "8"], "").status.code(),Some(2));
+    assert_eq!(run(&["A", "--ec", "X"], "").status.code(),Some(2));
+    assert_eq!(run(&["A", "-i", "file.txt"], "").status.code(),Some(2));
+}
+
+#[test]
+fn test_cli_terminal_output() {
+    use qr_generator::builder::QrBuilder;
+    use qr_generator::text::{TerminalOptions, TerminalStyle};
+    let qr = QrBuilder::new("HELLO").mask(2).build().unwrap();
+    let output = run(&["HELLO", "--mask", "2", "--format", "utf8"], "");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(),qr.to_terminal(&TerminalOptions::default()));
+    let output = run(&["HELLO", "--mask", "2", "-f", "ansi", "--invert", "--quiet-zone", "2"], "");
+    let options = TerminalOptions { style: TerminalStyle::Ansi, quiet_zone: 2, invert: true };
+    assert_eq!(String::from_utf8(output.stdout).unwrap(),qr.to_terminal(&options));
+}
+
+#[test]
+fn test_cli_print_output() {
+    let pdf_path = temp_path("code.pdf");
+    let output = run(&["HELLO", "--version", "1", "-o", pdf_path.to_str().unwrap(), "--module-size", "1mm", "--bleed", "3mm", "--quiet-zone", "2"], "");
+    assert!(output.status.success(),"{}",String::from_utf8_lossy(&output.stderr));
+    let document = lopdf::Document::load(&pdf_path).unwrap();
+    let page = *document.get_pages().get(&1).unwrap();
+    let page_dict = document.get_object(page).unwrap().as_dict().unwrap();
+    let trim: Vec<f32> = page_dict.get(b"TrimBox").unwrap().as_array().unwrap().iter().map(|el| el.as_float().unwrap()).collect();
+    let mm = 72.0 / 25.4;
+    assert!((trim[0] - 3.0 * mm).abs() < 0.01 && (trim[2] - 28.0 * mm).abs() < 0.01,"{:?}",trim);
+    let content = String::from_utf8(document.get_page_content(page).unwrap()).unwrap();
+    // The default white background is unprinted paper in CMYK
+    assert!(content.contains("0 0 0 0 k") && content.contains("0 0 0 1 k"));
+    std::fs::remove_file(pdf_path).unwrap();
+
+    let output = run(&["HELLO", "-f", "eps", "-o", "-", "--module-size", "2pt"], "");
+    let eps = String::from_utf8(output.stdout).unwrap();
+    assert!(eps.starts_with("%!PS-Adobe-3.0 EPSF-3.0\n%%BoundingBox: 0 0 58 58\n"));
+    assert_eq!(run(&["HELLO", "-f", "pdf", "--module-size", "2in"], "").status.code(),Some(2));
+}
+
+#[test]
+fn test_cli_mask_scorer() {
+    // qrcodegen chooses mask 0 for this symbol, which the legacy scorer does not
+    let args = ["quebec hotel mike", "--ec", "Q", "--version", "2", "--encoding", "byte", "-f", "txt", "-v"];
+    let output = run(&[&args[..], &["--mask-scorer", "iso"]].concat(), "");
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Mask index: 0"));
+    let output = run(&args, "");
+    assert!(!String::from_utf8(output.stderr).unwrap().contains("Mask index: 0"));
+    assert_eq!(run(&["A", "--mask", "1", "--mask-scorer", "iso"], "").status.code(),Some(2));
+}
+
+#[test]
+fn test_cli_mask_report() {
+    let output = run(&["HELLO WORLD", "--mask", "3", "-f", "txt", "-v"], "");
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Mask penalties (Legacy scorer):"),"{}",stderr);
+    // One row for each mask, with the given mask marked
+    let rows: Vec<&str> = stderr.lines().skip_while(|line| !line.contains("mask   line")).skip(1).take_while(|line| line.ends_with('%') || line.ends_with('*')).collect();
+    assert_eq!(rows.len(),8,"{}",stderr);
+    assert!(rows[3].trim_start().starts_with("3 ") && rows[3].ends_with(" *"),"{}",stderr);
+    assert_eq!(rows.iter().filter(|row| row.ends_with('*')).count(),1);
+}